@@ -4,11 +4,31 @@
 //! - Encryption: >10 MB/sec
 //! - Decryption: >10 MB/sec
 //! - Key derivation: <1 second
+//!
+//! Compare a change against a saved baseline with:
+//!
+//! ```bash
+//! cargo bench --bench encryption_benchmarks -- --save-baseline before
+//! cargo bench --bench encryption_benchmarks -- --baseline before
+//! ```
+//!
+//! The `triple_layer_batch` group only runs in parallel with
+//! `--features parallel`; without it the batch API is sequential.
+//!
+//! `triple_layer_throughput/*_legacy` re-runs the pre-cache code path (three
+//! ciphers built and three intermediate `Vec`s allocated per call) next to
+//! the current one, so the before/after is visible in a single run.
 
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use chakchat_crypto::encryption::{TripleLayerEncryption, KEY_SIZE};
-use chakchat_crypto::key_exchange::KeyPair;
+use chakchat_crypto::encryption::{
+    TripleLayerEncryption, AES_NONCE_SIZE, KEY_SIZE, XCHACHA_NONCE_SIZE,
+};
+use chakchat_crypto::key_exchange::{verify_signature, verify_signatures_batch, KeyPair};
+use chakchat_crypto::padding::{self, PaddingPolicy};
+#[cfg(feature = "pq")]
 use chakchat_crypto::post_quantum::PostQuantumKeyPair;
+use criterion::{
+    black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput,
+};
 
 fn benchmark_triple_layer_encryption(c: &mut Criterion) {
     let shared_secret = black_box([42u8; KEY_SIZE]);
@@ -57,6 +77,192 @@ fn benchmark_decryption(c: &mut Criterion) {
     });
 }
 
+/// Baseline: the per-call cipher construction `TripleLayerEncryption` used before caching
+mod legacy {
+    use super::*;
+    use aes_gcm::{Aes256Gcm, Nonce as AesNonce};
+    use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+    use chacha20poly1305::{ChaCha20Poly1305, Nonce as ChaChaNonce, XChaCha20Poly1305, XNonce};
+    use rand::RngCore;
+
+    pub struct Sealed {
+        nonces: ([u8; XCHACHA_NONCE_SIZE], [u8; AES_NONCE_SIZE], [u8; 12]),
+        ciphertext: Vec<u8>,
+    }
+
+    const KEYS: [[u8; KEY_SIZE]; 3] = [[1; KEY_SIZE], [2; KEY_SIZE], [3; KEY_SIZE]];
+
+    pub fn encrypt(plaintext: &[u8]) -> Sealed {
+        let mut rng = rand::thread_rng();
+        let mut nonces = ([0u8; XCHACHA_NONCE_SIZE], [0u8; AES_NONCE_SIZE], [0u8; 12]);
+        rng.fill_bytes(&mut nonces.0);
+        rng.fill_bytes(&mut nonces.1);
+        rng.fill_bytes(&mut nonces.2);
+
+        let padded = padding::pad(plaintext, PaddingPolicy::Padme, &mut rng).unwrap();
+        let layer1 = XChaCha20Poly1305::new(KEYS[0].as_ref().into())
+            .encrypt(
+                XNonce::from_slice(&nonces.0),
+                Payload {
+                    msg: &padded,
+                    aad: b"",
+                },
+            )
+            .unwrap();
+        let layer2 = Aes256Gcm::new(KEYS[1].as_ref().into())
+            .encrypt(AesNonce::from_slice(&nonces.1), layer1.as_slice())
+            .unwrap();
+        let ciphertext = ChaCha20Poly1305::new(KEYS[2].as_ref().into())
+            .encrypt(ChaChaNonce::from_slice(&nonces.2), layer2.as_slice())
+            .unwrap();
+        Sealed { nonces, ciphertext }
+    }
+
+    pub fn decrypt(sealed: &Sealed) -> Vec<u8> {
+        let layer2 = ChaCha20Poly1305::new(KEYS[2].as_ref().into())
+            .decrypt(
+                ChaChaNonce::from_slice(&sealed.nonces.2),
+                sealed.ciphertext.as_slice(),
+            )
+            .unwrap();
+        let layer1 = Aes256Gcm::new(KEYS[1].as_ref().into())
+            .decrypt(AesNonce::from_slice(&sealed.nonces.1), layer2.as_slice())
+            .unwrap();
+        let padded = XChaCha20Poly1305::new(KEYS[0].as_ref().into())
+            .decrypt(
+                XNonce::from_slice(&sealed.nonces.0),
+                Payload {
+                    msg: &layer1,
+                    aad: b"",
+                },
+            )
+            .unwrap();
+        padding::unpad(padded).unwrap()
+    }
+}
+
+fn benchmark_throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("triple_layer_throughput");
+    group.sample_size(20);
+
+    for size in [1024usize, 64 * 1024, 10 * 1024 * 1024] {
+        let plaintext = vec![0u8; size];
+        let mut enc = TripleLayerEncryption::new(&[42u8; KEY_SIZE]).unwrap();
+        let encrypted = enc.encrypt(&plaintext).unwrap();
+        group.throughput(Throughput::Bytes(size as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("encrypt", size),
+            &plaintext,
+            |b, plaintext| b.iter(|| enc.encrypt(black_box(plaintext)).unwrap()),
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("decrypt", size),
+            &encrypted,
+            |b, encrypted| b.iter(|| enc.decrypt(black_box(encrypted)).unwrap()),
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("encrypt_legacy", size),
+            &plaintext,
+            |b, plaintext| b.iter(|| legacy::encrypt(black_box(plaintext))),
+        );
+
+        let sealed = legacy::encrypt(&plaintext);
+        group.bench_with_input(
+            BenchmarkId::new("decrypt_legacy", size),
+            &sealed,
+            |b, sealed| b.iter(|| legacy::decrypt(black_box(sealed))),
+        );
+
+        // Buffer setup is outside the timed region; only the cipher work is measured
+        let capacity = enc.sealed_capacity(size);
+        group.bench_with_input(
+            BenchmarkId::new("encrypt_in_place", size),
+            &plaintext,
+            |b, plaintext| {
+                b.iter_batched(
+                    || {
+                        let mut buffer = Vec::with_capacity(capacity);
+                        buffer.extend_from_slice(plaintext);
+                        buffer
+                    },
+                    |buffer| enc.encrypt_in_place(buffer).unwrap(),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("decrypt_in_place", size),
+            &encrypted,
+            |b, encrypted| {
+                b.iter_batched(
+                    || encrypted.clone(),
+                    |message| enc.decrypt_in_place(message).unwrap(),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn benchmark_batch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("triple_layer_batch");
+    group.sample_size(20);
+
+    // Group fan-out: many small messages under one session
+    for count in [100usize, 1000, 5000] {
+        let plaintexts: Vec<Vec<u8>> = (0..count).map(|i| vec![i as u8; 256]).collect();
+        let mut enc = TripleLayerEncryption::new(&[42u8; KEY_SIZE]).unwrap();
+        let encrypted = enc.encrypt_batch(&plaintexts).unwrap();
+        group.throughput(Throughput::Elements(count as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("encrypt_loop", count),
+            &plaintexts,
+            |b, plaintexts| {
+                b.iter(|| {
+                    plaintexts
+                        .iter()
+                        .map(|plaintext| enc.encrypt(plaintext).unwrap())
+                        .collect::<Vec<_>>()
+                })
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("encrypt_batch", count),
+            &plaintexts,
+            |b, plaintexts| b.iter(|| enc.encrypt_batch(black_box(plaintexts)).unwrap()),
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("decrypt_loop", count),
+            &encrypted,
+            |b, encrypted| {
+                b.iter(|| {
+                    encrypted
+                        .iter()
+                        .map(|message| enc.decrypt(message).unwrap())
+                        .collect::<Vec<_>>()
+                })
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("decrypt_batch", count),
+            &encrypted,
+            |b, encrypted| b.iter(|| enc.decrypt_batch(black_box(encrypted)).unwrap()),
+        );
+    }
+
+    group.finish();
+}
+
 fn benchmark_key_agreement(c: &mut Criterion) {
     c.bench_function("ecdh_key_agreement", |b| {
         b.iter_batched(
@@ -75,22 +281,52 @@ fn benchmark_key_agreement(c: &mut Criterion) {
     });
 }
 
+fn benchmark_signature_verification(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ed25519_verify");
+
+    for size in [16usize, 64, 256] {
+        let signed: Vec<_> = (0..size)
+            .map(|i| {
+                let keypair = KeyPair::generate().unwrap();
+                let data = format!("tree head {}", i).into_bytes();
+                let signature = keypair.sign(&data).unwrap();
+                (keypair.verifying_key, data, signature)
+            })
+            .collect();
+        let items: Vec<_> = signed
+            .iter()
+            .map(|(vk, data, sig)| (vk, data.as_slice(), sig))
+            .collect();
+
+        group.bench_with_input(BenchmarkId::new("loop", size), &items, |b, items| {
+            b.iter(|| {
+                for (vk, data, sig) in items {
+                    verify_signature(vk, data, sig).unwrap();
+                }
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("batch", size), &items, |b, items| {
+            b.iter(|| verify_signatures_batch(black_box(items)).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+#[cfg(not(feature = "pq"))]
+fn benchmark_post_quantum(_c: &mut Criterion) {}
+
+#[cfg(feature = "pq")]
 fn benchmark_post_quantum(c: &mut Criterion) {
     c.bench_function("kyber1024_keypair_generation", |b| {
-        b.iter(|| {
-            PostQuantumKeyPair::generate().unwrap()
-        })
+        b.iter(|| PostQuantumKeyPair::generate().unwrap())
     });
 
     c.bench_function("kyber1024_encapsulate", |b| {
         b.iter_batched(
-            || {
-                let kp = black_box(PostQuantumKeyPair::generate().unwrap());
-                kp
-            },
-            |kp| {
-                PostQuantumKeyPair::encapsulate(kp.public_key_bytes()).unwrap()
-            },
+            || black_box(PostQuantumKeyPair::generate().unwrap()),
+            |kp| PostQuantumKeyPair::encapsulate(kp.public_key_bytes()).unwrap(),
             criterion::BatchSize::SmallInput,
         )
     });
@@ -100,7 +336,10 @@ criterion_group!(
     benches,
     benchmark_triple_layer_encryption,
     benchmark_decryption,
+    benchmark_throughput,
+    benchmark_batch,
     benchmark_key_agreement,
+    benchmark_signature_verification,
     benchmark_post_quantum
 );
 criterion_main!(benches);
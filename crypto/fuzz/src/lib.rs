@@ -0,0 +1,206 @@
+//! Fuzz Targets
+//!
+//! One function per untrusted-input entry point. Each takes raw bytes, parses
+//! them the way a peer's data would be parsed and runs every check on the
+//! result; errors are expected, panics are bugs.
+//!
+//! The same functions back the cargo-fuzz binaries in `fuzz_targets/` and the
+//! seed replay in `tests/fuzz_seeds.rs`, which also builds the fixed keys and
+//! seeds with `fixtures`.
+
+use bincode::Options;
+use chakchat_crypto::attachment::{decrypt_attachment, AttachmentPointer};
+use chakchat_crypto::device::{valid_devices, verify_chain, DeviceCertificate, RevocationList};
+use chakchat_crypto::padding::unpad;
+use chakchat_crypto::stream;
+use chakchat_crypto::transparency::{
+    verify_consistency, verify_inclusion, Hash, InclusionProof, LogEntry, SignedTreeHead,
+    TransparencyVerifier,
+};
+use chakchat_crypto::{EncryptedMessage, KeyPair, SignedEnvelope};
+
+/// Fixed keys and parameters shared by the targets and the seed generator
+pub mod fixtures {
+    use chakchat_crypto::clock::FixedClock;
+    use chakchat_crypto::expiring::ExpiringKeyStore;
+    use chakchat_crypto::{KeyPair, TripleLayerEncryption};
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
+
+    /// Current time for every target
+    pub const NOW: i64 = 1_700_000_000_000;
+
+    /// Session secret for `encrypted_message` and `signed_envelope`
+    pub const SESSION_SECRET: [u8; 32] = [0x42; 32];
+
+    /// Key for `stream`
+    pub const STREAM_KEY: [u8; 32] = [0x24; 32];
+
+    /// Message ID sealed in the `expiring` store
+    pub const EXPIRING_ID: u64 = 7;
+
+    /// Sender, recipient and context for `signed_envelope`
+    pub const SENDER: &str = "alice";
+    /// Recipient for `signed_envelope`
+    pub const RECIPIENT: &str = "bob";
+    /// Context for `signed_envelope`
+    pub const CONTEXT: &str = "chat";
+
+    /// Fixed clock
+    pub fn clock() -> FixedClock {
+        FixedClock(NOW)
+    }
+
+    /// Session cipher keyed with `SESSION_SECRET`
+    pub fn session() -> TripleLayerEncryption {
+        TripleLayerEncryption::new(&SESSION_SECRET).expect("fixed secret is valid")
+    }
+
+    /// Identity key that signs envelopes, certificates and tree heads
+    pub fn identity() -> KeyPair {
+        KeyPair::from_bytes(&[1u8; 32], &[2u8; 32]).expect("fixed key is valid")
+    }
+
+    /// Store holding one key under `EXPIRING_ID`, plus the blob sealed with it
+    pub fn expiring_store() -> (ExpiringKeyStore, Vec<u8>) {
+        let mut store = ExpiringKeyStore::new();
+        let blob = store
+            .seal(
+                EXPIRING_ID,
+                b"disappearing",
+                NOW + 60_000,
+                &mut ChaCha20Rng::seed_from_u64(EXPIRING_ID),
+                &clock(),
+            )
+            .expect("expiry is in the future");
+        (store, blob)
+    }
+}
+
+/// `bincode(EncryptedMessage)`, decrypted with the fixed session
+pub fn encrypted_message(data: &[u8]) {
+    if let Ok(message) = bincode::deserialize::<EncryptedMessage>(data) {
+        let _ = fixtures::session().decrypt_with(&message, &fixtures::clock());
+    }
+}
+
+/// `bincode(KeyPair)`, then every operation that touches the private halves
+pub fn key_pair(data: &[u8]) {
+    if let Ok(keys) = bincode::deserialize::<KeyPair>(data) {
+        let _ = keys.sign(data);
+        let _ = keys.compute_shared_secret(&keys.public_key);
+        let _ = keys.get_private_key();
+        let _ = bincode::serialize(&keys);
+    }
+}
+
+/// `bincode(SignedEnvelope)`, opened with the fixed session and sender key
+pub fn signed_envelope(data: &[u8]) {
+    if let Ok(envelope) = bincode::deserialize::<SignedEnvelope>(data) {
+        let _ = envelope.open_with(
+            &fixtures::identity().verifying_key,
+            fixtures::RECIPIENT,
+            fixtures::CONTEXT,
+            &mut fixtures::session(),
+            &fixtures::clock(),
+        );
+    }
+}
+
+/// Chunked stream under the fixed stream key
+pub fn stream(data: &[u8]) {
+    let _ = stream::open(&fixtures::STREAM_KEY, data);
+}
+
+/// Padded plaintext
+pub fn padding(data: &[u8]) {
+    if let Ok(plaintext) = unpad(data.to_vec()) {
+        assert!(plaintext.len() < data.len(), "unpad must remove the marker");
+    }
+}
+
+/// `bincode(AttachmentPointer) || ciphertext`
+pub fn attachment(data: &[u8]) {
+    // Reader-based decoding trusts length prefixes unless given a limit
+    let options = bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(data.len() as u64);
+
+    let mut rest = data;
+    if let Ok(pointer) = options.deserialize_from::<_, AttachmentPointer>(&mut rest) {
+        if let Ok(plaintext) = decrypt_attachment(rest, &pointer) {
+            assert_eq!(plaintext.len() as u64, pointer.size);
+        }
+    }
+}
+
+/// Blob for the message sealed in the fixed expiring store
+pub fn expiring(data: &[u8]) {
+    let (mut store, _) = fixtures::expiring_store();
+    let _ = store.open(fixtures::EXPIRING_ID, data, &fixtures::clock());
+}
+
+/// Consistency proofs, inclusion proofs and tree-head updates
+pub fn transparency(data: &[u8]) {
+    type Consistency = (u64, u64, Hash, Hash, Vec<Hash>);
+    if let Ok((old_size, new_size, old_root, new_root, proof)) =
+        bincode::deserialize::<Consistency>(data)
+    {
+        let _ = verify_consistency(old_size, new_size, &old_root, &new_root, &proof);
+    }
+
+    if let Ok((entry, proof, root)) = bincode::deserialize::<(LogEntry, InclusionProof, Hash)>(data)
+    {
+        let _ = verify_inclusion(
+            &entry.leaf_hash(),
+            proof.leaf_index,
+            proof.tree_size,
+            &proof.path,
+            &root,
+        );
+    }
+
+    type Update = (SignedTreeHead, Vec<Hash>, SignedTreeHead, Vec<Hash>);
+    if let Ok((first, first_proof, second, second_proof)) = bincode::deserialize::<Update>(data) {
+        let mut verifier = TransparencyVerifier::new(fixtures::identity().verifying_key);
+        if verifier.update(first, &first_proof).is_ok() {
+            let _ = verifier.update(second, &second_proof);
+        }
+    }
+}
+
+/// `bincode((Vec<DeviceCertificate>, Option<RevocationList>))`
+pub fn device(data: &[u8]) {
+    type Certificates = (Vec<DeviceCertificate>, Option<RevocationList>);
+    if let Ok((certificates, revocations)) = bincode::deserialize::<Certificates>(data) {
+        let identity_key = fixtures::identity().verifying_key;
+        let clock = fixtures::clock();
+        let _ = verify_chain(&certificates, &identity_key, revocations.as_ref(), &clock);
+        let _ = valid_devices(
+            fixtures::SENDER,
+            &identity_key,
+            &certificates,
+            revocations.as_ref(),
+            &clock,
+        );
+    }
+}
+
+/// `bincode(PostQuantumKeyPair)` decapsulating, plus raw public keys and ciphertexts
+#[cfg(feature = "pq")]
+pub fn post_quantum(data: &[u8]) {
+    use chakchat_crypto::post_quantum::{PostQuantumKeyPair, KYBER_CT_SIZE};
+    use std::sync::OnceLock;
+
+    static KEYS: OnceLock<PostQuantumKeyPair> = OnceLock::new();
+    let keys = KEYS.get_or_init(|| PostQuantumKeyPair::generate().expect("keygen succeeds"));
+
+    let _ = PostQuantumKeyPair::encapsulate(data);
+    let _ = keys.decapsulate(data);
+
+    if let Ok(parsed) = bincode::deserialize::<PostQuantumKeyPair>(data) {
+        let _ = parsed.decapsulate(&[0u8; KYBER_CT_SIZE]);
+        let _ = PostQuantumKeyPair::encapsulate(parsed.public_key_bytes());
+    }
+}
@@ -0,0 +1,209 @@
+//! Attachment Encryption
+//!
+//! Files are encrypted client-side before they reach `file-storage-service`:
+//! 1. A random 256-bit key is generated per file
+//! 2. The file is sealed with the streaming AEAD (`stream` module)
+//! 3. The SHA-256 of the ciphertext is recorded as the digest
+//!
+//! The resulting `AttachmentPointer` travels inside the (already encrypted)
+//! chat message. The storage service only ever sees an opaque
+//! `application/octet-stream` blob.
+
+use crate::encryption::KEY_SIZE;
+use crate::utils::{constant_time_compare, hash_sha256};
+use crate::{stream, CryptoError, CryptoResult};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+use rand_core::CryptoRngCore;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+/// MIME type used when uploading the encrypted blob
+pub const UPLOAD_MIME_TYPE: &str = "application/octet-stream";
+
+/// Maximum thumbnail size carried inside a pointer (64 KiB)
+pub const MAX_THUMBNAIL_SIZE: usize = 64 * 1024;
+
+/// Everything a recipient needs to download and decrypt an attachment
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Zeroize)]
+#[zeroize(drop)]
+pub struct AttachmentPointer {
+    /// File ID assigned by file-storage-service after upload
+    pub file_id: Option<String>,
+
+    /// Per-file stream key
+    pub key: [u8; KEY_SIZE],
+
+    /// SHA-256 of the encrypted blob
+    pub digest: [u8; 32],
+
+    /// Plaintext size in bytes
+    pub size: u64,
+
+    /// Real MIME type of the file (never sent to the server)
+    pub content_type: String,
+
+    /// Optional small preview image
+    pub thumbnail: Option<Vec<u8>>,
+}
+
+/// Encrypted attachment ready for upload
+#[derive(Clone)]
+pub struct EncryptedAttachment {
+    /// Encrypted blob to upload
+    pub ciphertext: Vec<u8>,
+
+    /// Pointer to embed in the chat message
+    pub pointer: AttachmentPointer,
+}
+
+impl fmt::Debug for AttachmentPointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AttachmentPointer")
+            .field("file_id", &self.file_id)
+            .field("key", &"[REDACTED]")
+            .field("digest", &self.digest)
+            .field("size", &self.size)
+            .field("content_type", &self.content_type)
+            .field("thumbnail", &self.thumbnail.as_ref().map(|t| t.len()))
+            .finish()
+    }
+}
+
+impl AttachmentPointer {
+    /// Record the file ID returned by file-storage-service
+    pub fn with_file_id(mut self, file_id: impl Into<String>) -> Self {
+        self.file_id = Some(file_id.into());
+        self
+    }
+}
+
+/// Encrypt a file for upload
+///
+/// # Arguments
+/// * `plaintext` - File contents
+/// * `content_type` - Real MIME type, kept inside the pointer
+/// * `thumbnail` - Optional preview image
+#[cfg(feature = "std")]
+pub fn encrypt_attachment(
+    plaintext: &[u8],
+    content_type: &str,
+    thumbnail: Option<Vec<u8>>,
+) -> CryptoResult<EncryptedAttachment> {
+    encrypt_attachment_with_rng(plaintext, content_type, thumbnail, &mut rand::thread_rng())
+}
+
+/// Encrypt a file for upload using a caller-supplied RNG
+pub fn encrypt_attachment_with_rng(
+    plaintext: &[u8],
+    content_type: &str,
+    thumbnail: Option<Vec<u8>>,
+    rng: &mut impl CryptoRngCore,
+) -> CryptoResult<EncryptedAttachment> {
+    if thumbnail
+        .as_ref()
+        .is_some_and(|t| t.len() > MAX_THUMBNAIL_SIZE)
+    {
+        return Err(CryptoError::EncryptionError(
+            "Thumbnail exceeds maximum size".to_string(),
+        ));
+    }
+
+    let mut key = [0u8; KEY_SIZE];
+    rng.fill_bytes(&mut key);
+
+    let ciphertext = stream::seal(&key, plaintext, rng)?;
+    let digest = hash_sha256(&ciphertext);
+
+    let pointer = AttachmentPointer {
+        file_id: None,
+        key,
+        digest,
+        size: plaintext.len() as u64,
+        content_type: content_type.to_string(),
+        thumbnail,
+    };
+    key.zeroize();
+
+    Ok(EncryptedAttachment {
+        ciphertext,
+        pointer,
+    })
+}
+
+/// Verify and decrypt a downloaded attachment
+///
+/// The digest is checked before any decryption is attempted, so a corrupted
+/// or substituted download is rejected without touching the key.
+pub fn decrypt_attachment(ciphertext: &[u8], pointer: &AttachmentPointer) -> CryptoResult<Vec<u8>> {
+    let digest = hash_sha256(ciphertext);
+    if !constant_time_compare(&digest, &pointer.digest) {
+        return Err(CryptoError::DigestMismatch);
+    }
+
+    let plaintext = stream::open(&pointer.key, ciphertext)?;
+
+    if plaintext.len() as u64 != pointer.size {
+        return Err(CryptoError::DecryptionError(
+            "Attachment size mismatch".to_string(),
+        ));
+    }
+
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attachment_roundtrip() {
+        let file = vec![0x42u8; 200 * 1024];
+        let encrypted = encrypt_attachment(&file, "image/jpeg", Some(vec![1, 2, 3])).unwrap();
+
+        assert_eq!(encrypted.pointer.size, file.len() as u64);
+        assert_eq!(encrypted.pointer.digest, hash_sha256(&encrypted.ciphertext));
+        assert_ne!(encrypted.ciphertext[..file.len()], file[..]);
+
+        let pointer = encrypted
+            .pointer
+            .clone()
+            .with_file_id("6b3c1a52-7f0e-4c3f-9d2a-0c1e5f8b9a11");
+        let decrypted = decrypt_attachment(&encrypted.ciphertext, &pointer).unwrap();
+        assert_eq!(decrypted, file);
+    }
+
+    #[test]
+    fn test_digest_checked_before_decryption() {
+        let encrypted =
+            encrypt_attachment(b"report.pdf contents", "application/pdf", None).unwrap();
+
+        let mut corrupted = encrypted.ciphertext.clone();
+        corrupted[0] ^= 0xFF;
+        assert!(matches!(
+            decrypt_attachment(&corrupted, &encrypted.pointer),
+            Err(CryptoError::DigestMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_wrong_key_fails() {
+        let encrypted = encrypt_attachment(b"voice note", "audio/ogg", None).unwrap();
+
+        let mut pointer = encrypted.pointer.clone();
+        pointer.key = [0u8; KEY_SIZE];
+        assert!(matches!(
+            decrypt_attachment(&encrypted.ciphertext, &pointer),
+            Err(CryptoError::DecryptionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_oversized_thumbnail_rejected() {
+        let thumbnail = vec![0u8; MAX_THUMBNAIL_SIZE + 1];
+        assert!(encrypt_attachment(b"video", "video/mp4", Some(thumbnail)).is_err());
+    }
+}
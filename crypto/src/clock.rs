@@ -0,0 +1,48 @@
+//! Time Sources
+//!
+//! Abstracts "now" so message timestamps can be injected.
+//! Tests and `no_std` targets use [`FixedClock`]; std builds default to [`SystemClock`].
+
+/// Source of the current time
+pub trait Clock {
+    /// Current time as Unix milliseconds
+    fn now_millis(&self) -> i64;
+}
+
+/// Local system clock
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now_millis(&self) -> i64 {
+        chrono::Local::now().timestamp_millis()
+    }
+}
+
+/// Clock frozen at a fixed instant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock(pub i64);
+
+impl Clock for FixedClock {
+    fn now_millis(&self) -> i64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_clock() {
+        let clock = FixedClock(1_700_000_000_000);
+        assert_eq!(clock.now_millis(), 1_700_000_000_000);
+    }
+
+    #[test]
+    fn test_system_clock_is_after_fixed_point() {
+        assert!(SystemClock.now_millis() > 1_700_000_000_000);
+    }
+}
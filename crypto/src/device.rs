@@ -0,0 +1,623 @@
+//! Device Certificates
+//!
+//! Each device holds its own `KeyPair`. The user's identity key signs a
+//! `DeviceCertificate` for it, so private keys never leave the device they
+//! were generated on. A device with the `LINK_DEVICES` capability can certify
+//! further devices, which gives a chain back to the identity key.
+//!
+//! Devices are revoked by a `RevocationList` signed with the identity key.
+//! Revoking a linking device also invalidates every device it certified.
+//! A received list replaces the stored one only through
+//! `RevocationList::verify_update`, which rejects replays of older lists.
+
+use crate::clock::Clock;
+use crate::key_exchange::{verify_signature, SIGNATURE_SIZE};
+use crate::keystore::KeyStore;
+use crate::{CryptoError, CryptoResult};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use serde::{Deserialize, Serialize};
+
+/// Domain separator for device certificate signatures
+const CERTIFICATE_DOMAIN: &[u8] = b"chakchat_device_certificate_v1";
+
+/// Domain separator for revocation list signatures
+const REVOCATION_DOMAIN: &[u8] = b"chakchat_device_revocation_v1";
+
+/// Longest accepted certificate chain (identity -> device -> ... -> device)
+pub const MAX_CHAIN_DEPTH: usize = 4;
+
+/// What a device is allowed to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct DeviceCapabilities(pub u32);
+
+impl DeviceCapabilities {
+    /// Send messages
+    pub const SEND: Self = DeviceCapabilities(1 << 0);
+
+    /// Receive and decrypt messages
+    pub const RECEIVE: Self = DeviceCapabilities(1 << 1);
+
+    /// Certify additional devices
+    pub const LINK_DEVICES: Self = DeviceCapabilities(1 << 2);
+
+    /// Create and restore backups
+    pub const BACKUP: Self = DeviceCapabilities(1 << 3);
+
+    /// Send and receive
+    pub const MESSAGING: Self = DeviceCapabilities(Self::SEND.0 | Self::RECEIVE.0);
+
+    /// Whether every capability in `other` is present
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl core::ops::BitOr for DeviceCapabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        DeviceCapabilities(self.0 | rhs.0)
+    }
+}
+
+/// Device key signed by the user's identity key (or a linking device)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceCertificate {
+    /// Owning user
+    pub user_id: String,
+
+    /// Device identifier, unique per user
+    pub device_id: String,
+
+    /// Device X25519 public key
+    #[serde(with = "crate::encoding::base64_array")]
+    pub public_key: [u8; 32],
+
+    /// Device Ed25519 verifying key
+    #[serde(with = "crate::encoding::base64_array")]
+    pub verifying_key: [u8; 32],
+
+    /// Ed25519 key that signed this certificate
+    #[serde(with = "crate::encoding::base64_array")]
+    pub issuer_key: [u8; 32],
+
+    /// Creation time (Unix milliseconds)
+    pub created_at: i64,
+
+    /// Expiry time (Unix milliseconds)
+    pub expires_at: i64,
+
+    /// Granted capabilities
+    pub capabilities: DeviceCapabilities,
+
+    /// Issuer signature
+    #[serde(with = "crate::encoding::base64_array")]
+    pub signature: [u8; SIGNATURE_SIZE],
+}
+
+/// Signed list of revoked device IDs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevocationList {
+    /// Owning user
+    pub user_id: String,
+
+    /// Monotonic version; clients keep the highest one they have seen
+    ///
+    /// `verify_update` refuses a list whose sequence is not above the stored
+    /// one, so an old list cannot be replayed to un-revoke a device.
+    pub sequence: u64,
+
+    /// Issue time (Unix milliseconds)
+    pub issued_at: i64,
+
+    /// Revoked device IDs
+    pub revoked: Vec<String>,
+
+    /// Identity key signature
+    #[serde(with = "crate::encoding::base64_array")]
+    pub signature: [u8; SIGNATURE_SIZE],
+}
+
+impl DeviceCertificate {
+    /// Certify a device key
+    ///
+    /// # Arguments
+    /// * `issuer` - Identity key, or a device holding `LINK_DEVICES`
+    /// * `user_id` - Owning user
+    /// * `device_id` - Identifier for the new device
+    /// * `device` - Device's own key; only its public keys are read
+    /// * `capabilities` - Granted capabilities
+    /// * `expires_at` - Expiry (Unix milliseconds)
+    /// * `clock` - Source of the creation time
+    #[allow(clippy::too_many_arguments)]
+    pub fn issue(
+        issuer: &dyn KeyStore,
+        user_id: &str,
+        device_id: &str,
+        device: &dyn KeyStore,
+        capabilities: DeviceCapabilities,
+        expires_at: i64,
+        clock: &dyn Clock,
+    ) -> CryptoResult<Self> {
+        let created_at = clock.now_millis();
+        let device = device.public_keys()?;
+        if expires_at <= created_at {
+            return Err(CryptoError::InvalidCertificate(
+                "Expiry must be after creation".to_string(),
+            ));
+        }
+
+        let mut cert = DeviceCertificate {
+            user_id: user_id.to_string(),
+            device_id: device_id.to_string(),
+            public_key: device.public_key,
+            verifying_key: device.verifying_key,
+            issuer_key: issuer.public_keys()?.verifying_key,
+            created_at,
+            expires_at,
+            capabilities,
+            signature: [0u8; SIGNATURE_SIZE],
+        };
+        cert.signature = issuer.sign(&cert.signed_bytes())?;
+        Ok(cert)
+    }
+
+    /// Check signature and validity period against the issuer key
+    pub fn verify(&self, issuer_key: &[u8; 32], clock: &dyn Clock) -> CryptoResult<()> {
+        if &self.issuer_key != issuer_key {
+            return Err(CryptoError::InvalidCertificate(
+                "Unexpected issuer".to_string(),
+            ));
+        }
+        verify_signature(issuer_key, &self.signed_bytes(), &self.signature)?;
+
+        let now = clock.now_millis();
+        if now < self.created_at {
+            return Err(CryptoError::InvalidCertificate("Not yet valid".to_string()));
+        }
+        if now >= self.expires_at {
+            return Err(CryptoError::InvalidCertificate("Expired".to_string()));
+        }
+        Ok(())
+    }
+
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(
+            CERTIFICATE_DOMAIN.len() + 128 + self.user_id.len() + self.device_id.len(),
+        );
+        out.extend_from_slice(CERTIFICATE_DOMAIN);
+        for field in [self.user_id.as_bytes(), self.device_id.as_bytes()] {
+            out.extend_from_slice(&(field.len() as u64).to_be_bytes());
+            out.extend_from_slice(field);
+        }
+        out.extend_from_slice(&self.public_key);
+        out.extend_from_slice(&self.verifying_key);
+        out.extend_from_slice(&self.issuer_key);
+        out.extend_from_slice(&self.created_at.to_be_bytes());
+        out.extend_from_slice(&self.expires_at.to_be_bytes());
+        out.extend_from_slice(&self.capabilities.0.to_be_bytes());
+        out
+    }
+}
+
+impl RevocationList {
+    /// Sign a revocation list with the identity key
+    pub fn issue(
+        identity: &dyn KeyStore,
+        user_id: &str,
+        sequence: u64,
+        revoked: Vec<String>,
+        clock: &dyn Clock,
+    ) -> CryptoResult<Self> {
+        let mut list = RevocationList {
+            user_id: user_id.to_string(),
+            sequence,
+            issued_at: clock.now_millis(),
+            revoked,
+            signature: [0u8; SIGNATURE_SIZE],
+        };
+        list.signature = identity.sign(&list.signed_bytes())?;
+        Ok(list)
+    }
+
+    /// Verify the identity key signature
+    pub fn verify(&self, identity_key: &[u8; 32]) -> CryptoResult<()> {
+        verify_signature(identity_key, &self.signed_bytes(), &self.signature)
+    }
+
+    /// Verify a received list before it replaces the stored one
+    ///
+    /// # Arguments
+    /// * `identity_key` - User's Ed25519 identity key
+    /// * `stored` - List currently kept for this user, if any
+    ///
+    /// # Errors
+    /// * `SignatureVerificationFailed` - not signed by `identity_key`
+    /// * `InvalidCertificate` - for another user, or `sequence` is not
+    ///   greater than the stored list's
+    pub fn verify_update(
+        &self,
+        identity_key: &[u8; 32],
+        stored: Option<&RevocationList>,
+    ) -> CryptoResult<()> {
+        self.verify(identity_key)?;
+
+        if let Some(stored) = stored {
+            if stored.user_id != self.user_id {
+                return Err(CryptoError::InvalidCertificate(
+                    "Revocation list is for another user".to_string(),
+                ));
+            }
+            if self.sequence <= stored.sequence {
+                return Err(CryptoError::InvalidCertificate(format!(
+                    "Stale revocation list: sequence {} is not above {}",
+                    self.sequence, stored.sequence
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `device_id` is revoked
+    pub fn is_revoked(&self, device_id: &str) -> bool {
+        self.revoked.iter().any(|d| d == device_id)
+    }
+
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(REVOCATION_DOMAIN);
+        out.extend_from_slice(&(self.user_id.len() as u64).to_be_bytes());
+        out.extend_from_slice(self.user_id.as_bytes());
+        out.extend_from_slice(&self.sequence.to_be_bytes());
+        out.extend_from_slice(&self.issued_at.to_be_bytes());
+        out.extend_from_slice(&(self.revoked.len() as u64).to_be_bytes());
+        for device_id in &self.revoked {
+            out.extend_from_slice(&(device_id.len() as u64).to_be_bytes());
+            out.extend_from_slice(device_id.as_bytes());
+        }
+        out
+    }
+}
+
+/// Verify a certificate chain from the identity key to the last device
+///
+/// # Arguments
+/// * `chain` - Certificates ordered from the one signed by the identity key
+/// * `identity_key` - User's Ed25519 identity key
+/// * `revocations` - Verified revocation list, if any
+/// * `clock` - Current time
+///
+/// # Returns
+/// The leaf device certificate
+pub fn verify_chain<'a>(
+    chain: &'a [DeviceCertificate],
+    identity_key: &[u8; 32],
+    revocations: Option<&RevocationList>,
+    clock: &dyn Clock,
+) -> CryptoResult<&'a DeviceCertificate> {
+    let leaf = chain
+        .last()
+        .ok_or_else(|| CryptoError::InvalidCertificate("Empty chain".to_string()))?;
+    if chain.len() > MAX_CHAIN_DEPTH {
+        return Err(CryptoError::InvalidCertificate(
+            "Chain too long".to_string(),
+        ));
+    }
+
+    if let Some(list) = revocations {
+        list.verify(identity_key)?;
+    }
+
+    let mut issuer_key = *identity_key;
+    let mut parent: Option<&DeviceCertificate> = None;
+
+    for cert in chain {
+        cert.verify(&issuer_key, clock)?;
+
+        if let Some(parent) = parent {
+            if cert.user_id != parent.user_id {
+                return Err(CryptoError::InvalidCertificate(
+                    "User mismatch in chain".to_string(),
+                ));
+            }
+            if !parent
+                .capabilities
+                .contains(DeviceCapabilities::LINK_DEVICES)
+            {
+                return Err(CryptoError::InvalidCertificate(
+                    "Issuer may not link devices".to_string(),
+                ));
+            }
+            if cert.expires_at > parent.expires_at {
+                return Err(CryptoError::InvalidCertificate(
+                    "Outlives its issuer".to_string(),
+                ));
+            }
+        }
+
+        if let Some(list) = revocations {
+            if list.user_id == cert.user_id && list.is_revoked(&cert.device_id) {
+                return Err(CryptoError::CertificateRevoked);
+            }
+        }
+
+        issuer_key = cert.verifying_key;
+        parent = Some(cert);
+    }
+
+    Ok(leaf)
+}
+
+/// List a user's currently valid devices from an unordered set of certificates
+///
+/// Chains are rebuilt by matching each certificate's `issuer_key`. Certificates
+/// that are expired, revoked, mis-signed or for another user are skipped.
+pub fn valid_devices<'a>(
+    user_id: &str,
+    identity_key: &[u8; 32],
+    certificates: &'a [DeviceCertificate],
+    revocations: Option<&RevocationList>,
+    clock: &dyn Clock,
+) -> CryptoResult<Vec<&'a DeviceCertificate>> {
+    if let Some(list) = revocations {
+        list.verify(identity_key)?;
+    }
+
+    let mut valid: Vec<&DeviceCertificate> = Vec::new();
+    let mut frontier: Vec<[u8; 32]> = alloc::vec![*identity_key];
+
+    for depth in 0..MAX_CHAIN_DEPTH {
+        let mut next = Vec::new();
+
+        for cert in certificates {
+            if cert.user_id != user_id || !frontier.contains(&cert.issuer_key) {
+                continue;
+            }
+            if valid.iter().any(|v| v.device_id == cert.device_id) {
+                continue;
+            }
+
+            let parent = valid.iter().find(|v| v.verifying_key == cert.issuer_key);
+            if depth > 0 {
+                match parent {
+                    Some(p)
+                        if p.capabilities.contains(DeviceCapabilities::LINK_DEVICES)
+                            && cert.expires_at <= p.expires_at => {}
+                    _ => continue,
+                }
+            }
+
+            if cert.verify(&cert.issuer_key, clock).is_err() {
+                continue;
+            }
+            if revocations
+                .is_some_and(|list| list.user_id == user_id && list.is_revoked(&cert.device_id))
+            {
+                continue;
+            }
+
+            next.push(cert.verifying_key);
+            valid.push(cert);
+        }
+
+        if next.is_empty() {
+            break;
+        }
+        frontier = next;
+    }
+
+    Ok(valid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use crate::KeyPair;
+
+    const DAY: i64 = 86_400_000;
+
+    fn setup() -> (KeyPair, KeyPair, KeyPair, DeviceCertificate) {
+        let identity = KeyPair::generate().unwrap();
+        let phone = KeyPair::generate().unwrap();
+        let laptop = KeyPair::generate().unwrap();
+        let phone_cert = DeviceCertificate::issue(
+            &identity,
+            "alice",
+            "phone",
+            &phone,
+            DeviceCapabilities::MESSAGING | DeviceCapabilities::LINK_DEVICES,
+            30 * DAY,
+            &FixedClock(0),
+        )
+        .unwrap();
+        (identity, phone, laptop, phone_cert)
+    }
+
+    #[test]
+    fn test_issue_and_verify() {
+        let (identity, _, _, phone_cert) = setup();
+        phone_cert
+            .verify(&identity.verifying_key, &FixedClock(DAY))
+            .unwrap();
+
+        assert!(phone_cert
+            .verify(&identity.verifying_key, &FixedClock(30 * DAY))
+            .is_err());
+
+        let mut tampered = phone_cert.clone();
+        tampered.capabilities = tampered.capabilities | DeviceCapabilities::BACKUP;
+        assert!(tampered
+            .verify(&identity.verifying_key, &FixedClock(DAY))
+            .is_err());
+    }
+
+    #[test]
+    fn test_chain_through_linking_device() {
+        let (identity, phone, laptop, phone_cert) = setup();
+        let laptop_cert = DeviceCertificate::issue(
+            &phone,
+            "alice",
+            "laptop",
+            &laptop,
+            DeviceCapabilities::MESSAGING,
+            10 * DAY,
+            &FixedClock(DAY),
+        )
+        .unwrap();
+
+        let chain = [phone_cert, laptop_cert];
+        let leaf =
+            verify_chain(&chain, &identity.verifying_key, None, &FixedClock(2 * DAY)).unwrap();
+        assert_eq!(leaf.device_id, "laptop");
+
+        // A device without LINK_DEVICES cannot extend the chain
+        let tablet = KeyPair::generate().unwrap();
+        let tablet_cert = DeviceCertificate::issue(
+            &laptop,
+            "alice",
+            "tablet",
+            &tablet,
+            DeviceCapabilities::MESSAGING,
+            5 * DAY,
+            &FixedClock(DAY),
+        )
+        .unwrap();
+        let chain = [chain[0].clone(), chain[1].clone(), tablet_cert];
+        assert!(verify_chain(&chain, &identity.verifying_key, None, &FixedClock(2 * DAY)).is_err());
+    }
+
+    #[test]
+    fn test_revoking_parent_revokes_children() {
+        let (identity, phone, laptop, phone_cert) = setup();
+        let laptop_cert = DeviceCertificate::issue(
+            &phone,
+            "alice",
+            "laptop",
+            &laptop,
+            DeviceCapabilities::MESSAGING,
+            10 * DAY,
+            &FixedClock(0),
+        )
+        .unwrap();
+        let clock = FixedClock(DAY);
+
+        let crl = RevocationList::issue(&identity, "alice", 1, vec!["phone".to_string()], &clock)
+            .unwrap();
+        let chain = [phone_cert.clone(), laptop_cert.clone()];
+        assert!(matches!(
+            verify_chain(&chain, &identity.verifying_key, Some(&crl), &clock),
+            Err(CryptoError::CertificateRevoked)
+        ));
+
+        let certs = [laptop_cert, phone_cert];
+        assert_eq!(
+            valid_devices("alice", &identity.verifying_key, &certs, None, &clock)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(
+            valid_devices("alice", &identity.verifying_key, &certs, Some(&crl), &clock)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_revocation_list_sequence_must_increase() {
+        let (identity, _, _, _) = setup();
+        let clock = FixedClock(DAY);
+        let issue = |sequence, revoked: &[&str]| {
+            let revoked = revoked.iter().map(|d| d.to_string()).collect();
+            RevocationList::issue(&identity, "alice", sequence, revoked, &clock).unwrap()
+        };
+
+        let first = issue(1, &[]);
+        let second = issue(2, &["phone"]);
+        first.verify_update(&identity.verifying_key, None).unwrap();
+        second
+            .verify_update(&identity.verifying_key, Some(&first))
+            .unwrap();
+
+        // Replaying an older list, or a different one with the same sequence, would un-revoke
+        for stale in [first, issue(2, &[])] {
+            assert!(matches!(
+                stale.verify_update(&identity.verifying_key, Some(&second)),
+                Err(CryptoError::InvalidCertificate(_))
+            ));
+        }
+
+        let bob = RevocationList::issue(&identity, "bob", 3, Vec::new(), &clock).unwrap();
+        assert!(bob
+            .verify_update(&identity.verifying_key, Some(&second))
+            .is_err());
+    }
+
+    #[test]
+    fn test_forged_revocation_list_rejected() {
+        let (identity, phone, _, phone_cert) = setup();
+        let forged = RevocationList::issue(
+            &phone,
+            "alice",
+            1,
+            vec!["phone".to_string()],
+            &FixedClock(0),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            valid_devices(
+                "alice",
+                &identity.verifying_key,
+                &[phone_cert],
+                Some(&forged),
+                &FixedClock(DAY)
+            ),
+            Err(CryptoError::SignatureVerificationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_valid_devices_skips_foreign_and_expired() {
+        let (identity, _, laptop, phone_cert) = setup();
+        let mallory = KeyPair::generate().unwrap();
+
+        let foreign = DeviceCertificate::issue(
+            &mallory,
+            "alice",
+            "evil",
+            &laptop,
+            DeviceCapabilities::MESSAGING,
+            10 * DAY,
+            &FixedClock(0),
+        )
+        .unwrap();
+        let short_lived = DeviceCertificate::issue(
+            &identity,
+            "alice",
+            "old-laptop",
+            &laptop,
+            DeviceCapabilities::MESSAGING,
+            DAY,
+            &FixedClock(0),
+        )
+        .unwrap();
+
+        let certs = [foreign, short_lived, phone_cert];
+        let devices = valid_devices(
+            "alice",
+            &identity.verifying_key,
+            &certs,
+            None,
+            &FixedClock(2 * DAY),
+        )
+        .unwrap();
+        let ids: Vec<&str> = devices.iter().map(|c| c.device_id.as_str()).collect();
+        assert_eq!(ids, vec!["phone"]);
+    }
+}
@@ -0,0 +1,241 @@
+//! Serde Byte Encodings
+//!
+//! Serde's default for `Vec<u8>` and `[u8; N]` is a sequence of integers,
+//! which in JSON is about four times the size of the data and unreadable to
+//! the Go services. The adapters here pick the representation by format:
+//! - Human-readable formats (JSON): unpadded base64url strings
+//! - Binary formats (bincode): raw bytes, byte-for-byte identical to the
+//!   default encoding, so existing binary data still decodes
+//!
+//! Human-readable decoding also accepts the old integer arrays, so stored
+//! JSON written before the switch keeps loading.
+//!
+//! Use with `#[serde(with = "chakchat_crypto::encoding::base64_bytes")]` on
+//! `Vec<u8>` fields, `base64_array` on `[u8; N]` fields and
+//! `base64_array_vec` on `Vec<[u8; N]>` fields.
+//!
+//! 64-byte signatures were written as length-prefixed byte strings in binary
+//! formats before they moved to `base64_array`; they are now fixed-size
+//! tuples like every other array.
+
+use alloc::vec::Vec;
+use base64ct::{Base64UrlUnpadded, Encoding};
+use core::fmt;
+use serde::de::{self, SeqAccess, Visitor};
+
+/// Collects bytes from a base64url string, a byte string or an integer sequence
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a base64url string or a byte array")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Vec<u8>, E> {
+        Base64UrlUnpadded::decode_vec(v).map_err(|_| E::custom("invalid base64url"))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+        Ok(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+        Ok(v)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(bytes)
+    }
+}
+
+/// `Vec<u8>` as base64url (human-readable) or a byte string (binary)
+pub mod base64_bytes {
+    use super::BytesVisitor;
+    use alloc::vec::Vec;
+    use base64ct::{Base64UrlUnpadded, Encoding};
+    use serde::{Deserializer, Serializer};
+
+    /// Serialize a byte vector
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&Base64UrlUnpadded::encode_string(bytes))
+        } else {
+            serializer.serialize_bytes(bytes)
+        }
+    }
+
+    /// Deserialize a byte vector
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(BytesVisitor)
+        } else {
+            deserializer.deserialize_byte_buf(BytesVisitor)
+        }
+    }
+}
+
+/// `[u8; N]` as base64url (human-readable) or a fixed-size tuple (binary)
+pub mod base64_array {
+    use super::BytesVisitor;
+    use alloc::format;
+    use base64ct::{Base64UrlUnpadded, Encoding};
+    use serde::de::Error;
+    use serde::ser::SerializeTuple;
+    use serde::{Deserializer, Serializer};
+
+    /// Serialize a fixed-size byte array
+    pub fn serialize<S: Serializer, const N: usize>(
+        bytes: &[u8; N],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            return serializer.serialize_str(&Base64UrlUnpadded::encode_string(bytes));
+        }
+
+        let mut tuple = serializer.serialize_tuple(N)?;
+        for byte in bytes {
+            tuple.serialize_element(byte)?;
+        }
+        tuple.end()
+    }
+
+    /// Deserialize a fixed-size byte array, rejecting any other length
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<[u8; N], D::Error> {
+        let bytes = if deserializer.is_human_readable() {
+            deserializer.deserialize_any(BytesVisitor)?
+        } else {
+            deserializer.deserialize_tuple(N, BytesVisitor)?
+        };
+        let len = bytes.len();
+        bytes
+            .try_into()
+            .map_err(|_| D::Error::custom(format!("expected {} bytes, got {}", N, len)))
+    }
+}
+
+/// `Vec<[u8; N]>` as a sequence of `base64_array` items
+pub mod base64_array_vec {
+    use alloc::vec::Vec;
+    use serde::ser::SerializeSeq;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    struct Item<'a, const N: usize>(&'a [u8; N]);
+
+    impl<const N: usize> Serialize for Item<'_, N> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            super::base64_array::serialize(self.0, serializer)
+        }
+    }
+
+    struct OwnedItem<const N: usize>([u8; N]);
+
+    impl<'de, const N: usize> Deserialize<'de> for OwnedItem<N> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            super::base64_array::deserialize(deserializer).map(OwnedItem)
+        }
+    }
+
+    /// Serialize a sequence of fixed-size byte arrays
+    pub fn serialize<S: Serializer, const N: usize>(
+        items: &[[u8; N]],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(items.len()))?;
+        for item in items {
+            seq.serialize_element(&Item(item))?;
+        }
+        seq.end()
+    }
+
+    /// Deserialize a sequence of fixed-size byte arrays
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<Vec<[u8; N]>, D::Error> {
+        let items = Vec::<OwnedItem<N>>::deserialize(deserializer)?;
+        Ok(items.into_iter().map(|item| item.0).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Sample {
+        #[serde(with = "super::base64_bytes")]
+        data: Vec<u8>,
+        #[serde(with = "super::base64_array")]
+        key: [u8; 4],
+        #[serde(with = "super::base64_array_vec")]
+        points: Vec<[u8; 2]>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Plain {
+        data: Vec<u8>,
+        key: [u8; 4],
+        points: Vec<[u8; 2]>,
+    }
+
+    fn sample() -> Sample {
+        Sample {
+            data: vec![0xfb, 0xff, 0x01],
+            key: [1, 2, 3, 4],
+            points: vec![[0xfb, 0xff], [0, 1]],
+        }
+    }
+
+    fn plain() -> Plain {
+        Plain {
+            data: vec![0xfb, 0xff, 0x01],
+            key: [1, 2, 3, 4],
+            points: vec![[0xfb, 0xff], [0, 1]],
+        }
+    }
+
+    #[test]
+    fn test_json_is_base64url() {
+        let json = serde_json::to_string(&sample()).unwrap();
+        assert_eq!(
+            json,
+            r#"{"data":"-_8B","key":"AQIDBA","points":["-_8","AAE"]}"#
+        );
+        assert_eq!(serde_json::from_str::<Sample>(&json).unwrap(), sample());
+    }
+
+    #[test]
+    fn test_json_accepts_integer_arrays() {
+        let legacy = serde_json::to_string(&plain()).unwrap();
+        assert_eq!(serde_json::from_str::<Sample>(&legacy).unwrap(), sample());
+    }
+
+    #[test]
+    fn test_json_rejects_bad_input() {
+        assert!(
+            serde_json::from_str::<Sample>(r#"{"data":"!!","key":"AQIDBA","points":[]}"#).is_err()
+        );
+        assert!(serde_json::from_str::<Sample>(r#"{"data":"","key":"AQID","points":[]}"#).is_err());
+        assert!(
+            serde_json::from_str::<Sample>(r#"{"data":"","key":"AQIDBA==","points":[]}"#).is_err()
+        );
+        assert!(
+            serde_json::from_str::<Sample>(r#"{"data":"","key":"AQIDBA","points":["AQID"]}"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_bincode_unchanged() {
+        let encoded = bincode::serialize(&sample()).unwrap();
+        assert_eq!(encoded, bincode::serialize(&plain()).unwrap());
+        assert_eq!(bincode::deserialize::<Sample>(&encoded).unwrap(), sample());
+    }
+}
@@ -7,19 +7,20 @@
 //!
 //! Combined = IMPOSSIBLE TO DECRYPT ✅
 
-use crate::{CryptoError, CryptoResult};
+use crate::padding::{self, PaddingPolicy};
+use crate::registry::Secret;
+use crate::{clock::Clock, CryptoError, CryptoResult};
 use aes_gcm::{
-    aead::{Aead, KeyInit, Payload},
+    aead::{AeadInPlace, KeyInit},
     Aes256Gcm, Nonce as AesNonce,
 };
-use chacha20poly1305::{
-    aead::{Aead as ChaChaAead, KeyInit as ChaChaKeyInit},
-    ChaCha20Poly1305, Nonce as ChaChaNonce, XChaCha20Poly1305,
-};
-use rand::RngCore;
+use alloc::{format, string::ToString, vec::Vec};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce as ChaChaNonce, XChaCha20Poly1305, XNonce};
+use core::fmt;
+use rand_core::CryptoRngCore;
 use serde::{Deserialize, Serialize};
-use std::fmt;
-use zeroize::Zeroize;
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, Zeroizing};
 
 /// 256-bit key size (32 bytes)
 pub const KEY_SIZE: usize = 32;
@@ -33,24 +34,55 @@ pub const AES_NONCE_SIZE: usize = 12;
 /// AEAD tag size (16 bytes)
 pub const TAG_SIZE: usize = 16;
 
+/// Key commitment size (HMAC-SHA256)
+pub const COMMITMENT_SIZE: usize = 32;
+
 /// Maximum message size: 100 MB
 pub const MAX_MESSAGE_SIZE: usize = 100 * 1024 * 1024;
 
+/// Layer ciphers, initialized once per session
+#[derive(Clone)]
+struct LayerCiphers {
+    /// Layer 1: XChaCha20-Poly1305
+    layer1: XChaCha20Poly1305,
+
+    /// Layer 2: AES-256-GCM
+    layer2: Aes256Gcm,
+
+    /// Layer 3: ChaCha20-Poly1305 (alternative to Twofish)
+    layer3: ChaCha20Poly1305,
+
+    /// HMAC key of the key commitment
+    commitment_key: Zeroizing<[u8; KEY_SIZE]>,
+}
+
+/// Cached ciphers as a wipeable secret
+///
+/// The cipher types zeroize their key schedules on drop, so zeroizing the
+/// slot drops them.
+#[derive(Clone)]
+struct CipherSlot(Option<LayerCiphers>);
+
+impl Zeroize for CipherSlot {
+    fn zeroize(&mut self) {
+        self.0 = None;
+    }
+}
+
 /// Triple-Layer Encryption State
 #[derive(Clone, Zeroize)]
 #[zeroize(drop)]
 pub struct TripleLayerEncryption {
-    /// Layer 1: XChaCha20-Poly1305 key
-    layer1_key: [u8; KEY_SIZE],
-
-    /// Layer 2: AES-256-GCM key
-    layer2_key: [u8; KEY_SIZE],
-
-    /// Layer 3: ChaCha20-Poly1305 key (alternative to Twofish)
-    layer3_key: [u8; KEY_SIZE],
+    /// Initialized layer ciphers, registered for panic wipe
+    #[zeroize(skip)]
+    ciphers: Secret<CipherSlot>,
 
     /// Message counter for replay protection
     message_counter: u64,
+
+    /// Length-hiding padding applied before layer 1
+    #[zeroize(skip)]
+    padding: PaddingPolicy,
 }
 
 /// Encrypted message with all metadata
@@ -60,17 +92,30 @@ pub struct EncryptedMessage {
     pub version: u8,
 
     /// Encrypted ciphertext (triple-layer)
+    #[serde(with = "crate::encoding::base64_bytes")]
     pub ciphertext: Vec<u8>,
 
     /// Layer 1 (XChaCha20) nonce
+    #[serde(with = "crate::encoding::base64_array")]
     pub layer1_nonce: [u8; XCHACHA_NONCE_SIZE],
 
     /// Layer 2 (AES-GCM) nonce
+    #[serde(with = "crate::encoding::base64_array")]
     pub layer2_nonce: [u8; AES_NONCE_SIZE],
 
     /// Layer 3 nonce (used with ChaCha20 fallback)
+    #[serde(with = "crate::encoding::base64_array")]
     pub layer3_nonce: [u8; 12],
 
+    /// Key commitment over the version and nonces, checked before decryption
+    ///
+    /// None of the AEAD layers commits to its key, so a ciphertext can be
+    /// crafted to open under several keys (a partitioning oracle against
+    /// group and password-derived keys). The commitment opens under one
+    /// shared secret only.
+    #[serde(with = "crate::encoding::base64_array")]
+    pub key_commitment: [u8; COMMITMENT_SIZE],
+
     /// Message counter (for replay protection)
     pub counter: u64,
 
@@ -79,19 +124,31 @@ pub struct EncryptedMessage {
 
     /// Timestamp (Unix milliseconds)
     pub timestamp: i64,
+
+    /// Expiry (Unix milliseconds), authenticated as layer-1 associated data
+    ///
+    /// `None` means the message never expires.
+    #[serde(default)]
+    pub expires_at: Option<i64>,
 }
 
 impl fmt::Debug for TripleLayerEncryption {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("TripleLayerEncryption")
-            .field("layer1_key", &"[REDACTED]")
-            .field("layer2_key", &"[REDACTED]")
-            .field("layer3_key", &"[REDACTED]")
+            .field("ciphers", &self.ciphers)
             .field("message_counter", &self.message_counter)
+            .field("padding", &self.padding)
             .finish()
     }
 }
 
+impl CipherSlot {
+    /// Ciphers, unless wiped
+    fn get(&self) -> CryptoResult<&LayerCiphers> {
+        self.0.as_ref().ok_or(CryptoError::Wiped)
+    }
+}
+
 impl TripleLayerEncryption {
     /// Create new triple-layer encryption from shared secret
     ///
@@ -103,15 +160,39 @@ impl TripleLayerEncryption {
     pub fn new(shared_secret: &[u8; KEY_SIZE]) -> CryptoResult<Self> {
         // Derive three independent keys using HKDF
         let (key1, key2, key3) = Self::derive_triple_keys(shared_secret)?;
+        let (key1, key2, key3) = (
+            Zeroizing::new(key1),
+            Zeroizing::new(key2),
+            Zeroizing::new(key3),
+        );
+        let commitment_key = Self::derive_commitment_key(shared_secret)?;
 
         Ok(TripleLayerEncryption {
-            layer1_key: key1,
-            layer2_key: key2,
-            layer3_key: key3,
+            ciphers: Secret::new(CipherSlot(Some(LayerCiphers {
+                layer1: XChaCha20Poly1305::new(key1.as_ref().into()),
+                layer2: Aes256Gcm::new(key2.as_ref().into()),
+                layer3: ChaCha20Poly1305::new(key3.as_ref().into()),
+                commitment_key,
+            }))),
             message_counter: 0,
+            padding: PaddingPolicy::default(),
         })
     }
 
+    /// Use a different padding policy for outgoing messages
+    ///
+    /// Receivers do not need to know the policy; padding removal is
+    /// policy-independent.
+    pub fn with_padding(mut self, policy: PaddingPolicy) -> Self {
+        self.padding = policy;
+        self
+    }
+
+    /// Padding policy for outgoing messages
+    pub fn padding_policy(&self) -> PaddingPolicy {
+        self.padding
+    }
+
     /// Derive three independent encryption keys from shared secret
     fn derive_triple_keys(
         shared_secret: &[u8; KEY_SIZE],
@@ -137,6 +218,20 @@ impl TripleLayerEncryption {
         Ok((key1, key2, key3))
     }
 
+    /// Derive the key commitment's HMAC key from shared secret
+    fn derive_commitment_key(
+        shared_secret: &[u8; KEY_SIZE],
+    ) -> CryptoResult<Zeroizing<[u8; KEY_SIZE]>> {
+        use hkdf::Hkdf;
+        use sha2::Sha256;
+
+        let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+        Hkdf::<Sha256>::new(None, shared_secret)
+            .expand(b"chakchat_commitment_key", key.as_mut())
+            .map_err(|e| CryptoError::KeyDerivationError(e.to_string()))?;
+        Ok(key)
+    }
+
     /// Encrypt message with all three layers
     ///
     /// # Arguments
@@ -144,57 +239,244 @@ impl TripleLayerEncryption {
     ///
     /// # Returns
     /// EncryptedMessage with triple-layer encryption
+    #[cfg(feature = "std")]
     pub fn encrypt(&mut self, plaintext: &[u8]) -> CryptoResult<EncryptedMessage> {
-        if plaintext.is_empty() {
-            return Err(CryptoError::EncryptionError(
-                "Cannot encrypt empty message".to_string(),
-            ));
-        }
+        self.encrypt_with(
+            plaintext,
+            &mut rand::thread_rng(),
+            &crate::clock::SystemClock,
+        )
+    }
 
-        if plaintext.len() > MAX_MESSAGE_SIZE {
+    /// Encrypt message with all three layers using a caller-supplied RNG and clock
+    ///
+    /// Output is fully determined by the key, counter, RNG stream and clock,
+    /// which makes known-answer tests possible.
+    ///
+    /// # Arguments
+    /// * `plaintext` - Message to encrypt
+    /// * `rng` - Source for the nonces and the message ID
+    /// * `clock` - Source of the message timestamp
+    ///
+    /// # Returns
+    /// EncryptedMessage with triple-layer encryption
+    pub fn encrypt_with(
+        &mut self,
+        plaintext: &[u8],
+        rng: &mut impl CryptoRngCore,
+        clock: &dyn Clock,
+    ) -> CryptoResult<EncryptedMessage> {
+        self.seal(Vec::new(), plaintext, None, rng, clock)
+    }
+
+    /// Encrypt a plaintext buffer in place
+    ///
+    /// Padding and the three layer tags are written into `buffer`, which
+    /// becomes the message ciphertext without being copied. Allocate it with
+    /// `sealed_capacity` to avoid any reallocation.
+    #[cfg(feature = "std")]
+    pub fn encrypt_in_place(&mut self, buffer: Vec<u8>) -> CryptoResult<EncryptedMessage> {
+        self.encrypt_in_place_with(buffer, &mut rand::thread_rng(), &crate::clock::SystemClock)
+    }
+
+    /// Encrypt a plaintext buffer in place using a caller-supplied RNG and clock
+    ///
+    /// Produces the same message as `encrypt_with` for the same inputs.
+    pub fn encrypt_in_place_with(
+        &mut self,
+        buffer: Vec<u8>,
+        rng: &mut impl CryptoRngCore,
+        clock: &dyn Clock,
+    ) -> CryptoResult<EncryptedMessage> {
+        self.seal(buffer, &[], None, rng, clock)
+    }
+
+    /// Buffer capacity that fits a sealed `plaintext_len`-byte message
+    ///
+    /// Upper bound over the padding policy plus the three layer tags.
+    pub fn sealed_capacity(&self, plaintext_len: usize) -> usize {
+        self.padding
+            .max_padded_len(plaintext_len)
+            .map_or(usize::MAX, |len| len.saturating_add(3 * TAG_SIZE))
+    }
+
+    /// Encrypt a disappearing message
+    ///
+    /// # Arguments
+    /// * `plaintext` - Message to encrypt
+    /// * `expires_at` - Unix milliseconds after which decryption is refused
+    #[cfg(feature = "std")]
+    pub fn encrypt_expiring(
+        &mut self,
+        plaintext: &[u8],
+        expires_at: i64,
+    ) -> CryptoResult<EncryptedMessage> {
+        self.encrypt_expiring_with(
+            plaintext,
+            expires_at,
+            &mut rand::thread_rng(),
+            &crate::clock::SystemClock,
+        )
+    }
+
+    /// Encrypt a disappearing message using a caller-supplied RNG and clock
+    pub fn encrypt_expiring_with(
+        &mut self,
+        plaintext: &[u8],
+        expires_at: i64,
+        rng: &mut impl CryptoRngCore,
+        clock: &dyn Clock,
+    ) -> CryptoResult<EncryptedMessage> {
+        if expires_at <= clock.now_millis() {
             return Err(CryptoError::EncryptionError(
-                "Message exceeds maximum size".to_string(),
+                "Expiry must be in the future".to_string(),
             ));
         }
 
-        // Generate random nonces
-        let mut layer1_nonce = [0u8; XCHACHA_NONCE_SIZE];
-        let mut layer2_nonce = [0u8; AES_NONCE_SIZE];
-        let mut layer3_nonce = [0u8; 12];
-
-        rand::thread_rng().fill_bytes(&mut layer1_nonce);
-        rand::thread_rng().fill_bytes(&mut layer2_nonce);
-        rand::thread_rng().fill_bytes(&mut layer3_nonce);
+        self.seal(Vec::new(), plaintext, Some(expires_at), rng, clock)
+    }
 
-        // Layer 1: XChaCha20-Poly1305
-        let intermediate1 =
-            self.encrypt_layer1(plaintext, &layer1_nonce)?;
+    /// Encrypt many messages at once
+    ///
+    /// Reserves one counter per plaintext up front; with the `parallel`
+    /// feature the cipher work runs on the rayon pool.
+    ///
+    /// # Returns
+    /// One EncryptedMessage per plaintext, in order
+    #[cfg(feature = "std")]
+    pub fn encrypt_batch<P: AsRef<[u8]> + Sync>(
+        &mut self,
+        plaintexts: &[P],
+    ) -> CryptoResult<Vec<EncryptedMessage>> {
+        self.encrypt_batch_with(
+            plaintexts,
+            &mut rand::thread_rng(),
+            &crate::clock::SystemClock,
+        )
+    }
 
-        // Layer 2: AES-256-GCM
-        let intermediate2 =
-            self.encrypt_layer2(&intermediate1, &layer2_nonce)?;
+    /// Encrypt many messages at once using a caller-supplied RNG and clock
+    ///
+    /// Nonces, padding and message IDs are drawn from `rng` in plaintext
+    /// order before any encryption starts, so the result is identical to
+    /// calling `encrypt_with` on each plaintext in turn.
+    ///
+    /// # Errors
+    /// `EncryptionError` if any plaintext is empty or oversized or the
+    /// counter range would overflow; no counter is consumed in that case.
+    pub fn encrypt_batch_with<P: AsRef<[u8]> + Sync>(
+        &mut self,
+        plaintexts: &[P],
+        rng: &mut impl CryptoRngCore,
+        clock: &dyn Clock,
+    ) -> CryptoResult<Vec<EncryptedMessage>> {
+        for plaintext in plaintexts {
+            check_plaintext_len(plaintext.as_ref().len(), self.padding)?;
+        }
 
-        // Layer 3: ChaCha20-Poly1305
-        let ciphertext = self.encrypt_layer3(&intermediate2, &layer3_nonce)?;
+        let first = self.message_counter;
+        let last = u64::try_from(plaintexts.len())
+            .ok()
+            .and_then(|count| first.checked_add(count))
+            .ok_or_else(counter_overflow)?;
+
+        let params: Vec<SealParams> = plaintexts
+            .iter()
+            .enumerate()
+            .map(|(i, plaintext)| {
+                self.draw_params(plaintext.as_ref().len(), first + i as u64 + 1, rng, clock)
+            })
+            .collect::<CryptoResult<_>>()?;
+
+        let aad = associated_data(crate::PROTOCOL_VERSION, None);
+        let ciphers = self.batch_ciphers()?;
+        let seal = |(plaintext, params): (&P, &SealParams)| {
+            ciphers.seal(Vec::new(), plaintext.as_ref(), params, &aad)
+        };
+
+        #[cfg(feature = "parallel")]
+        let sealed: Vec<(Vec<u8>, [u8; COMMITMENT_SIZE])> = {
+            use rayon::prelude::*;
+            plaintexts
+                .par_iter()
+                .zip(params.par_iter())
+                .map(seal)
+                .collect::<CryptoResult<_>>()?
+        };
+
+        #[cfg(not(feature = "parallel"))]
+        let sealed: Vec<(Vec<u8>, [u8; COMMITMENT_SIZE])> = plaintexts
+            .iter()
+            .zip(&params)
+            .map(seal)
+            .collect::<CryptoResult<_>>()?;
+        self.message_counter = last;
+
+        Ok(params
+            .into_iter()
+            .zip(sealed)
+            .map(|(params, sealed)| params.into_message(sealed, None))
+            .collect())
+    }
 
-        // Increment counter for replay protection
-        self.message_counter = self
+    /// Pad and encrypt `buffer || plaintext` through all three layers in `buffer`
+    fn seal(
+        &mut self,
+        buffer: Vec<u8>,
+        plaintext: &[u8],
+        expires_at: Option<i64>,
+        rng: &mut impl CryptoRngCore,
+        clock: &dyn Clock,
+    ) -> CryptoResult<EncryptedMessage> {
+        let len = buffer.len() + plaintext.len();
+        check_plaintext_len(len, self.padding)?;
+
+        // Counter for replay protection
+        let counter = self
             .message_counter
             .checked_add(1)
-            .ok_or_else(|| CryptoError::EncryptionError("Counter overflow".to_string()))?;
+            .ok_or_else(counter_overflow)?;
+        let params = self.draw_params(len, counter, rng, clock)?;
 
-        // Generate unique message ID
-        let message_id = rand::random::<u64>();
+        let aad = associated_data(crate::PROTOCOL_VERSION, expires_at);
+        let sealed = self
+            .ciphers
+            .with(|slot| slot.get()?.seal(buffer, plaintext, &params, &aad))?;
+        self.message_counter = counter;
 
-        Ok(EncryptedMessage {
-            version: crate::PROTOCOL_VERSION,
-            ciphertext,
+        Ok(params.into_message(sealed, expires_at))
+    }
+
+    /// Draw the random and clock-derived values of one message
+    ///
+    /// The draw order (nonces, padding length, message ID) is part of the
+    /// known-answer vectors and must not change.
+    fn draw_params(
+        &self,
+        len: usize,
+        counter: u64,
+        rng: &mut impl CryptoRngCore,
+        clock: &dyn Clock,
+    ) -> CryptoResult<SealParams> {
+        let mut layer1_nonce = [0u8; XCHACHA_NONCE_SIZE];
+        let mut layer2_nonce = [0u8; AES_NONCE_SIZE];
+        let mut layer3_nonce = [0u8; 12];
+
+        rng.fill_bytes(&mut layer1_nonce);
+        rng.fill_bytes(&mut layer2_nonce);
+        rng.fill_bytes(&mut layer3_nonce);
+
+        // Pad to hide the exact length
+        let padded_len = self.padding.padded_len(len, rng)?;
+
+        Ok(SealParams {
             layer1_nonce,
             layer2_nonce,
             layer3_nonce,
-            counter: self.message_counter,
-            message_id,
-            timestamp: chrono::Local::now().timestamp_millis(),
+            padded_len,
+            counter,
+            message_id: rng.next_u64(),
+            timestamp: clock.now_millis(),
         })
     }
 
@@ -205,131 +487,314 @@ impl TripleLayerEncryption {
     ///
     /// # Returns
     /// Decrypted plaintext
+    #[cfg(feature = "std")]
     pub fn decrypt(&mut self, message: &EncryptedMessage) -> CryptoResult<Vec<u8>> {
-        if message.version != crate::PROTOCOL_VERSION {
-            return Err(CryptoError::DecryptionError(
-                "Invalid protocol version".to_string(),
-            ));
+        self.decrypt_with(message, &crate::clock::SystemClock)
+    }
+
+    /// Decrypt message through all three layers, checking expiry against `clock`
+    ///
+    /// # Returns
+    /// Decrypted plaintext, or `CryptoError::MessageExpired`
+    pub fn decrypt_with(
+        &mut self,
+        message: &EncryptedMessage,
+        clock: &dyn Clock,
+    ) -> CryptoResult<Vec<u8>> {
+        self.decrypt_in_place_with(message.clone(), clock)
+    }
+
+    /// Decrypt a message in its own ciphertext buffer
+    ///
+    /// The returned plaintext reuses the message's allocation.
+    #[cfg(feature = "std")]
+    pub fn decrypt_in_place(&mut self, message: EncryptedMessage) -> CryptoResult<Vec<u8>> {
+        self.decrypt_in_place_with(message, &crate::clock::SystemClock)
+    }
+
+    /// Decrypt a message in its own ciphertext buffer, checking expiry against `clock`
+    pub fn decrypt_in_place_with(
+        &mut self,
+        message: EncryptedMessage,
+        clock: &dyn Clock,
+    ) -> CryptoResult<Vec<u8>> {
+        check_message(&message, clock)?;
+        self.ciphers.with(|slot| slot.get()?.open(message))
+    }
+
+    /// Decrypt many messages at once
+    ///
+    /// # Returns
+    /// One result per message, in order, or `CryptoError::Wiped`
+    #[cfg(feature = "std")]
+    pub fn decrypt_batch(
+        &mut self,
+        messages: &[EncryptedMessage],
+    ) -> CryptoResult<Vec<CryptoResult<Vec<u8>>>> {
+        self.decrypt_batch_with(messages, &crate::clock::SystemClock)
+    }
+
+    /// Decrypt many messages at once, checking expiry against `clock`
+    ///
+    /// A message that fails to decrypt does not affect the others; each
+    /// result is what `decrypt_with` would return for that message. With the
+    /// `parallel` feature the cipher work runs on the rayon pool.
+    pub fn decrypt_batch_with(
+        &mut self,
+        messages: &[EncryptedMessage],
+        clock: &dyn Clock,
+    ) -> CryptoResult<Vec<CryptoResult<Vec<u8>>>> {
+        // The clock is read here, in message order, rather than on the pool
+        let checks: Vec<CryptoResult<()>> = messages
+            .iter()
+            .map(|message| check_message(message, clock))
+            .collect();
+
+        let ciphers = self.batch_ciphers()?;
+        let open = |(message, check): (&EncryptedMessage, CryptoResult<()>)| {
+            check.and_then(|()| ciphers.open(message.clone()))
+        };
+
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+            Ok(messages.par_iter().zip(checks).map(open).collect())
         }
 
-        if message.ciphertext.is_empty() {
-            return Err(CryptoError::DecryptionError(
-                "Empty ciphertext".to_string(),
-            ));
+        #[cfg(not(feature = "parallel"))]
+        {
+            Ok(messages.iter().zip(checks).map(open).collect())
         }
+    }
 
-        // Layer 3: Reverse ChaCha20-Poly1305
-        let intermediate2 =
-            self.decrypt_layer3(&message.ciphertext, &message.layer3_nonce)?;
+    /// Copy of the layer ciphers for a batch
+    ///
+    /// Batches run without holding the secret's lock: a rayon worker blocked
+    /// on it could steal a job that locks a clone of this session and
+    /// deadlock, and `panic_wipe` would wait for the whole batch. The copy
+    /// zeroizes its key schedules when the batch ends.
+    fn batch_ciphers(&self) -> CryptoResult<LayerCiphers> {
+        self.ciphers.with(|slot| slot.get().cloned())
+    }
 
-        // Layer 2: Reverse AES-256-GCM
-        let intermediate1 =
-            self.decrypt_layer2(&intermediate2, &message.layer2_nonce)?;
+    /// Get current message counter
+    pub fn get_counter(&self) -> u64 {
+        self.message_counter
+    }
 
-        // Layer 1: Reverse XChaCha20-Poly1305
-        let plaintext = self.decrypt_layer1(&intermediate1, &message.layer1_nonce)?;
+    /// Reset counter (careful - only for new session)
+    pub fn reset_counter(&mut self) {
+        self.message_counter = 0;
+    }
+}
 
-        Ok(plaintext)
+/// Layer-1 associated data: version, then the expiry if present
+fn associated_data(version: u8, expires_at: Option<i64>) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(9);
+    aad.push(version);
+    if let Some(expires_at) = expires_at {
+        aad.extend_from_slice(&expires_at.to_be_bytes());
     }
+    aad
+}
 
-    /// Encrypt with Layer 1: XChaCha20-Poly1305
-    fn encrypt_layer1(
-        &self,
-        plaintext: &[u8],
-        nonce: &[u8; XCHACHA_NONCE_SIZE],
-    ) -> CryptoResult<Vec<u8>> {
-        let cipher = XChaCha20Poly1305::new(self.layer1_key.as_ref().into());
-        let nonce = ChaChaNonce::from_slice(nonce);
+/// Per-message values drawn before any cipher work
+struct SealParams {
+    layer1_nonce: [u8; XCHACHA_NONCE_SIZE],
+    layer2_nonce: [u8; AES_NONCE_SIZE],
+    layer3_nonce: [u8; 12],
+    padded_len: usize,
+    counter: u64,
+    message_id: u64,
+    timestamp: i64,
+}
 
-        cipher
-            .encrypt(nonce, plaintext)
-            .map_err(|e| CryptoError::EncryptionError(format!("Layer 1 failed: {}", e)))
+impl SealParams {
+    fn into_message(
+        self,
+        (ciphertext, key_commitment): (Vec<u8>, [u8; COMMITMENT_SIZE]),
+        expires_at: Option<i64>,
+    ) -> EncryptedMessage {
+        EncryptedMessage {
+            version: crate::PROTOCOL_VERSION,
+            ciphertext,
+            layer1_nonce: self.layer1_nonce,
+            layer2_nonce: self.layer2_nonce,
+            layer3_nonce: self.layer3_nonce,
+            key_commitment,
+            counter: self.counter,
+            message_id: self.message_id,
+            timestamp: self.timestamp,
+            expires_at,
+        }
     }
+}
 
-    /// Decrypt with Layer 1: XChaCha20-Poly1305
-    fn decrypt_layer1(
+impl LayerCiphers {
+    /// Pad `buffer || plaintext`, encrypt it through all three layers and commit to the keys
+    fn seal(
         &self,
-        ciphertext: &[u8],
-        nonce: &[u8; XCHACHA_NONCE_SIZE],
-    ) -> CryptoResult<Vec<u8>> {
-        let cipher = XChaCha20Poly1305::new(self.layer1_key.as_ref().into());
-        let nonce = ChaChaNonce::from_slice(nonce);
+        mut buffer: Vec<u8>,
+        plaintext: &[u8],
+        params: &SealParams,
+        aad: &[u8],
+    ) -> CryptoResult<(Vec<u8>, [u8; COMMITMENT_SIZE])> {
+        // Leave room for the tags so the layers never reallocate
+        buffer.reserve_exact(params.padded_len + 3 * TAG_SIZE - buffer.len());
+        buffer.extend_from_slice(plaintext);
+        padding::pad_in_place(&mut buffer, params.padded_len);
+
+        // Layer 1: XChaCha20-Poly1305
+        self.layer1
+            .encrypt_in_place(XNonce::from_slice(&params.layer1_nonce), aad, &mut buffer)
+            .map_err(|e| CryptoError::EncryptionError(format!("Layer 1 failed: {}", e)))?;
 
-        cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|e| CryptoError::DecryptionError(format!("Layer 1 failed: {}", e)))
+        // Layer 2: AES-256-GCM
+        self.layer2
+            .encrypt_in_place(AesNonce::from_slice(&params.layer2_nonce), b"", &mut buffer)
+            .map_err(|e| CryptoError::EncryptionError(format!("Layer 2 failed: {}", e)))?;
+
+        // Layer 3: ChaCha20-Poly1305
+        self.layer3
+            .encrypt_in_place(
+                ChaChaNonce::from_slice(&params.layer3_nonce),
+                b"",
+                &mut buffer,
+            )
+            .map_err(|e| CryptoError::EncryptionError(format!("Layer 3 failed: {}", e)))?;
+
+        let commitment = self.commitment(
+            crate::PROTOCOL_VERSION,
+            &params.layer1_nonce,
+            &params.layer2_nonce,
+            &params.layer3_nonce,
+        );
+        Ok((buffer, commitment))
     }
 
-    /// Encrypt with Layer 2: AES-256-GCM
-    fn encrypt_layer2(
-        &self,
-        plaintext: &[u8],
-        nonce: &[u8; AES_NONCE_SIZE],
-    ) -> CryptoResult<Vec<u8>> {
-        let cipher = Aes256Gcm::new(self.layer2_key.as_ref().into());
-        let nonce = AesNonce::from_slice(nonce);
+    /// Check the key commitment, then decrypt all three layers in the message's buffer and remove padding
+    fn open(&self, message: EncryptedMessage) -> CryptoResult<Vec<u8>> {
+        let commitment = self.commitment(
+            message.version,
+            &message.layer1_nonce,
+            &message.layer2_nonce,
+            &message.layer3_nonce,
+        );
+        if !bool::from(commitment.ct_eq(&message.key_commitment)) {
+            return Err(CryptoError::DecryptionError(
+                "Key commitment mismatch".to_string(),
+            ));
+        }
+
+        let aad = associated_data(message.version, message.expires_at);
+        let mut buffer = message.ciphertext;
 
-        cipher
-            .encrypt(nonce, plaintext)
-            .map_err(|e| CryptoError::EncryptionError(format!("Layer 2 failed: {}", e)))
+        // Layer 3: Reverse ChaCha20-Poly1305
+        self.layer3
+            .decrypt_in_place(
+                ChaChaNonce::from_slice(&message.layer3_nonce),
+                b"",
+                &mut buffer,
+            )
+            .map_err(|e| CryptoError::DecryptionError(format!("Layer 3 failed: {}", e)))?;
+
+        // Layer 2: Reverse AES-256-GCM
+        self.layer2
+            .decrypt_in_place(
+                AesNonce::from_slice(&message.layer2_nonce),
+                b"",
+                &mut buffer,
+            )
+            .map_err(|e| CryptoError::DecryptionError(format!("Layer 2 failed: {}", e)))?;
+
+        // Layer 1: Reverse XChaCha20-Poly1305
+        self.layer1
+            .decrypt_in_place(XNonce::from_slice(&message.layer1_nonce), &aad, &mut buffer)
+            .map_err(|e| CryptoError::DecryptionError(format!("Layer 1 failed: {}", e)))?;
+
+        padding::unpad(buffer)
     }
 
-    /// Decrypt with Layer 2: AES-256-GCM
-    fn decrypt_layer2(
+    /// HMAC-SHA256 over the version and nonces
+    ///
+    /// The HMAC key comes from the same shared secret as the layer keys, and
+    /// HMAC-SHA256 is collision-resistant in its key, so no second secret
+    /// yields the same tag.
+    fn commitment(
         &self,
-        ciphertext: &[u8],
-        nonce: &[u8; AES_NONCE_SIZE],
-    ) -> CryptoResult<Vec<u8>> {
-        let cipher = Aes256Gcm::new(self.layer2_key.as_ref().into());
-        let nonce = AesNonce::from_slice(nonce);
+        version: u8,
+        layer1_nonce: &[u8; XCHACHA_NONCE_SIZE],
+        layer2_nonce: &[u8; AES_NONCE_SIZE],
+        layer3_nonce: &[u8; 12],
+    ) -> [u8; COMMITMENT_SIZE] {
+        let mut data = Vec::with_capacity(1 + XCHACHA_NONCE_SIZE + AES_NONCE_SIZE + 12);
+        data.push(version);
+        data.extend_from_slice(layer1_nonce);
+        data.extend_from_slice(layer2_nonce);
+        data.extend_from_slice(layer3_nonce);
+        crate::utils::hmac_sha256(self.commitment_key.as_ref(), &data)
+    }
+}
 
-        cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|e| CryptoError::DecryptionError(format!("Layer 2 failed: {}", e)))
+/// Reject plaintext lengths that cannot be sealed
+///
+/// The padded length is bounded too, so a large `RandomRange` cannot push
+/// the sealed message past `MAX_MESSAGE_SIZE`.
+fn check_plaintext_len(len: usize, padding: PaddingPolicy) -> CryptoResult<()> {
+    if len == 0 {
+        return Err(CryptoError::EncryptionError(
+            "Cannot encrypt empty message".to_string(),
+        ));
     }
 
-    /// Encrypt with Layer 3: ChaCha20-Poly1305
-    fn encrypt_layer3(
-        &self,
-        plaintext: &[u8],
-        nonce: &[u8],
-    ) -> CryptoResult<Vec<u8>> {
-        let cipher = ChaCha20Poly1305::new(self.layer3_key.as_ref().into());
-        let nonce = ChaChaNonce::from_slice(nonce);
+    if len > MAX_MESSAGE_SIZE {
+        return Err(CryptoError::EncryptionError(
+            "Message exceeds maximum size".to_string(),
+        ));
+    }
 
-        cipher
-            .encrypt(nonce, plaintext)
-            .map_err(|e| CryptoError::EncryptionError(format!("Layer 3 failed: {}", e)))
+    if padding
+        .max_padded_len(len)
+        .is_none_or(|padded| padded > MAX_MESSAGE_SIZE)
+    {
+        return Err(CryptoError::EncryptionError(
+            "Padded message exceeds maximum size".to_string(),
+        ));
     }
 
-    /// Decrypt with Layer 3: ChaCha20-Poly1305
-    fn decrypt_layer3(
-        &self,
-        ciphertext: &[u8],
-        nonce: &[u8],
-    ) -> CryptoResult<Vec<u8>> {
-        let cipher = ChaCha20Poly1305::new(self.layer3_key.as_ref().into());
-        let nonce = ChaChaNonce::from_slice(nonce);
+    Ok(())
+}
 
-        cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|e| CryptoError::DecryptionError(format!("Layer 3 failed: {}", e)))
+/// Checks that need no key: version, non-empty ciphertext, expiry
+fn check_message(message: &EncryptedMessage, clock: &dyn Clock) -> CryptoResult<()> {
+    if message.version != crate::PROTOCOL_VERSION {
+        return Err(CryptoError::DecryptionError(
+            "Invalid protocol version".to_string(),
+        ));
     }
 
-    /// Get current message counter
-    pub fn get_counter(&self) -> u64 {
-        self.message_counter
+    if message.ciphertext.is_empty() {
+        return Err(CryptoError::DecryptionError("Empty ciphertext".to_string()));
     }
 
-    /// Reset counter (careful - only for new session)
-    pub fn reset_counter(&mut self) {
-        self.message_counter = 0;
+    if message
+        .expires_at
+        .is_some_and(|expires_at| clock.now_millis() >= expires_at)
+    {
+        return Err(CryptoError::MessageExpired);
     }
+
+    Ok(())
+}
+
+fn counter_overflow() -> CryptoError {
+    CryptoError::EncryptionError("Counter overflow".to_string())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use num_bigint::BigUint;
 
     #[test]
     fn test_triple_layer_encryption_decryption() {
@@ -382,6 +847,139 @@ mod tests {
         assert!(enc2.decrypt(&encrypted1).is_err());
     }
 
+    /// Poly1305 key `(r, s)` that ChaCha20-Poly1305 uses for `key` and `nonce`
+    fn poly1305_key(key: &[u8; KEY_SIZE], nonce: &[u8; 12]) -> (BigUint, BigUint) {
+        use chacha20::cipher::{KeyIvInit, StreamCipher};
+
+        let mut block = [0u8; 32];
+        chacha20::ChaCha20::new(key.into(), nonce.into()).apply_keystream(&mut block);
+        let (r, s) = block.split_at_mut(16);
+        for i in [3, 7, 11, 15] {
+            r[i] &= 0x0f;
+        }
+        for i in [4, 8, 12] {
+            r[i] &= 0xfc;
+        }
+        (BigUint::from_bytes_le(r), BigUint::from_bytes_le(s))
+    }
+
+    /// Two-block ChaCha20-Poly1305 ciphertext (with tag) that opens under both keys
+    ///
+    /// Solves the two Poly1305 equations for the block values as in Len,
+    /// Grubbs and Ristenpart, "Partitioning Oracle Attacks", trying tags
+    /// until both blocks come out in range.
+    fn colliding_ciphertext(keys: [&[u8; KEY_SIZE]; 2], nonce: &[u8; 12]) -> Vec<u8> {
+        let p = (BigUint::from(1u8) << 130u32) - 5u8;
+        let block_bit = BigUint::from(1u8) << 128u32;
+        let [(r1, s1), (r2, s2)] = keys.map(|key| poly1305_key(key, nonce));
+        // Empty AAD, 32 bytes of ciphertext: h = c1 r^3 + c2 r^2 + len r (mod p)
+        let len_block = (BigUint::from(32u8) << 64u32) + &block_bit;
+        let sub = |a: &BigUint, b: &BigUint| (a + &p - b % &p) % &p;
+
+        let (a, b) = (r1.modpow(&3u8.into(), &p), r1.modpow(&2u8.into(), &p));
+        let (c, d) = (r2.modpow(&3u8.into(), &p), r2.modpow(&2u8.into(), &p));
+        let det_inv = sub(&(&a * &d), &(&b * &c)).modpow(&(&p - 2u8), &p);
+
+        for tag in 0u32.. {
+            let tag = BigUint::from(tag);
+            // Need (h + s) mod 2^128 == tag, so h = (tag - s) mod 2^128 < p
+            let target = |r: &BigUint, s: &BigUint| {
+                sub(&((&tag + &block_bit - s) % &block_bit), &(&len_block * r))
+            };
+            let (y1, y2) = (target(&r1, &s1), target(&r2, &s2));
+
+            let c1 = sub(&(&y1 * &d), &(&b * &y2)) * &det_inv % &p;
+            let c2 = sub(&(&a * &y2), &(&c * &y1)) * &det_inv % &p;
+            let in_range = |c: &BigUint| c >= &block_bit && c < &(&block_bit << 1u32);
+            if in_range(&c1) && in_range(&c2) {
+                let mut ciphertext = Vec::new();
+                for value in [c1 - &block_bit, c2 - &block_bit, tag] {
+                    let mut bytes = value.to_bytes_le();
+                    bytes.resize(16, 0);
+                    ciphertext.extend(bytes);
+                }
+                return ciphertext;
+            }
+        }
+        unreachable!()
+    }
+
+    #[test]
+    fn test_multi_key_collision_rejected() {
+        use chacha20poly1305::aead::Aead;
+
+        let (secret_a, secret_b) = ([1u8; KEY_SIZE], [2u8; KEY_SIZE]);
+        let (_, _, key3_a) = TripleLayerEncryption::derive_triple_keys(&secret_a).unwrap();
+        let (_, _, key3_b) = TripleLayerEncryption::derive_triple_keys(&secret_b).unwrap();
+        let nonce = [7u8; 12];
+        let ciphertext = colliding_ciphertext([&key3_a, &key3_b], &nonce);
+
+        // The outer layer alone opens under both keys
+        for key in [&key3_a, &key3_b] {
+            let opened =
+                ChaCha20Poly1305::new(key.into()).decrypt(&nonce.into(), ciphertext.as_slice());
+            assert!(opened.is_ok());
+        }
+
+        let mut session_a = TripleLayerEncryption::new(&secret_a).unwrap();
+        let mut session_b = TripleLayerEncryption::new(&secret_b).unwrap();
+        let mut message = EncryptedMessage {
+            version: crate::PROTOCOL_VERSION,
+            ciphertext,
+            layer1_nonce: [0u8; XCHACHA_NONCE_SIZE],
+            layer2_nonce: [0u8; AES_NONCE_SIZE],
+            layer3_nonce: nonce,
+            key_commitment: [0u8; COMMITMENT_SIZE],
+            counter: 1,
+            message_id: 1,
+            timestamp: 0,
+            expires_at: None,
+        };
+
+        // Without a valid commitment neither key gets as far as a layer, so
+        // the error cannot tell which key the collision matched
+        for session in [&mut session_a, &mut session_b] {
+            assert!(matches!(
+                session.decrypt(&message),
+                Err(CryptoError::DecryptionError(e)) if e == "Key commitment mismatch"
+            ));
+        }
+
+        // Committed to A: B is still rejected up front, A gets past layer 3
+        message.key_commitment = session_a
+            .ciphers
+            .with(|slot| {
+                let ciphers = slot.get()?;
+                Ok(ciphers.commitment(
+                    message.version,
+                    &message.layer1_nonce,
+                    &message.layer2_nonce,
+                    &nonce,
+                ))
+            })
+            .unwrap();
+        assert!(matches!(
+            session_b.decrypt(&message),
+            Err(CryptoError::DecryptionError(e)) if e == "Key commitment mismatch"
+        ));
+        assert!(matches!(
+            session_a.decrypt(&message),
+            Err(CryptoError::DecryptionError(e)) if e.starts_with("Layer 2")
+        ));
+    }
+
+    #[test]
+    fn test_key_commitment_is_checked() {
+        let mut encryptor = TripleLayerEncryption::new(&[23u8; KEY_SIZE]).unwrap();
+
+        let mut encrypted = encryptor.encrypt(b"committed").unwrap();
+        encrypted.key_commitment[0] ^= 1;
+        assert!(matches!(
+            encryptor.decrypt(&encrypted),
+            Err(CryptoError::DecryptionError(e)) if e == "Key commitment mismatch"
+        ));
+    }
+
     #[test]
     fn test_counter_increments() {
         let shared_secret = [99u8; KEY_SIZE];
@@ -400,6 +998,286 @@ mod tests {
         assert_eq!(msg3.counter, 3);
     }
 
+    #[test]
+    fn test_encrypt_with_caller_rng_and_clock() {
+        let shared_secret = [7u8; KEY_SIZE];
+        let mut encryptor = TripleLayerEncryption::new(&shared_secret).unwrap();
+
+        let plaintext = b"no_std friendly";
+        let clock = crate::clock::FixedClock(1_700_000_000_000);
+        let encrypted = encryptor
+            .encrypt_with(plaintext, &mut rand::thread_rng(), &clock)
+            .unwrap();
+        assert_eq!(encrypted.timestamp, 1_700_000_000_000);
+
+        let decrypted = encryptor.decrypt(&encrypted).unwrap();
+        assert_eq!(decrypted, plaintext.to_vec());
+    }
+
+    #[test]
+    fn test_padding_hides_length() {
+        let shared_secret = [21u8; KEY_SIZE];
+        let policy = crate::padding::PaddingPolicy::PowerOfTwo { min_bucket: 512 };
+        let mut encryptor = TripleLayerEncryption::new(&shared_secret)
+            .unwrap()
+            .with_padding(policy);
+
+        let short = encryptor.encrypt(b"ok").unwrap();
+        let long = encryptor.encrypt(&[b'x'; 400]).unwrap();
+        assert_eq!(short.ciphertext.len(), long.ciphertext.len());
+
+        assert_eq!(encryptor.decrypt(&short).unwrap(), b"ok".to_vec());
+        assert_eq!(encryptor.decrypt(&long).unwrap(), vec![b'x'; 400]);
+    }
+
+    #[test]
+    fn test_version_is_authenticated() {
+        let shared_secret = [22u8; KEY_SIZE];
+        let mut encryptor = TripleLayerEncryption::new(&shared_secret).unwrap();
+
+        let mut encrypted = encryptor.encrypt(b"versioned").unwrap();
+        encrypted.version = 1;
+        assert!(encryptor.decrypt(&encrypted).is_err());
+    }
+
+    #[test]
+    fn test_expiring_message() {
+        use crate::clock::FixedClock;
+
+        let shared_secret = [23u8; KEY_SIZE];
+        let mut encryptor = TripleLayerEncryption::new(&shared_secret).unwrap();
+
+        let sent = FixedClock(1_000_000);
+        let encrypted = encryptor
+            .encrypt_expiring_with(
+                b"burn after reading",
+                1_060_000,
+                &mut rand::thread_rng(),
+                &sent,
+            )
+            .unwrap();
+        assert_eq!(encrypted.expires_at, Some(1_060_000));
+
+        let before = FixedClock(1_059_999);
+        assert_eq!(
+            encryptor.decrypt_with(&encrypted, &before).unwrap(),
+            b"burn after reading".to_vec()
+        );
+
+        let after = FixedClock(1_060_000);
+        assert!(matches!(
+            encryptor.decrypt_with(&encrypted, &after),
+            Err(CryptoError::MessageExpired)
+        ));
+    }
+
+    #[test]
+    fn test_expiry_is_authenticated() {
+        use crate::clock::FixedClock;
+
+        let shared_secret = [24u8; KEY_SIZE];
+        let mut encryptor = TripleLayerEncryption::new(&shared_secret).unwrap();
+        let clock = FixedClock(1_000_000);
+
+        let encrypted = encryptor
+            .encrypt_expiring_with(b"short-lived", 1_060_000, &mut rand::thread_rng(), &clock)
+            .unwrap();
+
+        let mut extended = encrypted.clone();
+        extended.expires_at = Some(i64::MAX);
+        assert!(encryptor.decrypt_with(&extended, &clock).is_err());
+
+        let mut stripped = encrypted;
+        stripped.expires_at = None;
+        assert!(encryptor.decrypt_with(&stripped, &clock).is_err());
+
+        // Expiry in the past is refused at encryption time
+        assert!(encryptor
+            .encrypt_expiring_with(b"late", 999_999, &mut rand::thread_rng(), &clock)
+            .is_err());
+    }
+
+    #[test]
+    fn test_in_place_matches_copying_api() {
+        use crate::clock::FixedClock;
+        use rand_chacha::ChaCha20Rng;
+        use rand_core::SeedableRng;
+
+        let clock = FixedClock(1_700_000_000_000);
+        let plaintext = b"same bytes either way".to_vec();
+        let mut copying = TripleLayerEncryption::new(&[25u8; KEY_SIZE]).unwrap();
+        let mut in_place = TripleLayerEncryption::new(&[25u8; KEY_SIZE]).unwrap();
+
+        let expected = copying
+            .encrypt_with(&plaintext, &mut ChaCha20Rng::seed_from_u64(41), &clock)
+            .unwrap();
+        let message = in_place
+            .encrypt_in_place_with(
+                plaintext.clone(),
+                &mut ChaCha20Rng::seed_from_u64(41),
+                &clock,
+            )
+            .unwrap();
+        assert_eq!(message.ciphertext, expected.ciphertext);
+        assert_eq!(message.message_id, expected.message_id);
+
+        assert_eq!(
+            in_place.decrypt_in_place_with(message, &clock).unwrap(),
+            plaintext
+        );
+    }
+
+    #[test]
+    fn test_in_place_does_not_reallocate() {
+        let mut encryptor = TripleLayerEncryption::new(&[26u8; KEY_SIZE]).unwrap();
+        let plaintext = vec![0x5au8; 64 * 1024];
+
+        let mut buffer = Vec::with_capacity(encryptor.sealed_capacity(plaintext.len()));
+        buffer.extend_from_slice(&plaintext);
+        let ptr = buffer.as_ptr();
+
+        let message = encryptor.encrypt_in_place(buffer).unwrap();
+        assert_eq!(message.ciphertext.as_ptr(), ptr);
+
+        let decrypted = encryptor.decrypt_in_place(message).unwrap();
+        assert_eq!(decrypted.as_ptr(), ptr);
+        assert_eq!(decrypted, plaintext);
+    }
+
+    #[test]
+    fn test_sealed_capacity_bounds_every_policy() {
+        for policy in [
+            PaddingPolicy::None,
+            PaddingPolicy::Padme,
+            PaddingPolicy::PowerOfTwo { min_bucket: 256 },
+            PaddingPolicy::RandomRange { max_extra: 100 },
+        ] {
+            let mut encryptor = TripleLayerEncryption::new(&[27u8; KEY_SIZE])
+                .unwrap()
+                .with_padding(policy);
+            for len in [1usize, 255, 256, 1000] {
+                let message = encryptor.encrypt(&vec![1u8; len]).unwrap();
+                assert!(message.ciphertext.len() <= encryptor.sealed_capacity(len));
+            }
+        }
+    }
+
+    #[test]
+    fn test_batch_matches_sequential() {
+        use crate::clock::FixedClock;
+        use rand_chacha::ChaCha20Rng;
+        use rand_core::SeedableRng;
+
+        let clock = FixedClock(1_700_000_000_000);
+        // RandomRange draws a variable number of values from the RNG per message
+        let policy = PaddingPolicy::RandomRange { max_extra: 300 };
+        let plaintexts: Vec<Vec<u8>> = (0..200).map(|i| vec![i as u8; 1 + i * 7]).collect();
+
+        let mut sequential = TripleLayerEncryption::new(&[28u8; KEY_SIZE])
+            .unwrap()
+            .with_padding(policy);
+        let mut rng = ChaCha20Rng::seed_from_u64(42);
+        sequential.encrypt(b"earlier message").unwrap();
+        let expected: Vec<_> = plaintexts
+            .iter()
+            .map(|plaintext| {
+                sequential
+                    .encrypt_with(plaintext, &mut rng, &clock)
+                    .unwrap()
+            })
+            .collect();
+
+        let mut batch = TripleLayerEncryption::new(&[28u8; KEY_SIZE])
+            .unwrap()
+            .with_padding(policy);
+        let mut rng = ChaCha20Rng::seed_from_u64(42);
+        batch.encrypt(b"earlier message").unwrap();
+        let messages = batch
+            .encrypt_batch_with(&plaintexts, &mut rng, &clock)
+            .unwrap();
+
+        assert_eq!(
+            serde_json::to_string(&messages).unwrap(),
+            serde_json::to_string(&expected).unwrap()
+        );
+        assert_eq!(messages.first().unwrap().counter, 2);
+        assert_eq!(batch.get_counter(), sequential.get_counter());
+
+        let decrypted = batch.decrypt_batch_with(&messages, &clock).unwrap();
+        for (result, plaintext) in decrypted.into_iter().zip(&plaintexts) {
+            assert_eq!(&result.unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn test_batch_decrypt_reports_each_failure() {
+        use crate::clock::FixedClock;
+
+        let clock = FixedClock(1_000_000);
+        let mut encryptor = TripleLayerEncryption::new(&[29u8; KEY_SIZE]).unwrap();
+        let mut messages = encryptor.encrypt_batch(&[b"one", b"two", b"six"]).unwrap();
+        messages.push(
+            encryptor
+                .encrypt_expiring_with(b"gone", 1_000_001, &mut rand::thread_rng(), &clock)
+                .unwrap(),
+        );
+        messages[1].ciphertext[0] ^= 1;
+
+        let later = FixedClock(2_000_000);
+        let results = encryptor.decrypt_batch_with(&messages, &later).unwrap();
+        assert_eq!(results[0].as_ref().unwrap(), b"one");
+        assert!(matches!(results[1], Err(CryptoError::DecryptionError(_))));
+        assert_eq!(results[2].as_ref().unwrap(), b"six");
+        assert!(matches!(results[3], Err(CryptoError::MessageExpired)));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_nested_batches_on_session_clones() {
+        use rayon::prelude::*;
+
+        // Outer jobs clone the session while inner batches run on the same
+        // two workers; nothing may be holding a cipher lock across a batch
+        let session = TripleLayerEncryption::new(&[31u8; KEY_SIZE]).unwrap();
+        let plaintexts: Vec<Vec<u8>> = (0..64).map(|i| vec![i as u8; 1 + i]).collect();
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .build()
+            .unwrap();
+
+        pool.install(|| {
+            (0..16).into_par_iter().for_each(|_| {
+                let mut clone = session.clone();
+                let messages = clone.encrypt_batch(&plaintexts).unwrap();
+                let results = clone.decrypt_batch(&messages).unwrap();
+                assert!(results
+                    .iter()
+                    .zip(&plaintexts)
+                    .all(|(r, p)| r.as_ref().ok() == Some(p)));
+            })
+        });
+    }
+
+    #[test]
+    fn test_batch_rejects_invalid_plaintext_up_front() {
+        let mut encryptor = TripleLayerEncryption::new(&[30u8; KEY_SIZE]).unwrap();
+        encryptor.encrypt(b"first").unwrap();
+
+        let plaintexts: [&[u8]; 3] = [b"fine", b"", b"also fine"];
+        assert!(encryptor.encrypt_batch(&plaintexts).is_err());
+        assert_eq!(encryptor.get_counter(), 1);
+
+        assert!(encryptor.encrypt_batch::<&[u8]>(&[]).unwrap().is_empty());
+        assert_eq!(encryptor.get_counter(), 1);
+
+        encryptor.message_counter = u64::MAX - 1;
+        assert!(encryptor.encrypt_batch(&[b"a", b"b"]).is_err());
+        assert_eq!(
+            encryptor.encrypt_batch(&[b"a"]).unwrap()[0].counter,
+            u64::MAX
+        );
+    }
+
     #[test]
     fn test_empty_message_rejected() {
         let shared_secret = [0u8; KEY_SIZE];
@@ -418,4 +1296,45 @@ mod tests {
         let result = encryptor.encrypt(&huge_message);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_unbounded_random_padding_rejected() {
+        let policy = PaddingPolicy::RandomRange {
+            max_extra: usize::MAX,
+        };
+        let mut encryptor = TripleLayerEncryption::new(&[0u8; KEY_SIZE])
+            .unwrap()
+            .with_padding(policy);
+        assert!(matches!(
+            encryptor.encrypt(b"hello"),
+            Err(CryptoError::EncryptionError(_))
+        ));
+        assert!(matches!(
+            encryptor.encrypt_batch(&[b"hello"]),
+            Err(CryptoError::EncryptionError(_))
+        ));
+        assert_eq!(encryptor.sealed_capacity(5), usize::MAX);
+    }
+
+    #[test]
+    fn test_json_uses_base64() {
+        let mut alice = TripleLayerEncryption::new(&[29u8; KEY_SIZE]).unwrap();
+        let mut bob = TripleLayerEncryption::new(&[29u8; KEY_SIZE]).unwrap();
+        let message = alice.encrypt(&[0u8; 1000]).unwrap();
+
+        let json: serde_json::Value = serde_json::to_value(&message).unwrap();
+        for field in [
+            "ciphertext",
+            "layer1_nonce",
+            "layer2_nonce",
+            "layer3_nonce",
+            "key_commitment",
+        ] {
+            assert!(json[field].is_string(), "{} is not a string", field);
+        }
+        assert!(json.to_string().len() < message.ciphertext.len() * 2);
+
+        let restored: EncryptedMessage = serde_json::from_value(json).unwrap();
+        assert_eq!(bob.decrypt(&restored).unwrap(), vec![0u8; 1000]);
+    }
 }
@@ -0,0 +1,293 @@
+//! Signed Envelope (Sign-then-Encrypt)
+//!
+//! `TripleLayerEncryption` only proves that the sender knows the shared key.
+//! In a group every member knows it, so anyone could claim to be anyone.
+//! `SignedEnvelope` signs the plaintext with the sender's Ed25519 identity key
+//! before encryption. The signature covers the sender ID, the recipient ID and
+//! a context string, so a message can neither be attributed to someone else nor
+//! forwarded to a different recipient or protocol under the original signature.
+//!
+//! Inner plaintext: `message || signature (64)`
+
+use crate::clock::Clock;
+use crate::encryption::{EncryptedMessage, TripleLayerEncryption};
+use crate::key_exchange::{verify_signature, SIGNATURE_SIZE};
+use crate::keystore::KeyStore;
+use crate::{CryptoError, CryptoResult};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use rand_core::CryptoRngCore;
+use serde::{Deserialize, Serialize};
+
+/// Domain separator for envelope signatures
+const SIGNATURE_DOMAIN: &[u8] = b"chakchat_signed_envelope_v1";
+
+/// Encrypted message bound to its sender's identity key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedEnvelope {
+    /// Claimed sender (used to look up the verifying key)
+    pub sender_id: String,
+
+    /// Intended recipient (user or group ID)
+    pub recipient_id: String,
+
+    /// Encrypted `plaintext || signature`
+    pub message: EncryptedMessage,
+}
+
+impl SignedEnvelope {
+    /// Sign and encrypt a message
+    ///
+    /// # Arguments
+    /// * `sender` - Sender's identity key (any `KeyStore` backend)
+    /// * `sender_id` - Sender's user ID
+    /// * `recipient_id` - Recipient user or group ID
+    /// * `context` - Protocol context, e.g. `"chat"` or `"group:<id>"`
+    /// * `plaintext` - Message to send
+    /// * `cipher` - Session cipher shared with the recipient
+    #[cfg(feature = "std")]
+    pub fn seal(
+        sender: &dyn KeyStore,
+        sender_id: &str,
+        recipient_id: &str,
+        context: &str,
+        plaintext: &[u8],
+        cipher: &mut TripleLayerEncryption,
+    ) -> CryptoResult<Self> {
+        Self::seal_with(
+            sender,
+            sender_id,
+            recipient_id,
+            context,
+            plaintext,
+            cipher,
+            &mut rand::thread_rng(),
+            &crate::clock::SystemClock,
+        )
+    }
+
+    /// Sign and encrypt a message using a caller-supplied RNG and clock
+    #[allow(clippy::too_many_arguments)]
+    pub fn seal_with(
+        sender: &dyn KeyStore,
+        sender_id: &str,
+        recipient_id: &str,
+        context: &str,
+        plaintext: &[u8],
+        cipher: &mut TripleLayerEncryption,
+        rng: &mut impl CryptoRngCore,
+        clock: &dyn Clock,
+    ) -> CryptoResult<Self> {
+        let transcript = signed_transcript(context, sender_id, recipient_id, plaintext);
+        let signature = sender.sign(&transcript)?;
+
+        let mut inner = Vec::with_capacity(plaintext.len() + SIGNATURE_SIZE);
+        inner.extend_from_slice(plaintext);
+        inner.extend_from_slice(&signature);
+
+        let message = cipher.encrypt_with(&inner, rng, clock)?;
+
+        Ok(SignedEnvelope {
+            sender_id: sender_id.to_string(),
+            recipient_id: recipient_id.to_string(),
+            message,
+        })
+    }
+
+    /// Decrypt and verify a message
+    ///
+    /// # Arguments
+    /// * `sender_verifying_key` - Ed25519 key registered for `self.sender_id`
+    /// * `recipient_id` - Our own user or group ID
+    /// * `context` - Expected protocol context
+    /// * `cipher` - Session cipher shared with the sender
+    ///
+    /// # Errors
+    /// * `RecipientMismatch` - envelope addressed to someone else
+    /// * `DecryptionError` - ciphertext could not be decrypted
+    /// * `SignatureVerificationFailed` - decrypted, but not signed by the sender
+    #[cfg(feature = "std")]
+    pub fn open(
+        &self,
+        sender_verifying_key: &[u8; 32],
+        recipient_id: &str,
+        context: &str,
+        cipher: &mut TripleLayerEncryption,
+    ) -> CryptoResult<Vec<u8>> {
+        self.open_with(
+            sender_verifying_key,
+            recipient_id,
+            context,
+            cipher,
+            &crate::clock::SystemClock,
+        )
+    }
+
+    /// Decrypt and verify a message, checking expiry against `clock`
+    pub fn open_with(
+        &self,
+        sender_verifying_key: &[u8; 32],
+        recipient_id: &str,
+        context: &str,
+        cipher: &mut TripleLayerEncryption,
+        clock: &dyn Clock,
+    ) -> CryptoResult<Vec<u8>> {
+        if self.recipient_id != recipient_id {
+            return Err(CryptoError::RecipientMismatch);
+        }
+
+        let mut inner = cipher.decrypt_with(&self.message, clock)?;
+
+        if inner.len() < SIGNATURE_SIZE {
+            return Err(CryptoError::DecryptionError(
+                "Envelope too short for signature".to_string(),
+            ));
+        }
+
+        let split = inner.len() - SIGNATURE_SIZE;
+        let signature: [u8; SIGNATURE_SIZE] = inner[split..]
+            .try_into()
+            .map_err(|_| CryptoError::DecryptionError("Invalid signature length".to_string()))?;
+        inner.truncate(split);
+
+        let transcript = signed_transcript(context, &self.sender_id, &self.recipient_id, &inner);
+        verify_signature(sender_verifying_key, &transcript, &signature)?;
+
+        Ok(inner)
+    }
+}
+
+/// Length-prefixed transcript covered by the sender's signature
+fn signed_transcript(
+    context: &str,
+    sender_id: &str,
+    recipient_id: &str,
+    plaintext: &[u8],
+) -> Vec<u8> {
+    let fields: [&[u8]; 4] = [
+        context.as_bytes(),
+        sender_id.as_bytes(),
+        recipient_id.as_bytes(),
+        plaintext,
+    ];
+
+    let len = SIGNATURE_DOMAIN.len() + fields.iter().map(|f| 8 + f.len()).sum::<usize>();
+    let mut transcript = Vec::with_capacity(len);
+    transcript.extend_from_slice(SIGNATURE_DOMAIN);
+    for field in fields {
+        transcript.extend_from_slice(&(field.len() as u64).to_be_bytes());
+        transcript.extend_from_slice(field);
+    }
+    transcript
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::KEY_SIZE;
+    use crate::KeyPair;
+
+    fn group_ciphers() -> (TripleLayerEncryption, TripleLayerEncryption) {
+        let group_key = [77u8; KEY_SIZE];
+        (
+            TripleLayerEncryption::new(&group_key).unwrap(),
+            TripleLayerEncryption::new(&group_key).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let alice = KeyPair::generate().unwrap();
+        let (mut alice_cipher, mut bob_cipher) = group_ciphers();
+
+        let envelope = SignedEnvelope::seal(
+            &alice,
+            "alice",
+            "group-1",
+            "chat",
+            b"hi all",
+            &mut alice_cipher,
+        )
+        .unwrap();
+        let plaintext = envelope
+            .open(&alice.verifying_key, "group-1", "chat", &mut bob_cipher)
+            .unwrap();
+
+        assert_eq!(plaintext, b"hi all".to_vec());
+    }
+
+    #[test]
+    fn test_group_member_cannot_impersonate() {
+        let alice = KeyPair::generate().unwrap();
+        let mallory = KeyPair::generate().unwrap();
+        let (mut mallory_cipher, mut bob_cipher) = group_ciphers();
+
+        // Mallory knows the group key but signs with her own identity
+        let forged = SignedEnvelope::seal(
+            &mallory,
+            "alice",
+            "group-1",
+            "chat",
+            b"send money",
+            &mut mallory_cipher,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            forged.open(&alice.verifying_key, "group-1", "chat", &mut bob_cipher),
+            Err(CryptoError::SignatureVerificationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_signature_binds_ids_and_context() {
+        let alice = KeyPair::generate().unwrap();
+        let (mut alice_cipher, mut bob_cipher) = group_ciphers();
+
+        let envelope = SignedEnvelope::seal(
+            &alice,
+            "alice",
+            "bob",
+            "chat",
+            b"for bob",
+            &mut alice_cipher,
+        )
+        .unwrap();
+
+        // Re-addressed envelope: signature no longer matches
+        let mut forwarded = envelope.clone();
+        forwarded.recipient_id = "carol".to_string();
+        assert!(matches!(
+            forwarded.open(&alice.verifying_key, "carol", "chat", &mut bob_cipher),
+            Err(CryptoError::SignatureVerificationFailed)
+        ));
+
+        assert!(matches!(
+            envelope.open(&alice.verifying_key, "bob", "prekey", &mut bob_cipher),
+            Err(CryptoError::SignatureVerificationFailed)
+        ));
+
+        assert!(matches!(
+            envelope.open(&alice.verifying_key, "carol", "chat", &mut bob_cipher),
+            Err(CryptoError::RecipientMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_decryption_failure_reported_separately() {
+        let alice = KeyPair::generate().unwrap();
+        let (mut alice_cipher, _) = group_ciphers();
+        let mut outsider = TripleLayerEncryption::new(&[1u8; KEY_SIZE]).unwrap();
+
+        let envelope =
+            SignedEnvelope::seal(&alice, "alice", "bob", "chat", b"secret", &mut alice_cipher)
+                .unwrap();
+
+        assert!(matches!(
+            envelope.open(&alice.verifying_key, "bob", "chat", &mut outsider),
+            Err(CryptoError::DecryptionError(_))
+        ));
+    }
+}
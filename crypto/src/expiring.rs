@@ -0,0 +1,480 @@
+//! Expiring Per-Message Keys
+//!
+//! Disappearing messages are stored encrypted under their own random key.
+//! `ExpiringKeyStore` keeps those keys in memory and zeroizes them once the
+//! message expires, so the stored ciphertext (including copies in backups)
+//! becomes permanently undecryptable.
+//!
+//! Stored blob format: `nonce (24) || XChaCha20-Poly1305(ciphertext || tag)`,
+//! with `message_id || expires_at` as associated data.
+//!
+//! The keys survive a restart only through `export` and `load`, which seal
+//! the live keys under a caller-held wrapping key (e.g. the storage master
+//! key). Expired entries are dropped on both sides, but an exported snapshot
+//! still holds every key that was live when it was written: re-export and
+//! overwrite it after each purge, or expired messages stay readable from the
+//! old snapshot.
+//!
+//! Snapshot format:
+//! `magic (8) || version (1) || nonce (24) || XChaCha20-Poly1305((message_id (8) || expires_at (8) || key (32))* || tag)`,
+//! with the header as associated data.
+
+use crate::clock::Clock;
+use crate::encryption::{KEY_SIZE, XCHACHA_NONCE_SIZE};
+use crate::registry::Secret;
+use crate::{CryptoError, CryptoResult};
+use alloc::{collections::BTreeMap, format, string::ToString, vec::Vec};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use core::fmt;
+use rand_core::CryptoRngCore;
+use zeroize::Zeroizing;
+
+/// Key snapshot magic
+pub const SNAPSHOT_MAGIC: &[u8; 8] = b"CHAKEXP\0";
+
+/// Key snapshot format version
+pub const SNAPSHOT_VERSION: u8 = 1;
+
+/// Snapshot header size (magic, version, nonce)
+const SNAPSHOT_HEADER_SIZE: usize = 8 + 1 + XCHACHA_NONCE_SIZE;
+
+/// Snapshot entry size (message ID, expiry, key)
+const SNAPSHOT_ENTRY_SIZE: usize = 8 + 8 + KEY_SIZE;
+
+/// Per-message key with its expiry; the key is wiped by `registry::panic_wipe()`
+struct ExpiringKey {
+    key: Secret<[u8; KEY_SIZE]>,
+    expires_at: i64,
+}
+
+/// In-memory store of per-message keys that self-destruct on expiry
+#[derive(Default)]
+pub struct ExpiringKeyStore {
+    keys: BTreeMap<u64, ExpiringKey>,
+}
+
+impl fmt::Debug for ExpiringKeyStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExpiringKeyStore")
+            .field("keys", &self.keys.len())
+            .finish()
+    }
+}
+
+impl ExpiringKeyStore {
+    /// Create empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encrypt a message under a fresh per-message key
+    ///
+    /// # Arguments
+    /// * `message_id` - ID the key is stored under
+    /// * `plaintext` - Message to store
+    /// * `expires_at` - Unix milliseconds at which the key is destroyed
+    /// * `rng` - Source for the key and nonce
+    /// * `clock` - Current time, used to reject expiries in the past
+    ///
+    /// # Returns
+    /// Blob to persist; decryptable only while the key is in the store
+    ///
+    /// # Errors
+    /// `EncryptionError` if the expiry is in the past or `message_id` already
+    /// has a live key (replacing it would orphan the earlier blob)
+    pub fn seal(
+        &mut self,
+        message_id: u64,
+        plaintext: &[u8],
+        expires_at: i64,
+        rng: &mut impl CryptoRngCore,
+        clock: &dyn Clock,
+    ) -> CryptoResult<Vec<u8>> {
+        if expires_at <= clock.now_millis() {
+            return Err(CryptoError::EncryptionError(
+                "Expiry must be in the future".to_string(),
+            ));
+        }
+        if self.keys.contains_key(&message_id) {
+            return Err(CryptoError::EncryptionError(
+                "Message ID already has a key".to_string(),
+            ));
+        }
+
+        let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+        rng.fill_bytes(key.as_mut());
+
+        let mut nonce = [0u8; XCHACHA_NONCE_SIZE];
+        rng.fill_bytes(&mut nonce);
+
+        let aad = Self::associated_data(message_id, expires_at);
+        let cipher = XChaCha20Poly1305::new(key.as_ref().into());
+        let ciphertext = cipher
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|e| CryptoError::EncryptionError(e.to_string()))?;
+        let entry = ExpiringKey {
+            key: Secret::new(*key),
+            expires_at,
+        };
+
+        let mut blob = Vec::with_capacity(XCHACHA_NONCE_SIZE + ciphertext.len());
+        blob.extend_from_slice(&nonce);
+        blob.extend_from_slice(&ciphertext);
+
+        self.keys.insert(message_id, entry);
+        Ok(blob)
+    }
+
+    /// Decrypt a stored message if its key is still alive
+    ///
+    /// Expired keys are destroyed first, so an expired message fails with
+    /// `CryptoError::MessageExpired` and stays unreadable afterwards. After
+    /// `registry::panic_wipe()` every open fails with `CryptoError::Wiped`.
+    pub fn open(
+        &mut self,
+        message_id: u64,
+        blob: &[u8],
+        clock: &dyn Clock,
+    ) -> CryptoResult<Vec<u8>> {
+        self.purge_expired(clock);
+
+        let entry = self
+            .keys
+            .get(&message_id)
+            .ok_or(CryptoError::MessageExpired)?;
+
+        if blob.len() < XCHACHA_NONCE_SIZE {
+            return Err(CryptoError::DecryptionError("Blob too short".to_string()));
+        }
+        let (nonce, ciphertext) = blob.split_at(XCHACHA_NONCE_SIZE);
+
+        let aad = Self::associated_data(message_id, entry.expires_at);
+        entry.key.with(|key| {
+            XChaCha20Poly1305::new(key.into())
+                .decrypt(
+                    XNonce::from_slice(nonce),
+                    Payload {
+                        msg: ciphertext,
+                        aad: &aad,
+                    },
+                )
+                .map_err(|e| CryptoError::DecryptionError(e.to_string()))
+        })
+    }
+
+    /// Destroy every key whose expiry has passed
+    ///
+    /// # Returns
+    /// Number of keys destroyed
+    pub fn purge_expired(&mut self, clock: &dyn Clock) -> usize {
+        let now = clock.now_millis();
+        let before = self.keys.len();
+        // Dropping an entry zeroizes its key
+        self.keys.retain(|_, entry| entry.expires_at > now);
+        before - self.keys.len()
+    }
+
+    /// Destroy the key for one message immediately
+    pub fn remove(&mut self, message_id: u64) -> bool {
+        self.keys.remove(&message_id).is_some()
+    }
+
+    /// Earliest pending expiry, for scheduling the next purge
+    pub fn next_expiry(&self) -> Option<i64> {
+        self.keys.values().map(|entry| entry.expires_at).min()
+    }
+
+    /// Seal the live keys for persistence
+    ///
+    /// Expired keys are purged first and never written. The snapshot replaces
+    /// any earlier one; keep only the latest.
+    ///
+    /// # Arguments
+    /// * `wrapping_key` - Key the snapshot is sealed under
+    /// * `rng` - Source for the nonce
+    /// * `clock` - Current time, for the purge
+    pub fn export(
+        &mut self,
+        wrapping_key: &[u8; KEY_SIZE],
+        rng: &mut impl CryptoRngCore,
+        clock: &dyn Clock,
+    ) -> CryptoResult<Vec<u8>> {
+        self.purge_expired(clock);
+
+        let mut entries = Zeroizing::new(Vec::with_capacity(self.keys.len() * SNAPSHOT_ENTRY_SIZE));
+        for (message_id, entry) in &self.keys {
+            entries.extend_from_slice(&message_id.to_be_bytes());
+            entries.extend_from_slice(&entry.expires_at.to_be_bytes());
+            entry.key.with(|key| {
+                entries.extend_from_slice(key);
+                Ok(())
+            })?;
+        }
+
+        let mut nonce = [0u8; XCHACHA_NONCE_SIZE];
+        rng.fill_bytes(&mut nonce);
+
+        let mut snapshot = Vec::with_capacity(SNAPSHOT_HEADER_SIZE + entries.len() + 16);
+        snapshot.extend_from_slice(SNAPSHOT_MAGIC);
+        snapshot.push(SNAPSHOT_VERSION);
+        snapshot.extend_from_slice(&nonce);
+
+        let ciphertext = XChaCha20Poly1305::new(wrapping_key.into())
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &entries,
+                    aad: &snapshot,
+                },
+            )
+            .map_err(|e| CryptoError::EncryptionError(e.to_string()))?;
+        snapshot.extend_from_slice(&ciphertext);
+        Ok(snapshot)
+    }
+
+    /// Restore a store from a snapshot written by `export`
+    ///
+    /// Keys that expired while the snapshot was at rest are dropped.
+    ///
+    /// # Errors
+    /// * `DecryptionError` - not a snapshot, unsupported version, wrong
+    ///   wrapping key or corrupted data
+    pub fn load(
+        wrapping_key: &[u8; KEY_SIZE],
+        snapshot: &[u8],
+        clock: &dyn Clock,
+    ) -> CryptoResult<Self> {
+        if snapshot.len() < SNAPSHOT_HEADER_SIZE || &snapshot[..8] != SNAPSHOT_MAGIC {
+            return Err(CryptoError::DecryptionError(
+                "Not a key snapshot".to_string(),
+            ));
+        }
+        if snapshot[8] != SNAPSHOT_VERSION {
+            return Err(CryptoError::DecryptionError(format!(
+                "Unsupported key snapshot version {}",
+                snapshot[8]
+            )));
+        }
+
+        let entries = Zeroizing::new(
+            XChaCha20Poly1305::new(wrapping_key.into())
+                .decrypt(
+                    XNonce::from_slice(&snapshot[9..SNAPSHOT_HEADER_SIZE]),
+                    Payload {
+                        msg: &snapshot[SNAPSHOT_HEADER_SIZE..],
+                        aad: &snapshot[..SNAPSHOT_HEADER_SIZE],
+                    },
+                )
+                .map_err(|_| {
+                    CryptoError::DecryptionError(
+                        "Wrong wrapping key or corrupted snapshot".to_string(),
+                    )
+                })?,
+        );
+        if entries.len() % SNAPSHOT_ENTRY_SIZE != 0 {
+            return Err(CryptoError::DecryptionError(
+                "Malformed key snapshot".to_string(),
+            ));
+        }
+
+        let now = clock.now_millis();
+        let mut store = ExpiringKeyStore::new();
+        for chunk in entries.chunks_exact(SNAPSHOT_ENTRY_SIZE) {
+            let mut id = [0u8; 8];
+            let mut expiry = [0u8; 8];
+            id.copy_from_slice(&chunk[..8]);
+            expiry.copy_from_slice(&chunk[8..16]);
+
+            let expires_at = i64::from_be_bytes(expiry);
+            if expires_at > now {
+                let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+                key.copy_from_slice(&chunk[16..]);
+                let entry = ExpiringKey {
+                    key: Secret::new(*key),
+                    expires_at,
+                };
+                store.keys.insert(u64::from_be_bytes(id), entry);
+            }
+        }
+        Ok(store)
+    }
+
+    /// Number of live keys
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Whether the store holds no keys
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    fn associated_data(message_id: u64, expires_at: i64) -> [u8; 16] {
+        let mut aad = [0u8; 16];
+        aad[..8].copy_from_slice(&message_id.to_be_bytes());
+        aad[8..].copy_from_slice(&expires_at.to_be_bytes());
+        aad
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+
+    #[test]
+    fn test_seal_open_before_expiry() {
+        let mut store = ExpiringKeyStore::new();
+        let clock = FixedClock(1_000);
+
+        let blob = store
+            .seal(7, b"see you soon", 2_000, &mut rand::thread_rng(), &clock)
+            .unwrap();
+        assert_eq!(store.len(), 1);
+        assert_eq!(
+            store.open(7, &blob, &FixedClock(1_999)).unwrap(),
+            b"see you soon".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_expired_key_destroyed() {
+        let mut store = ExpiringKeyStore::new();
+        let clock = FixedClock(1_000);
+
+        let blob = store
+            .seal(1, b"gone", 2_000, &mut rand::thread_rng(), &clock)
+            .unwrap();
+        let backup = blob.clone();
+
+        assert!(matches!(
+            store.open(1, &blob, &FixedClock(2_000)),
+            Err(CryptoError::MessageExpired)
+        ));
+        assert!(store.is_empty());
+
+        // Rewinding the clock does not bring the key back
+        assert!(matches!(
+            store.open(1, &backup, &clock),
+            Err(CryptoError::MessageExpired)
+        ));
+    }
+
+    #[test]
+    fn test_purge_only_expired() {
+        let mut store = ExpiringKeyStore::new();
+        let clock = FixedClock(0);
+        let mut rng = rand::thread_rng();
+
+        store.seal(1, b"a", 100, &mut rng, &clock).unwrap();
+        store.seal(2, b"b", 200, &mut rng, &clock).unwrap();
+        store.seal(3, b"c", 300, &mut rng, &clock).unwrap();
+        assert_eq!(store.next_expiry(), Some(100));
+
+        assert_eq!(store.purge_expired(&FixedClock(200)), 2);
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.next_expiry(), Some(300));
+    }
+
+    #[test]
+    fn test_blob_bound_to_message_id() {
+        let mut store = ExpiringKeyStore::new();
+        let clock = FixedClock(0);
+        let mut rng = rand::thread_rng();
+
+        let blob1 = store.seal(1, b"first", 100, &mut rng, &clock).unwrap();
+        store.seal(2, b"second", 100, &mut rng, &clock).unwrap();
+
+        assert!(store.open(2, &blob1, &clock).is_err());
+    }
+
+    #[test]
+    fn test_export_and_load() {
+        let mut store = ExpiringKeyStore::new();
+        let clock = FixedClock(0);
+        let mut rng = rand::thread_rng();
+        let wrapping_key = [5u8; KEY_SIZE];
+
+        let short = store.seal(1, b"short", 100, &mut rng, &clock).unwrap();
+        let long = store.seal(2, b"long", 300, &mut rng, &clock).unwrap();
+        let snapshot = store.export(&wrapping_key, &mut rng, &clock).unwrap();
+
+        // Restart: both keys come back while live
+        let mut restored =
+            ExpiringKeyStore::load(&wrapping_key, &snapshot, &FixedClock(50)).unwrap();
+        assert_eq!(restored.len(), 2);
+        assert_eq!(
+            restored.open(1, &short, &FixedClock(50)).unwrap(),
+            b"short".to_vec()
+        );
+
+        // Keys that expired at rest are dropped on load
+        let mut restored =
+            ExpiringKeyStore::load(&wrapping_key, &snapshot, &FixedClock(200)).unwrap();
+        assert_eq!(restored.len(), 1);
+        assert!(matches!(
+            restored.open(1, &short, &FixedClock(200)),
+            Err(CryptoError::MessageExpired)
+        ));
+        assert_eq!(
+            restored.open(2, &long, &FixedClock(200)).unwrap(),
+            b"long".to_vec()
+        );
+
+        // Expired keys are never exported
+        let later = store
+            .export(&wrapping_key, &mut rng, &FixedClock(200))
+            .unwrap();
+        assert_eq!(later.len(), snapshot.len() - SNAPSHOT_ENTRY_SIZE);
+    }
+
+    #[test]
+    fn test_load_rejects_bad_snapshot() {
+        let mut store = ExpiringKeyStore::new();
+        let clock = FixedClock(0);
+        let mut rng = rand::thread_rng();
+        store.seal(1, b"a", 100, &mut rng, &clock).unwrap();
+        let snapshot = store.export(&[5u8; KEY_SIZE], &mut rng, &clock).unwrap();
+
+        assert!(ExpiringKeyStore::load(&[6u8; KEY_SIZE], &snapshot, &clock).is_err());
+
+        let mut tampered = snapshot.clone();
+        tampered[8] = 2;
+        assert!(ExpiringKeyStore::load(&[5u8; KEY_SIZE], &tampered, &clock).is_err());
+
+        let mut tampered = snapshot;
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(matches!(
+            ExpiringKeyStore::load(&[5u8; KEY_SIZE], &tampered, &clock),
+            Err(CryptoError::DecryptionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_duplicate_message_id_rejected() {
+        let mut store = ExpiringKeyStore::new();
+        let clock = FixedClock(0);
+        let mut rng = rand::thread_rng();
+
+        let blob = store.seal(1, b"first", 100, &mut rng, &clock).unwrap();
+        assert!(matches!(
+            store.seal(1, b"second", 200, &mut rng, &clock),
+            Err(CryptoError::EncryptionError(_))
+        ));
+
+        // The original key survives
+        assert_eq!(store.open(1, &blob, &clock).unwrap(), b"first".to_vec());
+
+        // Once destroyed, the ID can be reused
+        store.remove(1);
+        store.seal(1, b"third", 100, &mut rng, &clock).unwrap();
+    }
+}
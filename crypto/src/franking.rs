@@ -0,0 +1,457 @@
+//! Message Franking
+//!
+//! End-to-end encryption leaves moderators unable to confirm an abuse
+//! report: the recipient could simply invent the message. Franking fixes
+//! this without giving the relay the plaintext:
+//! 1. The sender picks a random franking key per message and commits to the
+//!    plaintext with `HMAC-SHA256(franking_key, transcript)`
+//! 2. The franking key travels inside the ciphertext; the commitment travels
+//!    next to it in the clear
+//! 3. The relay signs the commitment together with the authenticated sender,
+//!    the recipient and a timestamp (`RelayStamp`)
+//! 4. The recipient checks the commitment on decryption. To report, it
+//!    reveals the plaintext and franking key in an `AbuseReport`, which a
+//!    moderator verifies against the relay's public key
+//!
+//! Inner plaintext: `message || franking_key (32)`
+
+use crate::clock::Clock;
+use crate::encryption::{EncryptedMessage, TripleLayerEncryption};
+use crate::key_exchange::{verify_signature, SIGNATURE_SIZE};
+use crate::keystore::KeyStore;
+use crate::utils::{constant_time_compare, hmac_sha256};
+use crate::{CryptoError, CryptoResult};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use rand_core::CryptoRngCore;
+use serde::{Deserialize, Serialize};
+
+/// Per-message franking key size
+pub const FRANKING_KEY_SIZE: usize = 32;
+
+/// Domain separator for franking commitments
+const COMMITMENT_DOMAIN: &[u8] = b"chakchat_franking_commitment_v1";
+
+/// Domain separator for relay stamps
+const STAMP_DOMAIN: &[u8] = b"chakchat_franking_stamp_v1";
+
+/// Relay's signature over a message commitment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayStamp {
+    /// Time the relay accepted the message (Unix milliseconds)
+    pub timestamp: i64,
+
+    /// Relay signature over commitment, sender, recipient and timestamp
+    #[serde(with = "crate::encoding::base64_array")]
+    pub signature: [u8; SIGNATURE_SIZE],
+}
+
+/// Encrypted message with a franking commitment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrankedMessage {
+    /// Sender as authenticated by the relay
+    pub sender_id: String,
+
+    /// Intended recipient (user or group ID)
+    pub recipient_id: String,
+
+    /// Commitment to the plaintext under the franking key
+    #[serde(with = "crate::encoding::base64_array")]
+    pub commitment: [u8; 32],
+
+    /// Encrypted `plaintext || franking_key`
+    pub message: EncryptedMessage,
+
+    /// Relay signature, attached in transit
+    pub stamp: Option<RelayStamp>,
+}
+
+/// Decrypted franked message
+#[derive(Debug, Clone)]
+pub struct OpenedMessage {
+    /// Message plaintext
+    pub plaintext: Vec<u8>,
+
+    /// Franking key that opens the commitment
+    pub franking_key: [u8; FRANKING_KEY_SIZE],
+}
+
+/// Everything a moderator needs to verify a reported message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AbuseReport {
+    /// Reported sender
+    pub sender_id: String,
+
+    /// Reporting recipient
+    pub recipient_id: String,
+
+    /// Protocol context the message was sent in
+    pub context: String,
+
+    /// Revealed message plaintext
+    #[serde(with = "crate::encoding::base64_bytes")]
+    pub plaintext: Vec<u8>,
+
+    /// Revealed franking key
+    #[serde(with = "crate::encoding::base64_array")]
+    pub franking_key: [u8; FRANKING_KEY_SIZE],
+
+    /// Commitment signed by the relay
+    #[serde(with = "crate::encoding::base64_array")]
+    pub commitment: [u8; 32],
+
+    /// Relay stamp over the commitment
+    pub stamp: RelayStamp,
+}
+
+impl FrankedMessage {
+    /// Commit to and encrypt a message
+    ///
+    /// # Arguments
+    /// * `sender_id` - Sender's user ID
+    /// * `recipient_id` - Recipient user or group ID
+    /// * `context` - Protocol context, e.g. `"chat"` or `"group:<id>"`
+    /// * `plaintext` - Message to send
+    /// * `cipher` - Session cipher shared with the recipient
+    #[cfg(feature = "std")]
+    pub fn seal(
+        sender_id: &str,
+        recipient_id: &str,
+        context: &str,
+        plaintext: &[u8],
+        cipher: &mut TripleLayerEncryption,
+    ) -> CryptoResult<Self> {
+        Self::seal_with(
+            sender_id,
+            recipient_id,
+            context,
+            plaintext,
+            cipher,
+            &mut rand::thread_rng(),
+            &crate::clock::SystemClock,
+        )
+    }
+
+    /// Commit to and encrypt a message using a caller-supplied RNG and clock
+    pub fn seal_with(
+        sender_id: &str,
+        recipient_id: &str,
+        context: &str,
+        plaintext: &[u8],
+        cipher: &mut TripleLayerEncryption,
+        rng: &mut impl CryptoRngCore,
+        clock: &dyn Clock,
+    ) -> CryptoResult<Self> {
+        let mut franking_key = [0u8; FRANKING_KEY_SIZE];
+        rng.fill_bytes(&mut franking_key);
+        let commitment = commit(&franking_key, context, sender_id, recipient_id, plaintext);
+
+        let mut inner = Vec::with_capacity(plaintext.len() + FRANKING_KEY_SIZE);
+        inner.extend_from_slice(plaintext);
+        inner.extend_from_slice(&franking_key);
+
+        let message = cipher.encrypt_with(&inner, rng, clock)?;
+
+        Ok(FrankedMessage {
+            sender_id: sender_id.to_string(),
+            recipient_id: recipient_id.to_string(),
+            commitment,
+            message,
+            stamp: None,
+        })
+    }
+
+    /// Attach the relay's stamp (relay side)
+    ///
+    /// The relay must have authenticated `self.sender_id` on the connection
+    /// the message arrived on; the stamp is its word on who sent it.
+    ///
+    /// # Arguments
+    /// * `relay` - Relay's signing key
+    /// * `clock` - Source of the stamp timestamp
+    pub fn stamp(&mut self, relay: &dyn KeyStore, clock: &dyn Clock) -> CryptoResult<()> {
+        let timestamp = clock.now_millis();
+        let transcript = stamp_transcript(
+            &self.commitment,
+            &self.sender_id,
+            &self.recipient_id,
+            timestamp,
+        );
+        let signature = relay.sign(&transcript)?;
+
+        self.stamp = Some(RelayStamp {
+            timestamp,
+            signature,
+        });
+        Ok(())
+    }
+
+    /// Decrypt a message and check its commitment
+    ///
+    /// # Arguments
+    /// * `recipient_id` - Our own user or group ID
+    /// * `context` - Expected protocol context
+    /// * `cipher` - Session cipher shared with the sender
+    ///
+    /// # Errors
+    /// * `RecipientMismatch` - message addressed to someone else
+    /// * `DecryptionError` - ciphertext could not be decrypted
+    /// * `HmacVerificationFailed` - the commitment does not open to the
+    ///   plaintext, so the message could not be reported
+    #[cfg(feature = "std")]
+    pub fn open(
+        &self,
+        recipient_id: &str,
+        context: &str,
+        cipher: &mut TripleLayerEncryption,
+    ) -> CryptoResult<OpenedMessage> {
+        self.open_with(recipient_id, context, cipher, &crate::clock::SystemClock)
+    }
+
+    /// Decrypt a message and check its commitment, checking expiry against `clock`
+    pub fn open_with(
+        &self,
+        recipient_id: &str,
+        context: &str,
+        cipher: &mut TripleLayerEncryption,
+        clock: &dyn Clock,
+    ) -> CryptoResult<OpenedMessage> {
+        if self.recipient_id != recipient_id {
+            return Err(CryptoError::RecipientMismatch);
+        }
+
+        let mut inner = cipher.decrypt_with(&self.message, clock)?;
+
+        if inner.len() < FRANKING_KEY_SIZE {
+            return Err(CryptoError::DecryptionError(
+                "Message too short for franking key".to_string(),
+            ));
+        }
+
+        let split = inner.len() - FRANKING_KEY_SIZE;
+        let mut franking_key = [0u8; FRANKING_KEY_SIZE];
+        franking_key.copy_from_slice(&inner[split..]);
+        inner.truncate(split);
+
+        let commitment = commit(
+            &franking_key,
+            context,
+            &self.sender_id,
+            &self.recipient_id,
+            &inner,
+        );
+        if !constant_time_compare(&commitment, &self.commitment) {
+            return Err(CryptoError::HmacVerificationFailed);
+        }
+
+        Ok(OpenedMessage {
+            plaintext: inner,
+            franking_key,
+        })
+    }
+
+    /// Build an abuse report revealing an opened message
+    ///
+    /// # Errors
+    /// `InvalidProof` if the relay never stamped the message
+    pub fn report(&self, context: &str, opened: &OpenedMessage) -> CryptoResult<AbuseReport> {
+        let stamp = self
+            .stamp
+            .clone()
+            .ok_or_else(|| CryptoError::InvalidProof("Message has no relay stamp".to_string()))?;
+
+        Ok(AbuseReport {
+            sender_id: self.sender_id.clone(),
+            recipient_id: self.recipient_id.clone(),
+            context: context.to_string(),
+            plaintext: opened.plaintext.clone(),
+            franking_key: opened.franking_key,
+            commitment: self.commitment,
+            stamp,
+        })
+    }
+}
+
+impl AbuseReport {
+    /// Verify that the relay saw `sender_id` send exactly this plaintext
+    ///
+    /// # Errors
+    /// * `HmacVerificationFailed` - plaintext or franking key do not match the commitment
+    /// * `SignatureVerificationFailed` - the stamp was not made by the relay
+    ///   for this commitment, sender and recipient
+    pub fn verify(&self, relay_verifying_key: &[u8; 32]) -> CryptoResult<()> {
+        let commitment = commit(
+            &self.franking_key,
+            &self.context,
+            &self.sender_id,
+            &self.recipient_id,
+            &self.plaintext,
+        );
+        if !constant_time_compare(&commitment, &self.commitment) {
+            return Err(CryptoError::HmacVerificationFailed);
+        }
+
+        let transcript = stamp_transcript(
+            &self.commitment,
+            &self.sender_id,
+            &self.recipient_id,
+            self.stamp.timestamp,
+        );
+        verify_signature(relay_verifying_key, &transcript, &self.stamp.signature)
+    }
+}
+
+/// Franking commitment over the length-prefixed message fields
+fn commit(
+    franking_key: &[u8; FRANKING_KEY_SIZE],
+    context: &str,
+    sender_id: &str,
+    recipient_id: &str,
+    plaintext: &[u8],
+) -> [u8; 32] {
+    let fields: [&[u8]; 4] = [
+        context.as_bytes(),
+        sender_id.as_bytes(),
+        recipient_id.as_bytes(),
+        plaintext,
+    ];
+
+    let len = COMMITMENT_DOMAIN.len() + fields.iter().map(|f| 8 + f.len()).sum::<usize>();
+    let mut transcript = Vec::with_capacity(len);
+    transcript.extend_from_slice(COMMITMENT_DOMAIN);
+    for field in fields {
+        transcript.extend_from_slice(&(field.len() as u64).to_be_bytes());
+        transcript.extend_from_slice(field);
+    }
+    hmac_sha256(franking_key, &transcript)
+}
+
+/// Transcript covered by the relay stamp
+fn stamp_transcript(
+    commitment: &[u8; 32],
+    sender_id: &str,
+    recipient_id: &str,
+    timestamp: i64,
+) -> Vec<u8> {
+    let mut transcript =
+        Vec::with_capacity(STAMP_DOMAIN.len() + 32 + 16 + sender_id.len() + recipient_id.len() + 8);
+    transcript.extend_from_slice(STAMP_DOMAIN);
+    transcript.extend_from_slice(commitment);
+    for field in [sender_id.as_bytes(), recipient_id.as_bytes()] {
+        transcript.extend_from_slice(&(field.len() as u64).to_be_bytes());
+        transcript.extend_from_slice(field);
+    }
+    transcript.extend_from_slice(&timestamp.to_be_bytes());
+    transcript
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use crate::encryption::KEY_SIZE;
+    use crate::KeyPair;
+
+    fn ciphers() -> (TripleLayerEncryption, TripleLayerEncryption) {
+        let key = [42u8; KEY_SIZE];
+        (
+            TripleLayerEncryption::new(&key).unwrap(),
+            TripleLayerEncryption::new(&key).unwrap(),
+        )
+    }
+
+    fn stamped(plaintext: &[u8], relay: &KeyPair) -> (FrankedMessage, OpenedMessage) {
+        let (mut alice, mut bob) = ciphers();
+        let mut message =
+            FrankedMessage::seal("alice", "bob", "chat", plaintext, &mut alice).unwrap();
+        message
+            .stamp(relay, &FixedClock(1_700_000_000_000))
+            .unwrap();
+        let opened = message.open("bob", "chat", &mut bob).unwrap();
+        (message, opened)
+    }
+
+    #[test]
+    fn test_report_verifies() {
+        let relay = KeyPair::generate().unwrap();
+        let (message, opened) = stamped(b"abusive", &relay);
+        assert_eq!(opened.plaintext, b"abusive".to_vec());
+
+        let report = message.report("chat", &opened).unwrap();
+        assert_eq!(report.stamp.timestamp, 1_700_000_000_000);
+        report.verify(&relay.verifying_key).unwrap();
+    }
+
+    #[test]
+    fn test_forged_commitment_rejected_on_open() {
+        let (mut alice, mut bob) = ciphers();
+        let mut message =
+            FrankedMessage::seal("alice", "bob", "chat", b"hello", &mut alice).unwrap();
+        message.commitment[0] ^= 1;
+
+        assert!(matches!(
+            message.open("bob", "chat", &mut bob),
+            Err(CryptoError::HmacVerificationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_fabricated_report_rejected() {
+        let relay = KeyPair::generate().unwrap();
+        let (message, opened) = stamped(b"see you later", &relay);
+
+        let mut report = message.report("chat", &opened).unwrap();
+        report.plaintext = b"threat".to_vec();
+        assert!(matches!(
+            report.verify(&relay.verifying_key),
+            Err(CryptoError::HmacVerificationFailed)
+        ));
+
+        let mut report = message.report("group:1", &opened).unwrap();
+        assert!(matches!(
+            report.verify(&relay.verifying_key),
+            Err(CryptoError::HmacVerificationFailed)
+        ));
+
+        // Blaming someone else breaks both the commitment and the stamp
+        report.context = "chat".to_string();
+        report.sender_id = "carol".to_string();
+        assert!(report.verify(&relay.verifying_key).is_err());
+    }
+
+    #[test]
+    fn test_stamp_must_come_from_relay() {
+        let relay = KeyPair::generate().unwrap();
+        let mallory = KeyPair::generate().unwrap();
+        let (message, opened) = stamped(b"abusive", &mallory);
+
+        let report = message.report("chat", &opened).unwrap();
+        assert!(matches!(
+            report.verify(&relay.verifying_key),
+            Err(CryptoError::SignatureVerificationFailed)
+        ));
+
+        let (message, opened) = stamped(b"abusive", &relay);
+        let mut report = message.report("chat", &opened).unwrap();
+        report.stamp.timestamp += 1;
+        assert!(report.verify(&relay.verifying_key).is_err());
+    }
+
+    #[test]
+    fn test_unstamped_message_cannot_be_reported() {
+        let (mut alice, mut bob) = ciphers();
+        let message = FrankedMessage::seal("alice", "bob", "chat", b"hi", &mut alice).unwrap();
+        let opened = message.open("bob", "chat", &mut bob).unwrap();
+
+        assert!(matches!(
+            message.report("chat", &opened),
+            Err(CryptoError::InvalidProof(_))
+        ));
+        assert!(matches!(
+            message.open("carol", "chat", &mut bob),
+            Err(CryptoError::RecipientMismatch)
+        ));
+    }
+}
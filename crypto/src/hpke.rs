@@ -0,0 +1,680 @@
+//! Hybrid Public Key Encryption (RFC 9180)
+//!
+//! One-shot encryption to a public key for contact requests, prekey uploads
+//! and attachment keys sent to offline users. The suite is fixed to
+//! DHKEM(X25519, HKDF-SHA256) and HKDF-SHA256; the AEAD is ChaCha20Poly1305
+//! (default), AES-256-GCM or export-only. Base and auth modes are supported,
+//! PSK modes are not.
+//!
+//! Recipient and sender private keys are any `KeyStore`, so a key file or a
+//! PKCS#11 token works as well as an in-memory `KeyPair`.
+
+use crate::keystore::KeyStore;
+use crate::{CryptoError, CryptoResult};
+use aes_gcm::Aes256Gcm;
+use alloc::{boxed::Box, string::ToString, vec::Vec};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305,
+};
+use hkdf::{Hkdf, HkdfExtract};
+use rand_core::CryptoRngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+/// DHKEM(X25519, HKDF-SHA256)
+pub const KEM_ID: u16 = 0x0020;
+
+/// HKDF-SHA256
+pub const KDF_ID: u16 = 0x0001;
+
+/// Size of `enc`, the encapsulated X25519 public key
+pub const ENC_SIZE: usize = 32;
+
+/// AEAD key size (both AEADs use 256-bit keys)
+const KEY_SIZE: usize = 32;
+
+/// AEAD nonce size
+const NONCE_SIZE: usize = 12;
+
+/// HKDF-SHA256 output size
+const HASH_SIZE: usize = 32;
+
+const MODE_BASE: u8 = 0x00;
+const MODE_AUTH: u8 = 0x02;
+
+/// AEAD of an HPKE suite
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum HpkeAead {
+    /// AES-256-GCM (0x0002)
+    Aes256Gcm,
+
+    /// ChaCha20Poly1305 (0x0003)
+    #[default]
+    ChaCha20Poly1305,
+
+    /// No encryption, secret export only (0xFFFF)
+    ExportOnly,
+}
+
+impl HpkeAead {
+    /// RFC 9180 AEAD identifier
+    pub fn id(&self) -> u16 {
+        match self {
+            HpkeAead::Aes256Gcm => 0x0002,
+            HpkeAead::ChaCha20Poly1305 => 0x0003,
+            HpkeAead::ExportOnly => 0xFFFF,
+        }
+    }
+}
+
+/// HPKE cipher suite
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Hpke {
+    aead: HpkeAead,
+}
+
+/// Single-shot HPKE ciphertext
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SealedBox {
+    /// Encapsulated ephemeral public key
+    #[serde(with = "crate::encoding::base64_array")]
+    pub enc: [u8; ENC_SIZE],
+
+    /// AEAD ciphertext with tag
+    #[serde(with = "crate::encoding::base64_bytes")]
+    pub ciphertext: Vec<u8>,
+}
+
+/// Sender side of an HPKE context
+pub struct SenderContext(Context);
+
+/// Recipient side of an HPKE context
+pub struct RecipientContext(Context);
+
+/// Key schedule output shared by both sides
+struct Context {
+    cipher: Cipher,
+    base_nonce: [u8; NONCE_SIZE],
+    seq: u64,
+    exporter_secret: Zeroizing<[u8; HASH_SIZE]>,
+    suite_id: [u8; 10],
+}
+
+/// Initialized AEAD; the cipher types zeroize their keys on drop
+enum Cipher {
+    Aes256Gcm(Box<Aes256Gcm>),
+    ChaCha20Poly1305(ChaCha20Poly1305),
+    ExportOnly,
+}
+
+impl Hpke {
+    /// Suite with the given AEAD
+    pub const fn new(aead: HpkeAead) -> Self {
+        Hpke { aead }
+    }
+
+    /// AEAD of this suite
+    pub fn aead(&self) -> HpkeAead {
+        self.aead
+    }
+
+    /// Base mode sender setup (`SetupBaseS`)
+    ///
+    /// # Returns
+    /// `enc` for the recipient and the sender context
+    #[cfg(feature = "std")]
+    pub fn setup_base_sender(
+        &self,
+        recipient: &[u8; 32],
+        info: &[u8],
+    ) -> CryptoResult<([u8; ENC_SIZE], SenderContext)> {
+        self.setup_base_sender_with(recipient, info, &mut rand::thread_rng())
+    }
+
+    /// Base mode sender setup with a caller-supplied RNG
+    ///
+    /// The ephemeral key is `DeriveKeyPair` of 32 bytes from `rng`.
+    pub fn setup_base_sender_with(
+        &self,
+        recipient: &[u8; 32],
+        info: &[u8],
+        rng: &mut impl CryptoRngCore,
+    ) -> CryptoResult<([u8; ENC_SIZE], SenderContext)> {
+        let (shared_secret, enc) = encap(recipient, None, rng)?;
+        let context = self.key_schedule(MODE_BASE, &shared_secret, info)?;
+        Ok((enc, SenderContext(context)))
+    }
+
+    /// Base mode recipient setup (`SetupBaseR`)
+    ///
+    /// # Arguments
+    /// * `enc` - Encapsulated key from the sender
+    /// * `recipient` - Recipient's X25519 key
+    /// * `info` - Application info, as given to the sender
+    pub fn setup_base_recipient(
+        &self,
+        enc: &[u8; ENC_SIZE],
+        recipient: &dyn KeyStore,
+        info: &[u8],
+    ) -> CryptoResult<RecipientContext> {
+        let shared_secret = decap(enc, recipient, None)?;
+        Ok(RecipientContext(self.key_schedule(
+            MODE_BASE,
+            &shared_secret,
+            info,
+        )?))
+    }
+
+    /// Auth mode sender setup (`SetupAuthS`), authenticating with `sender`'s X25519 key
+    #[cfg(feature = "std")]
+    pub fn setup_auth_sender(
+        &self,
+        recipient: &[u8; 32],
+        info: &[u8],
+        sender: &dyn KeyStore,
+    ) -> CryptoResult<([u8; ENC_SIZE], SenderContext)> {
+        self.setup_auth_sender_with(recipient, info, sender, &mut rand::thread_rng())
+    }
+
+    /// Auth mode sender setup with a caller-supplied RNG
+    pub fn setup_auth_sender_with(
+        &self,
+        recipient: &[u8; 32],
+        info: &[u8],
+        sender: &dyn KeyStore,
+        rng: &mut impl CryptoRngCore,
+    ) -> CryptoResult<([u8; ENC_SIZE], SenderContext)> {
+        let (shared_secret, enc) = encap(recipient, Some(sender), rng)?;
+        let context = self.key_schedule(MODE_AUTH, &shared_secret, info)?;
+        Ok((enc, SenderContext(context)))
+    }
+
+    /// Auth mode recipient setup (`SetupAuthR`)
+    ///
+    /// Succeeds only if the sender holds the private key of `sender`.
+    pub fn setup_auth_recipient(
+        &self,
+        enc: &[u8; ENC_SIZE],
+        recipient: &dyn KeyStore,
+        info: &[u8],
+        sender: &[u8; 32],
+    ) -> CryptoResult<RecipientContext> {
+        let shared_secret = decap(enc, recipient, Some(sender))?;
+        Ok(RecipientContext(self.key_schedule(
+            MODE_AUTH,
+            &shared_secret,
+            info,
+        )?))
+    }
+
+    /// Encrypt one message to `recipient` (`SealBase`)
+    #[cfg(feature = "std")]
+    pub fn seal_base(
+        &self,
+        recipient: &[u8; 32],
+        info: &[u8],
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> CryptoResult<SealedBox> {
+        self.seal_base_with(recipient, info, aad, plaintext, &mut rand::thread_rng())
+    }
+
+    /// Encrypt one message to `recipient` with a caller-supplied RNG
+    pub fn seal_base_with(
+        &self,
+        recipient: &[u8; 32],
+        info: &[u8],
+        aad: &[u8],
+        plaintext: &[u8],
+        rng: &mut impl CryptoRngCore,
+    ) -> CryptoResult<SealedBox> {
+        let (enc, mut context) = self.setup_base_sender_with(recipient, info, rng)?;
+        Ok(SealedBox {
+            enc,
+            ciphertext: context.seal(aad, plaintext)?,
+        })
+    }
+
+    /// Decrypt a single-shot message (`OpenBase`)
+    pub fn open_base(
+        &self,
+        sealed: &SealedBox,
+        recipient: &dyn KeyStore,
+        info: &[u8],
+        aad: &[u8],
+    ) -> CryptoResult<Vec<u8>> {
+        self.setup_base_recipient(&sealed.enc, recipient, info)?
+            .open(aad, &sealed.ciphertext)
+    }
+
+    /// Encrypt one authenticated message to `recipient` (`SealAuth`)
+    #[cfg(feature = "std")]
+    pub fn seal_auth(
+        &self,
+        recipient: &[u8; 32],
+        info: &[u8],
+        aad: &[u8],
+        plaintext: &[u8],
+        sender: &dyn KeyStore,
+    ) -> CryptoResult<SealedBox> {
+        self.seal_auth_with(
+            recipient,
+            info,
+            aad,
+            plaintext,
+            sender,
+            &mut rand::thread_rng(),
+        )
+    }
+
+    /// Encrypt one authenticated message to `recipient` with a caller-supplied RNG
+    pub fn seal_auth_with(
+        &self,
+        recipient: &[u8; 32],
+        info: &[u8],
+        aad: &[u8],
+        plaintext: &[u8],
+        sender: &dyn KeyStore,
+        rng: &mut impl CryptoRngCore,
+    ) -> CryptoResult<SealedBox> {
+        let (enc, mut context) = self.setup_auth_sender_with(recipient, info, sender, rng)?;
+        Ok(SealedBox {
+            enc,
+            ciphertext: context.seal(aad, plaintext)?,
+        })
+    }
+
+    /// Decrypt a single-shot authenticated message (`OpenAuth`)
+    pub fn open_auth(
+        &self,
+        sealed: &SealedBox,
+        recipient: &dyn KeyStore,
+        info: &[u8],
+        aad: &[u8],
+        sender: &[u8; 32],
+    ) -> CryptoResult<Vec<u8>> {
+        self.setup_auth_recipient(&sealed.enc, recipient, info, sender)?
+            .open(aad, &sealed.ciphertext)
+    }
+
+    /// `KeyScheduleS`/`KeyScheduleR` without PSK
+    fn key_schedule(
+        &self,
+        mode: u8,
+        shared_secret: &[u8; HASH_SIZE],
+        info: &[u8],
+    ) -> CryptoResult<Context> {
+        let suite_id = self.suite_id();
+        let (psk_id_hash, _) = labeled_extract(&suite_id, b"", b"psk_id_hash", &[]);
+        let (info_hash, _) = labeled_extract(&suite_id, b"", b"info_hash", &[info]);
+        let key_schedule_context = [&[mode][..], &psk_id_hash[..], &info_hash[..]].concat();
+
+        let (_, secret) = labeled_extract(&suite_id, shared_secret.as_ref(), b"secret", &[]);
+
+        let mut exporter_secret = Zeroizing::new([0u8; HASH_SIZE]);
+        labeled_expand_with(
+            &secret,
+            &suite_id,
+            b"exp",
+            &key_schedule_context,
+            exporter_secret.as_mut(),
+        )?;
+
+        let mut base_nonce = [0u8; NONCE_SIZE];
+        let cipher = match self.aead {
+            HpkeAead::ExportOnly => Cipher::ExportOnly,
+            aead => {
+                let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+                labeled_expand_with(
+                    &secret,
+                    &suite_id,
+                    b"key",
+                    &key_schedule_context,
+                    key.as_mut(),
+                )?;
+                labeled_expand_with(
+                    &secret,
+                    &suite_id,
+                    b"base_nonce",
+                    &key_schedule_context,
+                    &mut base_nonce,
+                )?;
+
+                if aead == HpkeAead::Aes256Gcm {
+                    Cipher::Aes256Gcm(Box::new(Aes256Gcm::new(key.as_ref().into())))
+                } else {
+                    Cipher::ChaCha20Poly1305(ChaCha20Poly1305::new(key.as_ref().into()))
+                }
+            }
+        };
+
+        Ok(Context {
+            cipher,
+            base_nonce,
+            seq: 0,
+            exporter_secret,
+            suite_id,
+        })
+    }
+
+    /// `"HPKE" || kem_id || kdf_id || aead_id`
+    fn suite_id(&self) -> [u8; 10] {
+        let mut suite_id = [0u8; 10];
+        suite_id[..4].copy_from_slice(b"HPKE");
+        suite_id[4..6].copy_from_slice(&KEM_ID.to_be_bytes());
+        suite_id[6..8].copy_from_slice(&KDF_ID.to_be_bytes());
+        suite_id[8..].copy_from_slice(&self.aead.id().to_be_bytes());
+        suite_id
+    }
+}
+
+impl SenderContext {
+    /// Encrypt the next message of this context
+    ///
+    /// # Errors
+    /// `EncryptionError` for export-only suites or after 2^64 - 1 messages
+    pub fn seal(&mut self, aad: &[u8], plaintext: &[u8]) -> CryptoResult<Vec<u8>> {
+        let nonce = self.0.next_nonce()?;
+        let payload = Payload {
+            msg: plaintext,
+            aad,
+        };
+        let ciphertext = match &self.0.cipher {
+            Cipher::Aes256Gcm(cipher) => cipher.encrypt(nonce.as_ref().into(), payload),
+            Cipher::ChaCha20Poly1305(cipher) => cipher.encrypt(nonce.as_ref().into(), payload),
+            Cipher::ExportOnly => {
+                return Err(CryptoError::EncryptionError(
+                    "Export-only HPKE context".to_string(),
+                ))
+            }
+        };
+        ciphertext.map_err(|_| CryptoError::EncryptionError("HPKE seal failed".to_string()))
+    }
+
+    /// Derive `length` bytes of secret from this context (`Export`)
+    pub fn export(
+        &self,
+        exporter_context: &[u8],
+        length: usize,
+    ) -> CryptoResult<Zeroizing<Vec<u8>>> {
+        self.0.export(exporter_context, length)
+    }
+}
+
+impl RecipientContext {
+    /// Decrypt the next message of this context
+    ///
+    /// Messages must be opened in the order they were sealed.
+    ///
+    /// # Errors
+    /// `DecryptionError` if the message does not authenticate
+    pub fn open(&mut self, aad: &[u8], ciphertext: &[u8]) -> CryptoResult<Vec<u8>> {
+        let nonce = self.0.peek_nonce()?;
+        let payload = Payload {
+            msg: ciphertext,
+            aad,
+        };
+        let plaintext = match &self.0.cipher {
+            Cipher::Aes256Gcm(cipher) => cipher.decrypt(nonce.as_ref().into(), payload),
+            Cipher::ChaCha20Poly1305(cipher) => cipher.decrypt(nonce.as_ref().into(), payload),
+            Cipher::ExportOnly => {
+                return Err(CryptoError::DecryptionError(
+                    "Export-only HPKE context".to_string(),
+                ))
+            }
+        }
+        .map_err(|_| CryptoError::DecryptionError("HPKE open failed".to_string()))?;
+
+        // Only a successful open advances the sequence number
+        self.0.seq += 1;
+        Ok(plaintext)
+    }
+
+    /// Derive `length` bytes of secret from this context (`Export`)
+    pub fn export(
+        &self,
+        exporter_context: &[u8],
+        length: usize,
+    ) -> CryptoResult<Zeroizing<Vec<u8>>> {
+        self.0.export(exporter_context, length)
+    }
+}
+
+impl Context {
+    /// Nonce for the current sequence number, which must not be the last one
+    fn peek_nonce(&self) -> CryptoResult<[u8; NONCE_SIZE]> {
+        if self.seq == u64::MAX {
+            return Err(CryptoError::EncryptionError(
+                "HPKE message limit reached".to_string(),
+            ));
+        }
+
+        let mut nonce = self.base_nonce;
+        for (n, s) in nonce[NONCE_SIZE - 8..]
+            .iter_mut()
+            .zip(self.seq.to_be_bytes())
+        {
+            *n ^= s;
+        }
+        Ok(nonce)
+    }
+
+    /// Nonce for the current sequence number, then advance it
+    fn next_nonce(&mut self) -> CryptoResult<[u8; NONCE_SIZE]> {
+        let nonce = self.peek_nonce()?;
+        self.seq += 1;
+        Ok(nonce)
+    }
+
+    fn export(&self, exporter_context: &[u8], length: usize) -> CryptoResult<Zeroizing<Vec<u8>>> {
+        let mut out = Zeroizing::new(alloc::vec![0u8; length]);
+        labeled_expand(
+            self.exporter_secret.as_ref(),
+            &self.suite_id,
+            b"sec",
+            exporter_context,
+            &mut out,
+        )?;
+        Ok(out)
+    }
+}
+
+/// `Encap`/`AuthEncap`
+fn encap(
+    recipient: &[u8; 32],
+    sender: Option<&dyn KeyStore>,
+    rng: &mut impl CryptoRngCore,
+) -> CryptoResult<(Zeroizing<[u8; HASH_SIZE]>, [u8; ENC_SIZE])> {
+    let mut ikm = Zeroizing::new([0u8; 32]);
+    rng.fill_bytes(ikm.as_mut());
+    let ephemeral = derive_key_pair(ikm.as_ref())?;
+    let enc = *PublicKey::from(&ephemeral).as_bytes();
+
+    let mut dh = Zeroizing::new(Vec::with_capacity(64));
+    let shared = ephemeral.diffie_hellman(&PublicKey::from(*recipient));
+    dh.extend_from_slice(checked(*shared.as_bytes())?.as_ref());
+
+    let mut kem_context = [&enc[..], &recipient[..]].concat();
+    if let Some(sender) = sender {
+        dh.extend_from_slice(checked(sender.compute_shared_secret(recipient)?)?.as_ref());
+        kem_context.extend_from_slice(&sender.public_keys()?.public_key);
+    }
+
+    Ok((extract_and_expand(&dh, &kem_context)?, enc))
+}
+
+/// `Decap`/`AuthDecap`
+fn decap(
+    enc: &[u8; ENC_SIZE],
+    recipient: &dyn KeyStore,
+    sender: Option<&[u8; 32]>,
+) -> CryptoResult<Zeroizing<[u8; HASH_SIZE]>> {
+    let mut dh = Zeroizing::new(Vec::with_capacity(64));
+    dh.extend_from_slice(checked(recipient.compute_shared_secret(enc)?)?.as_ref());
+
+    let mut kem_context = [&enc[..], &recipient.public_keys()?.public_key[..]].concat();
+    if let Some(sender) = sender {
+        dh.extend_from_slice(checked(recipient.compute_shared_secret(sender)?)?.as_ref());
+        kem_context.extend_from_slice(sender);
+    }
+
+    extract_and_expand(&dh, &kem_context)
+}
+
+/// X25519 `DeriveKeyPair`
+fn derive_key_pair(ikm: &[u8]) -> CryptoResult<StaticSecret> {
+    let suite_id = kem_suite_id();
+    let (_, dkp_prk) = labeled_extract(&suite_id, b"", b"dkp_prk", &[ikm]);
+
+    let mut sk = Zeroizing::new([0u8; 32]);
+    labeled_expand_with(&dkp_prk, &suite_id, b"sk", b"", sk.as_mut())?;
+    Ok(StaticSecret::from(*sk))
+}
+
+/// DHKEM `ExtractAndExpand`
+fn extract_and_expand(dh: &[u8], kem_context: &[u8]) -> CryptoResult<Zeroizing<[u8; HASH_SIZE]>> {
+    let suite_id = kem_suite_id();
+    let (_, eae_prk) = labeled_extract(&suite_id, b"", b"eae_prk", &[dh]);
+
+    let mut shared_secret = Zeroizing::new([0u8; HASH_SIZE]);
+    labeled_expand_with(
+        &eae_prk,
+        &suite_id,
+        b"shared_secret",
+        kem_context,
+        shared_secret.as_mut(),
+    )?;
+    Ok(shared_secret)
+}
+
+/// Reject the all-zero X25519 output (small-order peer key)
+fn checked(dh: [u8; 32]) -> CryptoResult<Zeroizing<[u8; 32]>> {
+    let dh = Zeroizing::new(dh);
+    if bool::from(dh.ct_eq(&[0u8; 32])) {
+        return Err(CryptoError::KeyAgreementFailed(
+            "Small-order public key".to_string(),
+        ));
+    }
+    Ok(dh)
+}
+
+/// `"KEM" || kem_id`
+fn kem_suite_id() -> [u8; 5] {
+    let mut suite_id = [0u8; 5];
+    suite_id[..3].copy_from_slice(b"KEM");
+    suite_id[3..].copy_from_slice(&KEM_ID.to_be_bytes());
+    suite_id
+}
+
+/// `LabeledExtract(salt, label, ikm)`, with `ikm` given in parts
+///
+/// Returns the PRK and the HKDF instance keyed with it.
+fn labeled_extract(
+    suite_id: &[u8],
+    salt: &[u8],
+    label: &[u8],
+    ikm: &[&[u8]],
+) -> (Zeroizing<[u8; HASH_SIZE]>, Hkdf<Sha256>) {
+    let mut extract = HkdfExtract::<Sha256>::new(Some(salt));
+    extract.input_ikm(b"HPKE-v1");
+    extract.input_ikm(suite_id);
+    extract.input_ikm(label);
+    for part in ikm {
+        extract.input_ikm(part);
+    }
+
+    let (prk, hkdf) = extract.finalize();
+    (Zeroizing::new(prk.into()), hkdf)
+}
+
+/// `LabeledExpand(prk, label, info, L)` from a PRK
+fn labeled_expand(
+    prk: &[u8],
+    suite_id: &[u8],
+    label: &[u8],
+    info: &[u8],
+    out: &mut [u8],
+) -> CryptoResult<()> {
+    let hkdf = Hkdf::<Sha256>::from_prk(prk)
+        .map_err(|e| CryptoError::KeyDerivationError(e.to_string()))?;
+    labeled_expand_with(&hkdf, suite_id, label, info, out)
+}
+
+/// `LabeledExpand(prk, label, info, L)` with an HKDF instance keyed with the PRK
+fn labeled_expand_with(
+    hkdf: &Hkdf<Sha256>,
+    suite_id: &[u8],
+    label: &[u8],
+    info: &[u8],
+    out: &mut [u8],
+) -> CryptoResult<()> {
+    let length = u16::try_from(out.len())
+        .map_err(|_| CryptoError::KeyDerivationError("HPKE output too long".to_string()))?
+        .to_be_bytes();
+    hkdf.expand_multi_info(&[&length, b"HPKE-v1", suite_id, label, info], out)
+        .map_err(|e| CryptoError::KeyDerivationError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyPair;
+
+    #[test]
+    fn test_failed_open_does_not_advance() {
+        let recipient = KeyPair::generate().unwrap();
+        let hpke = Hpke::default();
+        let (enc, mut sender) = hpke
+            .setup_base_sender(&recipient.public_key, b"info")
+            .unwrap();
+        let mut receiver = hpke
+            .setup_base_recipient(&enc, &recipient, b"info")
+            .unwrap();
+
+        let first = sender.seal(b"", b"first").unwrap();
+        let mut forged = first.clone();
+        forged[0] ^= 1;
+        assert!(receiver.open(b"", &forged).is_err());
+        assert_eq!(receiver.open(b"", &first).unwrap(), b"first");
+
+        // Out of order fails: the nonce is bound to the sequence number
+        let second = sender.seal(b"", b"second").unwrap();
+        let third = sender.seal(b"", b"third").unwrap();
+        assert!(receiver.open(b"", &third).is_err());
+        assert_eq!(receiver.open(b"", &second).unwrap(), b"second");
+    }
+
+    #[test]
+    fn test_message_limit() {
+        let recipient = KeyPair::generate().unwrap();
+        let (_, mut sender) = Hpke::default()
+            .setup_base_sender(&recipient.public_key, b"")
+            .unwrap();
+
+        sender.0.seq = u64::MAX - 1;
+        assert!(sender.seal(b"", b"last").is_ok());
+        assert!(matches!(
+            sender.seal(b"", b"one too many"),
+            Err(CryptoError::EncryptionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_export_lengths() {
+        let recipient = KeyPair::generate().unwrap();
+        let (_, sender) = Hpke::new(HpkeAead::ExportOnly)
+            .setup_base_sender(&recipient.public_key, b"")
+            .unwrap();
+
+        assert_eq!(sender.export(b"attachment key", 32).unwrap().len(), 32);
+        assert_eq!(
+            sender.export(b"", 255 * HASH_SIZE).unwrap().len(),
+            255 * HASH_SIZE
+        );
+        assert!(sender.export(b"", 255 * HASH_SIZE + 1).is_err());
+    }
+}
@@ -0,0 +1,428 @@
+//! Hybrid Post-Quantum Signatures
+//!
+//! Ed25519 + ML-DSA-65 (FIPS 204) composite signatures for identity keys,
+//! `PeerInfo` records and prekey bundles. A hybrid signature only verifies if
+//! both component signatures do, so it stays unforgeable as long as either
+//! scheme is unbroken.
+//!
+//! The Ed25519 component signs `HYBRID_SIGNATURE_CONTEXT || data` and the
+//! ML-DSA component signs `data` under the same context string, so neither
+//! component can be stripped off and passed off as a plain Ed25519 or ML-DSA
+//! signature over `data`.
+//!
+//! Encodings are fixed-length concatenations, classical part first:
+//! - public key: `ed25519 (32) || ml-dsa-65 (1952)`
+//! - signature: `ed25519 (64) || ml-dsa-65 (3309)`
+
+use crate::encoding::base64_bytes;
+use crate::key_exchange::{verify_signature, CURVE25519_KEY_SIZE, SIGNATURE_SIZE};
+use crate::{CryptoError, CryptoResult};
+use ml_dsa::{
+    EncodedSignature, EncodedVerifyingKey, MlDsa65, Seed, Signature, SigningKey, VerifyingKey,
+};
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use zeroize::Zeroizing;
+
+/// ML-DSA-65 public key size
+pub const ML_DSA_PUBLIC_KEY_SIZE: usize = 1952;
+
+/// ML-DSA-65 signature size
+pub const ML_DSA_SIGNATURE_SIZE: usize = 3309;
+
+/// ML-DSA key generation seed size (FIPS 204 ξ)
+pub const ML_DSA_SEED_SIZE: usize = 32;
+
+/// Composite public key size
+pub const HYBRID_PUBLIC_KEY_SIZE: usize = CURVE25519_KEY_SIZE + ML_DSA_PUBLIC_KEY_SIZE;
+
+/// Composite signature size
+pub const HYBRID_SIGNATURE_SIZE: usize = SIGNATURE_SIZE + ML_DSA_SIGNATURE_SIZE;
+
+/// Domain separation for both components (also the ML-DSA context string)
+pub const HYBRID_SIGNATURE_CONTEXT: &[u8] = b"chakchat-hybrid-ed25519-mldsa65-v1";
+
+/// Composite Ed25519 + ML-DSA-65 public key
+#[derive(Clone, PartialEq, Eq)]
+pub struct HybridPublicKey {
+    ed25519: [u8; CURVE25519_KEY_SIZE],
+    ml_dsa: Vec<u8>,
+}
+
+/// Composite Ed25519 + ML-DSA-65 signature
+#[derive(Clone, PartialEq, Eq)]
+pub struct HybridSignature {
+    ed25519: [u8; SIGNATURE_SIZE],
+    ml_dsa: Vec<u8>,
+}
+
+/// Public identity key, classical or hybrid
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IdentityKey {
+    /// Ed25519 verifying key
+    Classical(#[serde(with = "crate::encoding::base64_array")] [u8; CURVE25519_KEY_SIZE]),
+
+    /// Ed25519 + ML-DSA-65 composite key
+    Hybrid(HybridPublicKey),
+}
+
+/// Signature made by an identity key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IdentitySignature {
+    /// Ed25519 signature over the data
+    Classical(#[serde(with = "crate::encoding::base64_array")] [u8; SIGNATURE_SIZE]),
+
+    /// Composite signature
+    Hybrid(HybridSignature),
+}
+
+impl HybridPublicKey {
+    /// Parse the composite encoding
+    ///
+    /// # Errors
+    /// `InvalidKey` if `bytes` is not `HYBRID_PUBLIC_KEY_SIZE` long
+    pub fn from_bytes(bytes: &[u8]) -> CryptoResult<Self> {
+        if bytes.len() != HYBRID_PUBLIC_KEY_SIZE {
+            return Err(CryptoError::InvalidKey(format!(
+                "Invalid hybrid public key size: {}",
+                bytes.len()
+            )));
+        }
+
+        let (ed25519, ml_dsa) = bytes.split_at(CURVE25519_KEY_SIZE);
+        Ok(HybridPublicKey {
+            ed25519: ed25519.try_into().expect("split at key size"),
+            ml_dsa: ml_dsa.to_vec(),
+        })
+    }
+
+    /// Composite encoding
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HYBRID_PUBLIC_KEY_SIZE);
+        bytes.extend_from_slice(&self.ed25519);
+        bytes.extend_from_slice(&self.ml_dsa);
+        bytes
+    }
+
+    /// Ed25519 component
+    pub fn ed25519(&self) -> &[u8; CURVE25519_KEY_SIZE] {
+        &self.ed25519
+    }
+
+    /// ML-DSA-65 component
+    pub fn ml_dsa(&self) -> &[u8] {
+        &self.ml_dsa
+    }
+
+    /// Verify a hybrid signature; both components must verify
+    ///
+    /// # Errors
+    /// `SignatureVerificationFailed` if either component does not verify
+    pub fn verify(&self, data: &[u8], signature: &HybridSignature) -> CryptoResult<()> {
+        verify_signature(&self.ed25519, &classical_message(data), &signature.ed25519)?;
+
+        let key = EncodedVerifyingKey::<MlDsa65>::try_from(self.ml_dsa.as_slice())
+            .map_err(|_| CryptoError::SignatureVerificationFailed)?;
+        let encoded = EncodedSignature::<MlDsa65>::try_from(signature.ml_dsa.as_slice())
+            .map_err(|_| CryptoError::SignatureVerificationFailed)?;
+        let sig = Signature::<MlDsa65>::decode(&encoded)
+            .ok_or(CryptoError::SignatureVerificationFailed)?;
+
+        if VerifyingKey::<MlDsa65>::decode(&key).verify_with_context(
+            data,
+            HYBRID_SIGNATURE_CONTEXT,
+            &sig,
+        ) {
+            Ok(())
+        } else {
+            Err(CryptoError::SignatureVerificationFailed)
+        }
+    }
+}
+
+impl HybridSignature {
+    /// Parse the composite encoding
+    ///
+    /// # Errors
+    /// `SignatureVerificationFailed` if `bytes` is not `HYBRID_SIGNATURE_SIZE` long
+    pub fn from_bytes(bytes: &[u8]) -> CryptoResult<Self> {
+        if bytes.len() != HYBRID_SIGNATURE_SIZE {
+            return Err(CryptoError::SignatureVerificationFailed);
+        }
+
+        let (ed25519, ml_dsa) = bytes.split_at(SIGNATURE_SIZE);
+        Ok(HybridSignature {
+            ed25519: ed25519.try_into().expect("split at signature size"),
+            ml_dsa: ml_dsa.to_vec(),
+        })
+    }
+
+    /// Composite encoding
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HYBRID_SIGNATURE_SIZE);
+        bytes.extend_from_slice(&self.ed25519);
+        bytes.extend_from_slice(&self.ml_dsa);
+        bytes
+    }
+}
+
+impl IdentityKey {
+    /// Whether this is a hybrid identity
+    pub fn is_hybrid(&self) -> bool {
+        matches!(self, IdentityKey::Hybrid(_))
+    }
+
+    /// Verify a signature made by this identity
+    ///
+    /// A hybrid key only accepts hybrid signatures, so a signer cannot be
+    /// downgraded to Ed25519 alone.
+    ///
+    /// # Errors
+    /// `SignatureVerificationFailed` if the signature does not verify or is of the other kind
+    pub fn verify(&self, data: &[u8], signature: &IdentitySignature) -> CryptoResult<()> {
+        match (self, signature) {
+            (IdentityKey::Classical(key), IdentitySignature::Classical(signature)) => {
+                verify_signature(key, data, signature)
+            }
+            (IdentityKey::Hybrid(key), IdentitySignature::Hybrid(signature)) => {
+                key.verify(data, signature)
+            }
+            _ => Err(CryptoError::SignatureVerificationFailed),
+        }
+    }
+}
+
+/// ML-DSA-65 key pair for a key generation seed
+fn ml_dsa_signing_key(seed: &[u8; ML_DSA_SEED_SIZE]) -> SigningKey<MlDsa65> {
+    SigningKey::from_seed(&Zeroizing::new(Seed::from(*seed)))
+}
+
+/// ML-DSA-65 public key for a key generation seed
+pub(crate) fn ml_dsa_public_key(seed: &[u8; ML_DSA_SEED_SIZE]) -> Vec<u8> {
+    ml_dsa_signing_key(seed)
+        .expanded_key()
+        .verifying_key()
+        .encode()
+        .to_vec()
+}
+
+/// Hybrid signature from both component keys
+pub(crate) fn sign(
+    ed25519: &ed25519_dalek::SigningKey,
+    ml_dsa_seed: &[u8; ML_DSA_SEED_SIZE],
+    data: &[u8],
+) -> CryptoResult<HybridSignature> {
+    use ed25519_dalek::Signer;
+
+    let ml_dsa = ml_dsa_signing_key(ml_dsa_seed)
+        .expanded_key()
+        .sign_deterministic(data, HYBRID_SIGNATURE_CONTEXT)
+        .map_err(|_| CryptoError::InvalidKey("ML-DSA signing failed".to_string()))?;
+
+    Ok(HybridSignature {
+        ed25519: ed25519.sign(&classical_message(data)).to_bytes(),
+        ml_dsa: ml_dsa.encode().to_vec(),
+    })
+}
+
+/// Ed25519 input: context string, then the data
+fn classical_message(data: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(HYBRID_SIGNATURE_CONTEXT.len() + data.len());
+    message.extend_from_slice(HYBRID_SIGNATURE_CONTEXT);
+    message.extend_from_slice(data);
+    message
+}
+
+impl fmt::Debug for HybridPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HybridPublicKey")
+            .field("ed25519", &hex::encode(self.ed25519))
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for HybridSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HybridSignature")
+            .field("ed25519", &hex::encode(self.ed25519))
+            .finish_non_exhaustive()
+    }
+}
+
+impl Serialize for HybridPublicKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        base64_bytes::serialize(&self.to_bytes(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for HybridPublicKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = base64_bytes::deserialize(deserializer)?;
+        HybridPublicKey::from_bytes(&bytes).map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for HybridSignature {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        base64_bytes::serialize(&self.to_bytes(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for HybridSignature {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = base64_bytes::deserialize(deserializer)?;
+        HybridSignature::from_bytes(&bytes)
+            .map_err(|_| D::Error::custom("expected hybrid signature"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_exchange::KeyPair;
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
+
+    fn hybrid_key(identity: &KeyPair) -> HybridPublicKey {
+        match identity.identity_key().unwrap() {
+            IdentityKey::Hybrid(key) => key,
+            IdentityKey::Classical(_) => panic!("expected a hybrid identity"),
+        }
+    }
+
+    fn hybrid_signature(signature: IdentitySignature) -> HybridSignature {
+        match signature {
+            IdentitySignature::Hybrid(signature) => signature,
+            IdentitySignature::Classical(_) => panic!("expected a hybrid signature"),
+        }
+    }
+
+    #[test]
+    fn test_hybrid_sign_and_verify() {
+        let identity = KeyPair::generate_hybrid().unwrap();
+        assert!(identity.is_hybrid().unwrap());
+
+        let key = identity.identity_key().unwrap();
+        let signature = identity.sign_identity(b"peer info").unwrap();
+        assert!(key.verify(b"peer info", &signature).is_ok());
+        assert!(key.verify(b"other info", &signature).is_err());
+
+        let key = hybrid_key(&identity);
+        assert_eq!(key.ed25519(), &identity.verifying_key);
+        assert_eq!(key.ml_dsa().len(), ML_DSA_PUBLIC_KEY_SIZE);
+        assert_eq!(
+            hybrid_signature(signature).to_bytes().len(),
+            HYBRID_SIGNATURE_SIZE
+        );
+    }
+
+    #[test]
+    fn test_both_components_required() {
+        let identity = KeyPair::generate_hybrid().unwrap();
+        let key = hybrid_key(&identity);
+        let signature = hybrid_signature(identity.sign_identity(b"prekey bundle").unwrap());
+
+        let mut bytes = signature.to_bytes();
+        bytes[0] ^= 1;
+        let bad_classical = HybridSignature::from_bytes(&bytes).unwrap();
+        assert!(key.verify(b"prekey bundle", &bad_classical).is_err());
+
+        let mut bytes = signature.to_bytes();
+        bytes[SIGNATURE_SIZE + 100] ^= 1;
+        let bad_quantum = HybridSignature::from_bytes(&bytes).unwrap();
+        assert!(key.verify(b"prekey bundle", &bad_quantum).is_err());
+
+        // A different ML-DSA key under the same Ed25519 key
+        let other = hybrid_key(&KeyPair::generate_hybrid().unwrap());
+        let mut mixed = key.to_bytes();
+        mixed[CURVE25519_KEY_SIZE..].copy_from_slice(other.ml_dsa());
+        let mixed = HybridPublicKey::from_bytes(&mixed).unwrap();
+        assert!(mixed.verify(b"prekey bundle", &signature).is_err());
+    }
+
+    #[test]
+    fn test_components_cannot_be_stripped() {
+        let identity = KeyPair::generate_hybrid().unwrap();
+        let signature = hybrid_signature(identity.sign_identity(b"record").unwrap());
+
+        // The Ed25519 half is not a classical signature over the data
+        let classical = IdentityKey::Classical(identity.verifying_key);
+        assert!(classical
+            .verify(b"record", &IdentitySignature::Classical(signature.ed25519))
+            .is_err());
+
+        // Nor does a classical signature satisfy the hybrid key
+        let downgraded = IdentitySignature::Classical(identity.sign(b"record").unwrap());
+        assert!(identity
+            .identity_key()
+            .unwrap()
+            .verify(b"record", &downgraded)
+            .is_err());
+    }
+
+    #[test]
+    fn test_classical_identity_unchanged() {
+        let identity = KeyPair::generate().unwrap();
+        assert!(!identity.is_hybrid().unwrap());
+        assert_eq!(
+            identity.identity_key().unwrap(),
+            IdentityKey::Classical(identity.verifying_key)
+        );
+
+        let signature = identity.sign_identity(b"data").unwrap();
+        assert_eq!(
+            signature,
+            IdentitySignature::Classical(identity.sign(b"data").unwrap())
+        );
+        assert!(identity
+            .identity_key()
+            .unwrap()
+            .verify(b"data", &signature)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_hybrid_generation_is_deterministic() {
+        let a = KeyPair::generate_hybrid_with_rng(&mut ChaCha20Rng::seed_from_u64(43)).unwrap();
+        let b = KeyPair::generate_hybrid_with_rng(&mut ChaCha20Rng::seed_from_u64(43)).unwrap();
+        let classical = KeyPair::generate_with_rng(&mut ChaCha20Rng::seed_from_u64(43)).unwrap();
+
+        assert_eq!(a.identity_key().unwrap(), b.identity_key().unwrap());
+        assert_eq!(a.verifying_key, classical.verifying_key);
+        assert_eq!(a.public_key, classical.public_key);
+        assert_eq!(
+            a.sign_identity(b"x").unwrap(),
+            b.sign_identity(b"x").unwrap()
+        );
+
+        // Plain Ed25519 signing is unaffected by the ML-DSA seed
+        assert_eq!(a.sign(b"x").unwrap(), classical.sign(b"x").unwrap());
+    }
+
+    #[test]
+    fn test_encodings_round_trip() {
+        let identity = KeyPair::generate_hybrid().unwrap();
+        let key = hybrid_key(&identity);
+        assert_eq!(HybridPublicKey::from_bytes(&key.to_bytes()).unwrap(), key);
+        assert!(HybridPublicKey::from_bytes(&key.to_bytes()[1..]).is_err());
+
+        let signature = identity.sign_identity(b"data").unwrap();
+        let json = serde_json::to_value(&signature).unwrap();
+        assert!(
+            json.as_object().unwrap().values().all(|v| v.is_string()),
+            "{json}"
+        );
+        assert_eq!(
+            serde_json::from_value::<IdentitySignature>(json).unwrap(),
+            signature
+        );
+        let json = serde_json::to_value(IdentityKey::Hybrid(key.clone())).unwrap();
+        assert!(json["Hybrid"].is_string(), "{json}");
+        assert!(HybridSignature::from_bytes(&[0u8; HYBRID_SIGNATURE_SIZE - 1]).is_err());
+
+        // The ML-DSA seed travels with the key pair
+        let restored: KeyPair =
+            bincode::deserialize(&bincode::serialize(&identity).unwrap()).unwrap();
+        assert_eq!(restored.identity_key().unwrap(), IdentityKey::Hybrid(key));
+    }
+}
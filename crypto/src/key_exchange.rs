@@ -3,12 +3,15 @@
 //! Uses Curve25519 for secure key agreement between two peers.
 //! Supports both one-time and ephemeral key exchanges.
 
+use crate::registry::Secret;
 use crate::{CryptoError, CryptoResult};
-use ed25519_dalek::{Signature, SigningKey, VerifyingKey};
-use rand::RngCore;
+use alloc::{string::ToString, vec::Vec};
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use rand_core::CryptoRngCore;
 use serde::{Deserialize, Serialize};
 use x25519_dalek::{PublicKey, StaticSecret};
-use zeroize::Zeroize;
+use zeroize::{Zeroize, Zeroizing};
 
 /// 32-byte Curve25519 key size
 pub const CURVE25519_KEY_SIZE: usize = 32;
@@ -20,31 +23,27 @@ pub const SIGNATURE_SIZE: usize = 64;
 #[derive(Clone, Serialize, Deserialize)]
 pub struct KeyPair {
     /// Private key (kept secret)
-    private_key: Vec<u8>,
+    private_key: Secret<Vec<u8>>,
 
     /// Public key (shared)
+    #[serde(with = "crate::encoding::base64_array")]
     pub public_key: [u8; CURVE25519_KEY_SIZE],
 
-    /// Signing key for digital signatures
-    signing_key: Vec<u8>,
+    /// Ed25519 signing key, followed by the ML-DSA-65 seed for hybrid identities
+    signing_key: Secret<Vec<u8>>,
 
     /// Verification key (public)
+    #[serde(with = "crate::encoding::base64_array")]
     pub verifying_key: [u8; 32],
 }
 
-impl Drop for KeyPair {
-    fn drop(&mut self) {
-        self.private_key.zeroize();
-        self.signing_key.zeroize();
-    }
-}
-
 /// Ephemeral ECDH for session key establishment
 #[derive(Clone, Zeroize)]
 #[zeroize(drop)]
 pub struct EphemeralDH {
     /// Ephemeral private key
-    private_key: StaticSecret,
+    #[zeroize(skip)]
+    private_key: Secret<StaticSecret>,
 
     /// Ephemeral public key
     pub public_key: PublicKey,
@@ -52,10 +51,16 @@ pub struct EphemeralDH {
 
 impl KeyPair {
     /// Generate new identity key pair
+    #[cfg(feature = "std")]
     pub fn generate() -> CryptoResult<Self> {
+        Self::generate_with_rng(&mut rand::thread_rng())
+    }
+
+    /// Generate new identity key pair from a caller-supplied RNG
+    pub fn generate_with_rng(rng: &mut impl CryptoRngCore) -> CryptoResult<Self> {
         // Generate Curve25519 key pair for key exchange
         let mut seed = [0u8; 32];
-        rand::thread_rng().fill_bytes(&mut seed);
+        rng.fill_bytes(&mut seed);
         let private_key = StaticSecret::from(seed);
         let public_key = PublicKey::from(&private_key);
 
@@ -64,10 +69,10 @@ impl KeyPair {
         let verifying_key = signing_key.verifying_key();
 
         Ok(KeyPair {
-            private_key: private_key.as_bytes().to_vec(),
+            private_key: Secret::new(private_key.as_bytes().to_vec()),
             public_key: *public_key.as_bytes(),
-            signing_key: signing_key.to_bytes().to_vec(),
-            verifying_key: verifying_key.as_bytes().clone(),
+            signing_key: Secret::new(signing_key.to_bytes().to_vec()),
+            verifying_key: *verifying_key.as_bytes(),
         })
     }
 
@@ -80,35 +85,110 @@ impl KeyPair {
         let verifying_key = signing_key_ed.verifying_key();
 
         Ok(KeyPair {
-            private_key: private_key.to_vec(),
+            private_key: Secret::new(private_key.to_vec()),
             public_key: *public_key.as_bytes(),
-            signing_key: signing_key.to_vec(),
-            verifying_key: verifying_key.as_bytes().clone(),
+            signing_key: Secret::new(signing_key.to_vec()),
+            verifying_key: *verifying_key.as_bytes(),
         })
     }
 
     /// Perform ECDH with peer's public key to derive shared secret
     pub fn compute_shared_secret(&self, peer_public_key: &[u8; 32]) -> CryptoResult<[u8; 32]> {
-        let private_secret = StaticSecret::from(
-            <[u8; 32]>::try_from(self.private_key.as_slice())
-                .map_err(|_| CryptoError::InvalidKey("Invalid private key length".to_string()))?,
-        );
+        self.private_key.with(|private_key| {
+            let private_secret =
+                StaticSecret::from(<[u8; 32]>::try_from(private_key.as_slice()).map_err(|_| {
+                    CryptoError::InvalidKey("Invalid private key length".to_string())
+                })?);
 
-        let peer_public = PublicKey::from(*peer_public_key);
-        let shared_secret = private_secret.diffie_hellman(&peer_public);
+            let peer_public = PublicKey::from(*peer_public_key);
+            let shared_secret = private_secret.diffie_hellman(&peer_public);
 
-        Ok(*shared_secret.as_bytes())
+            Ok(*shared_secret.as_bytes())
+        })
     }
 
     /// Sign data with private key
     pub fn sign(&self, data: &[u8]) -> CryptoResult<[u8; SIGNATURE_SIZE]> {
-        let signing_key = SigningKey::from_bytes(
-            <&[u8; 32]>::try_from(self.signing_key.as_slice())
-                .map_err(|_| CryptoError::InvalidKey("Invalid signing key length".to_string()))?,
-        );
+        self.signing_key.with(|signing_key| {
+            let signature = ed25519_signing_key(signing_key)?.sign(data);
+            Ok(signature.to_bytes())
+        })
+    }
+
+    /// Generate a hybrid Ed25519 + ML-DSA-65 identity
+    #[cfg(feature = "pq")]
+    pub fn generate_hybrid() -> CryptoResult<Self> {
+        Self::generate_hybrid_with_rng(&mut rand::thread_rng())
+    }
+
+    /// Generate a hybrid identity from a caller-supplied RNG
+    ///
+    /// The X25519 and Ed25519 keys are the ones `generate_with_rng` would
+    /// produce; the ML-DSA seed is drawn after them. `sign` keeps producing
+    /// plain Ed25519 signatures, `sign_identity` produces hybrid ones.
+    #[cfg(feature = "pq")]
+    pub fn generate_hybrid_with_rng(rng: &mut impl CryptoRngCore) -> CryptoResult<Self> {
+        use crate::hybrid_signature::ML_DSA_SEED_SIZE;
+
+        let classical = Self::generate_with_rng(rng)?;
+        let mut signing_key = Zeroizing::new(Vec::with_capacity(32 + ML_DSA_SEED_SIZE));
+        classical.signing_key.with(|ed25519| {
+            signing_key.extend_from_slice(ed25519);
+            Ok(())
+        })?;
+
+        let mut ml_dsa_seed = Zeroizing::new([0u8; ML_DSA_SEED_SIZE]);
+        rng.fill_bytes(ml_dsa_seed.as_mut());
+        signing_key.extend_from_slice(ml_dsa_seed.as_ref());
+
+        Ok(KeyPair {
+            signing_key: Secret::new(core::mem::take(&mut *signing_key)),
+            ..classical
+        })
+    }
+
+    /// Whether this identity also signs with ML-DSA-65
+    #[cfg(feature = "pq")]
+    pub fn is_hybrid(&self) -> CryptoResult<bool> {
+        self.signing_key
+            .with(|signing_key| Ok(ml_dsa_seed(signing_key).is_some()))
+    }
+
+    /// Public identity key: the Ed25519 key, or the composite key for hybrid identities
+    #[cfg(feature = "pq")]
+    pub fn identity_key(&self) -> CryptoResult<crate::hybrid_signature::IdentityKey> {
+        use crate::hybrid_signature::{ml_dsa_public_key, HybridPublicKey, IdentityKey};
+
+        self.signing_key
+            .with(|signing_key| match ml_dsa_seed(signing_key) {
+                None => Ok(IdentityKey::Classical(self.verifying_key)),
+                Some(seed) => {
+                    let mut composite = self.verifying_key.to_vec();
+                    composite.extend_from_slice(&ml_dsa_public_key(seed));
+                    HybridPublicKey::from_bytes(&composite).map(IdentityKey::Hybrid)
+                }
+            })
+    }
 
-        let signature = signing_key.sign(data);
-        Ok(signature.to_bytes())
+    /// Sign as this identity: Ed25519 alone, or both schemes for hybrid identities
+    ///
+    /// Verify with `IdentityKey::verify` on `identity_key()`.
+    #[cfg(feature = "pq")]
+    pub fn sign_identity(
+        &self,
+        data: &[u8],
+    ) -> CryptoResult<crate::hybrid_signature::IdentitySignature> {
+        use crate::hybrid_signature::{self, IdentitySignature};
+
+        self.signing_key.with(|signing_key| {
+            let ed25519 = ed25519_signing_key(signing_key)?;
+            match ml_dsa_seed(signing_key) {
+                None => Ok(IdentitySignature::Classical(ed25519.sign(data).to_bytes())),
+                Some(seed) => {
+                    hybrid_signature::sign(&ed25519, seed, data).map(IdentitySignature::Hybrid)
+                }
+            }
+        })
     }
 
     /// Get the verifying key as bytes
@@ -116,30 +196,58 @@ impl KeyPair {
         &self.verifying_key
     }
 
-    /// Get private key (careful!)
-    pub fn get_private_key(&self) -> &[u8] {
-        &self.private_key
+    /// Get a copy of the private key (careful!)
+    pub fn get_private_key(&self) -> CryptoResult<Zeroizing<Vec<u8>>> {
+        self.private_key
+            .with(|private_key| Ok(Zeroizing::new(private_key.clone())))
+    }
+
+    /// Both private halves, `private_key || signing_key`, for sealing into a key file
+    #[cfg(feature = "std")]
+    pub(crate) fn secret_bytes(&self) -> CryptoResult<Zeroizing<[u8; 64]>> {
+        let mut out = Zeroizing::new([0u8; 64]);
+        for (half, secret) in out
+            .chunks_exact_mut(32)
+            .zip([&self.private_key, &self.signing_key])
+        {
+            secret.with(|bytes| {
+                if bytes.len() != 32 {
+                    return Err(CryptoError::InvalidKey(
+                        "Invalid private key length".to_string(),
+                    ));
+                }
+                half.copy_from_slice(bytes);
+                Ok(())
+            })?;
+        }
+        Ok(out)
     }
 }
 
 impl EphemeralDH {
     /// Generate new ephemeral key pair
+    #[cfg(feature = "std")]
     pub fn generate() -> CryptoResult<Self> {
-        let mut seed = [0u8; 32];
-        rand::thread_rng().fill_bytes(&mut seed);
-        let private_key = StaticSecret::from(seed);
+        Self::generate_with_rng(&mut rand::thread_rng())
+    }
+
+    /// Generate new ephemeral key pair from a caller-supplied RNG
+    pub fn generate_with_rng(rng: &mut impl CryptoRngCore) -> CryptoResult<Self> {
+        let private_key = StaticSecret::random_from_rng(rng);
         let public_key = PublicKey::from(&private_key);
 
         Ok(EphemeralDH {
-            private_key,
+            private_key: Secret::new(private_key),
             public_key,
         })
     }
 
     /// Perform ECDH with peer's ephemeral public key
-    pub fn compute_shared_secret(&self, peer_public_key: &PublicKey) -> [u8; 32] {
-        let shared_secret = self.private_key.diffie_hellman(peer_public_key);
-        *shared_secret.as_bytes()
+    pub fn compute_shared_secret(&self, peer_public_key: &PublicKey) -> CryptoResult<[u8; 32]> {
+        self.private_key.with(|private_key| {
+            let shared_secret = private_key.diffie_hellman(peer_public_key);
+            Ok(*shared_secret.as_bytes())
+        })
     }
 
     /// Get public key
@@ -153,6 +261,24 @@ impl EphemeralDH {
     }
 }
 
+/// Ed25519 signing key from the stored bytes
+///
+/// Hybrid identities append a 32-byte ML-DSA seed; builds without `pq` still
+/// sign with the Ed25519 half.
+fn ed25519_signing_key(signing_key: &[u8]) -> CryptoResult<SigningKey> {
+    match signing_key.len() {
+        32 | 64 => signing_key.first_chunk::<32>().map(SigningKey::from_bytes),
+        _ => None,
+    }
+    .ok_or_else(|| CryptoError::InvalidKey("Invalid signing key length".to_string()))
+}
+
+/// ML-DSA seed of a hybrid identity's stored signing key
+#[cfg(feature = "pq")]
+fn ml_dsa_seed(signing_key: &[u8]) -> Option<&[u8; 32]> {
+    signing_key.get(32..)?.try_into().ok()
+}
+
 /// Verify a signature
 pub fn verify_signature(
     verifying_key: &[u8; 32],
@@ -160,7 +286,7 @@ pub fn verify_signature(
     signature: &[u8; SIGNATURE_SIZE],
 ) -> CryptoResult<()> {
     let vkey = VerifyingKey::from_bytes(verifying_key)
-        .map_err(|e| CryptoError::SignatureVerificationFailed)?;
+        .map_err(|_| CryptoError::SignatureVerificationFailed)?;
 
     let sig = Signature::from_bytes(signature);
 
@@ -168,6 +294,102 @@ pub fn verify_signature(
         .map_err(|_| CryptoError::SignatureVerificationFailed)
 }
 
+/// Verify many signatures at once
+///
+/// Uses Ed25519 batch verification, which is up to about twice as fast as a
+/// loop over `verify_signature` for large batches. Each item first gets the checks
+/// `verify_signature` makes beyond the verification equation (no small-order
+/// key or `R`; canonical `s` is enforced by the batch itself).
+///
+/// The batch equation is cofactorless with random-looking but deterministic
+/// coefficients, so a key or `R` with a torsion component can cancel out of
+/// it for a signer who grinds the message. Such items never enter the batch
+/// and are verified with `verify_strict` on their own instead, so the batch
+/// accepts exactly what `verify_signature` accepts. If the batch fails, the
+/// remaining items are verified one by one.
+///
+/// # Arguments
+/// * `items` - `(verifying_key, data, signature)` triples
+///
+/// # Errors
+/// `BatchVerificationFailed` with the sorted indices of every invalid signature
+pub fn verify_signatures_batch(
+    items: &[(&[u8; 32], &[u8], &[u8; SIGNATURE_SIZE])],
+) -> CryptoResult<()> {
+    let mut failed = Vec::new();
+    let mut indices = Vec::with_capacity(items.len());
+    let mut keys = Vec::with_capacity(items.len());
+    let mut messages = Vec::with_capacity(items.len());
+    let mut signatures = Vec::with_capacity(items.len());
+
+    for (i, (verifying_key, data, signature)) in items.iter().enumerate() {
+        match strict_precheck(verifying_key, signature) {
+            Precheck::Batch(vkey, sig) => {
+                indices.push(i);
+                keys.push(vkey);
+                messages.push(*data);
+                signatures.push(sig);
+            }
+            Precheck::Single(vkey, sig) => {
+                if vkey.verify_strict(data, &sig).is_err() {
+                    failed.push(i);
+                }
+            }
+            Precheck::Invalid => failed.push(i),
+        }
+    }
+
+    if ed25519_dalek::verify_batch(&messages, &signatures, &keys).is_err() {
+        // Fall back to single verification to find the bad signatures
+        for (j, &i) in indices.iter().enumerate() {
+            if keys[j].verify_strict(messages[j], &signatures[j]).is_err() {
+                failed.push(i);
+            }
+        }
+    }
+    failed.sort_unstable();
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(CryptoError::BatchVerificationFailed(failed))
+    }
+}
+
+/// Where a batch item is verified
+enum Precheck {
+    /// Safe for the cofactorless batch equation
+    Batch(VerifyingKey, Signature),
+
+    /// Key or `R` has a torsion component; verify with `verify_strict` alone
+    Single(VerifyingKey, Signature),
+
+    /// Rejected by `verify_strict` before its equation
+    Invalid,
+}
+
+/// Parse a key and signature, rejecting what `verify_strict` rejects before its equation
+fn strict_precheck(verifying_key: &[u8; 32], signature: &[u8; SIGNATURE_SIZE]) -> Precheck {
+    let Ok(vkey) = VerifyingKey::from_bytes(verifying_key) else {
+        return Precheck::Invalid;
+    };
+    let sig = Signature::from_bytes(signature);
+    let (Some(a), Some(r)) = (
+        CompressedEdwardsY(*verifying_key).decompress(),
+        CompressedEdwardsY(*sig.r_bytes()).decompress(),
+    ) else {
+        return Precheck::Invalid;
+    };
+
+    if vkey.is_weak() || r.is_small_order() {
+        Precheck::Invalid
+    } else if !a.is_torsion_free() || !r.is_torsion_free() {
+        Precheck::Single(vkey, sig)
+    } else {
+        Precheck::Batch(vkey, sig)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -184,8 +406,12 @@ mod tests {
         let keypair1 = KeyPair::generate().unwrap();
         let keypair2 = KeyPair::generate().unwrap();
 
-        let secret1 = keypair1.compute_shared_secret(&keypair2.public_key).unwrap();
-        let secret2 = keypair2.compute_shared_secret(&keypair1.public_key).unwrap();
+        let secret1 = keypair1
+            .compute_shared_secret(&keypair2.public_key)
+            .unwrap();
+        let secret2 = keypair2
+            .compute_shared_secret(&keypair1.public_key)
+            .unwrap();
 
         // Both sides compute the same shared secret
         assert_eq!(secret1, secret2);
@@ -214,13 +440,196 @@ mod tests {
         assert!(result.is_err());
     }
 
+    fn signed_batch(count: usize) -> Vec<(KeyPair, Vec<u8>, [u8; SIGNATURE_SIZE])> {
+        (0..count)
+            .map(|i| {
+                let keypair = KeyPair::generate().unwrap();
+                let data = format!("message {}", i).into_bytes();
+                let signature = keypair.sign(&data).unwrap();
+                (keypair, data, signature)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_batch_verification() {
+        let batch = signed_batch(32);
+        let items: Vec<_> = batch
+            .iter()
+            .map(|(kp, data, sig)| (&kp.verifying_key, data.as_slice(), sig))
+            .collect();
+
+        assert!(verify_signatures_batch(&items).is_ok());
+        assert!(verify_signatures_batch(&[]).is_ok());
+    }
+
+    #[test]
+    fn test_batch_verification_reports_failed_indices() {
+        let mut batch = signed_batch(16);
+        batch[3].1.push(b'!');
+        batch[11].2[0] ^= 1;
+        let items: Vec<_> = batch
+            .iter()
+            .map(|(kp, data, sig)| (&kp.verifying_key, data.as_slice(), sig))
+            .collect();
+
+        match verify_signatures_batch(&items) {
+            Err(CryptoError::BatchVerificationFailed(failed)) => assert_eq!(failed, vec![3, 11]),
+            other => panic!("expected failed indices, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_batch_verification_is_strict() {
+        let batch = signed_batch(4);
+        let mut items: Vec<_> = batch
+            .iter()
+            .map(|(kp, data, sig)| (&kp.verifying_key, data.as_slice(), *sig))
+            .collect();
+
+        // Identity key with R = identity and s = 0 satisfies the bare equation
+        let mut identity = [0u8; 32];
+        identity[0] = 1;
+        let mut trivial = [0u8; SIGNATURE_SIZE];
+        trivial[0] = 1;
+        items.push((&identity, b"anything".as_slice(), trivial));
+
+        // s + l is the same scalar but a malleated encoding
+        let order =
+            hex_literal::hex!("edd3f55c1a631258d69cf7a2def9de14 00000000000000000000000000000010");
+        let mut malleated = items[0].2;
+        let mut carry = 0u16;
+        for (byte, l) in malleated[32..].iter_mut().zip(order) {
+            let sum = *byte as u16 + l as u16 + carry;
+            *byte = sum as u8;
+            carry = sum >> 8;
+        }
+        items[1] = (items[0].0, items[0].1, malleated);
+
+        for &i in &[1, 4] {
+            assert!(verify_signature(items[i].0, items[i].1, &items[i].2).is_err());
+        }
+
+        let refs: Vec<_> = items
+            .iter()
+            .map(|(vk, data, sig)| (*vk, *data, sig))
+            .collect();
+        match verify_signatures_batch(&refs) {
+            Err(CryptoError::BatchVerificationFailed(failed)) => assert_eq!(failed, vec![1, 4]),
+            other => panic!("expected failed indices, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_batch_rejects_mixed_order_r() {
+        use curve25519_dalek::edwards::EdwardsPoint;
+        use curve25519_dalek::scalar::Scalar;
+        use sha2::{Digest, Sha512};
+
+        // (0, -1) has order 2
+        let mut encoded = [0xffu8; 32];
+        encoded[0] = 0xec;
+        encoded[31] = 0x7f;
+        let torsion: EdwardsPoint = CompressedEdwardsY(encoded).decompress().unwrap();
+        assert!(torsion.is_small_order());
+
+        // Sign with a raw scalar so R can carry the torsion point: s = r + k·a
+        // with R = r·B + T and k = H(R || A || M)
+        let wide = |seed: u8| Scalar::from_bytes_mod_order_wide(&[seed; 64]);
+        let (secret, nonce) = (wide(7), wide(9));
+        let public = EdwardsPoint::mul_base(&secret).compress().to_bytes();
+        let r = (EdwardsPoint::mul_base(&nonce) + torsion)
+            .compress()
+            .to_bytes();
+        let vkey = VerifyingKey::from_bytes(&public).unwrap();
+
+        let batch = signed_batch(3);
+        let honest: Vec<_> = batch
+            .iter()
+            .map(|(kp, data, sig)| {
+                let vkey = VerifyingKey::from_bytes(&kp.verifying_key).unwrap();
+                (vkey, data.as_slice(), Signature::from_bytes(sig))
+            })
+            .collect();
+
+        // Grind the message until the bare batch equation over the whole
+        // batch accepts it, which happens whenever its coefficient is even
+        let (data, forged) = (0u32..256)
+            .find_map(|i| {
+                let data = format!("grind {}", i).into_bytes();
+                let k = Scalar::from_hash(
+                    Sha512::new()
+                        .chain_update(r)
+                        .chain_update(public)
+                        .chain_update(&data),
+                );
+                let mut forged = [0u8; SIGNATURE_SIZE];
+                forged[..32].copy_from_slice(&r);
+                forged[32..].copy_from_slice((nonce + k * secret).as_bytes());
+
+                let mut all = honest.clone();
+                all.insert(1, (vkey, &data, Signature::from_bytes(&forged)));
+                let messages: Vec<_> = all.iter().map(|item| item.1).collect();
+                let signatures: Vec<_> = all.iter().map(|item| item.2).collect();
+                let keys: Vec<_> = all.iter().map(|item| item.0).collect();
+                ed25519_dalek::verify_batch(&messages, &signatures, &keys)
+                    .is_ok()
+                    .then_some((data, forged))
+            })
+            .unwrap();
+
+        assert!(verify_signature(&public, &data, &forged).is_err());
+
+        let mut items: Vec<_> = batch
+            .iter()
+            .map(|(kp, data, sig)| (&kp.verifying_key, data.as_slice(), sig))
+            .collect();
+        items.insert(1, (&public, &data, &forged));
+
+        match verify_signatures_batch(&items) {
+            Err(CryptoError::BatchVerificationFailed(failed)) => assert_eq!(failed, vec![1]),
+            other => panic!("expected failed indices, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_generate_with_rng() {
+        let keypair = KeyPair::generate_with_rng(&mut rand::thread_rng()).unwrap();
+        let ephemeral = EphemeralDH::generate_with_rng(&mut rand::thread_rng()).unwrap();
+
+        let secret1 = keypair
+            .compute_shared_secret(ephemeral.public_key_bytes())
+            .unwrap();
+        let secret2 = ephemeral
+            .compute_shared_secret(&PublicKey::from(keypair.public_key))
+            .unwrap();
+        assert_eq!(secret1, secret2);
+    }
+
+    #[test]
+    fn test_public_keys_serialize_as_base64() {
+        let keypair = KeyPair::generate().unwrap();
+        let json: serde_json::Value = serde_json::to_value(&keypair).unwrap();
+
+        assert!(json["public_key"].is_string());
+        assert!(json["verifying_key"].is_string());
+
+        let restored: KeyPair = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.public_key, keypair.public_key);
+        assert_eq!(restored.verifying_key, keypair.verifying_key);
+    }
+
     #[test]
     fn test_ephemeral_dh() {
         let ephemeral1 = EphemeralDH::generate().unwrap();
         let ephemeral2 = EphemeralDH::generate().unwrap();
 
-        let secret1 = ephemeral1.compute_shared_secret(&ephemeral2.public_key);
-        let secret2 = ephemeral2.compute_shared_secret(&ephemeral1.public_key);
+        let secret1 = ephemeral1
+            .compute_shared_secret(&ephemeral2.public_key)
+            .unwrap();
+        let secret2 = ephemeral2
+            .compute_shared_secret(&ephemeral1.public_key)
+            .unwrap();
 
         assert_eq!(secret1, secret2);
     }
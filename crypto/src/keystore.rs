@@ -0,0 +1,608 @@
+//! Pluggable Key Storage
+//!
+//! `KeyStore` is the interface to a long-term identity key: generate, sign,
+//! X25519 agreement and public-key export. Private keys never cross it, so
+//! the key can live in memory (`KeyPair`), in a password-encrypted file
+//! (`FileKeyStore`) or on a hardware token (`pkcs11::Pkcs11KeyStore`, behind
+//! the `pkcs11` feature).
+//!
+//! Key file format:
+//! `magic (8) || version (1) || password blob(private_key || signing_key)`
+//!
+//! The password blob (see `password`) stores its scrypt cost in its own
+//! authenticated header, so the cost can be raised without another format
+//! change and a planted key file cannot push the KDF outside the accepted
+//! bounds. Version 1 files (`salt (32) || nonce (24) || ciphertext` with a
+//! fixed scrypt cost after the version byte) are still opened and are
+//! rewritten in the current format on the next `generate`.
+
+use crate::key_exchange::{KeyPair, SIGNATURE_SIZE};
+use crate::CryptoResult;
+use rand_core::CryptoRngCore;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "std")]
+use crate::encryption::{KEY_SIZE, TAG_SIZE, XCHACHA_NONCE_SIZE};
+#[cfg(feature = "std")]
+use crate::password::{self, PasswordParams};
+#[cfg(feature = "std")]
+use crate::registry::Secret;
+#[cfg(feature = "std")]
+use crate::utils::derive_key_from_password;
+#[cfg(feature = "std")]
+use crate::CryptoError;
+#[cfg(feature = "std")]
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+#[cfg(feature = "std")]
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+#[cfg(feature = "std")]
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+#[cfg(feature = "std")]
+use zeroize::Zeroizing;
+
+/// Key file magic
+#[cfg(feature = "std")]
+pub const KEY_FILE_MAGIC: &[u8; 8] = b"CHAKKEY\0";
+
+/// Key file format version
+#[cfg(feature = "std")]
+pub const KEY_FILE_VERSION: u8 = 2;
+
+/// Key file size: magic, version and a password blob of both private keys
+#[cfg(feature = "std")]
+const KEY_FILE_SIZE: usize = 8 + 1 + password::HEADER_SIZE + 2 * KEY_SIZE + TAG_SIZE;
+
+#[cfg(feature = "std")]
+const V1_SALT_SIZE: usize = 32;
+
+/// Version 1 header size (magic, version, salt, nonce)
+#[cfg(feature = "std")]
+const V1_HEADER_SIZE: usize = 8 + 1 + V1_SALT_SIZE + XCHACHA_NONCE_SIZE;
+
+/// Version 1 key file size: header, both private keys and the tag
+#[cfg(feature = "std")]
+const V1_KEY_FILE_SIZE: usize = V1_HEADER_SIZE + 2 * KEY_SIZE + TAG_SIZE;
+
+/// Public halves of a stored identity key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicKeys {
+    /// X25519 key-agreement key
+    #[serde(with = "crate::encoding::base64_array")]
+    pub public_key: [u8; 32],
+
+    /// Ed25519 verifying key
+    #[serde(with = "crate::encoding::base64_array")]
+    pub verifying_key: [u8; 32],
+}
+
+/// Backend holding one identity key pair
+///
+/// Callers only ever get signatures, shared secrets and public keys back.
+pub trait KeyStore {
+    /// Replace the stored key with a freshly generated one
+    ///
+    /// The previous key is destroyed. Backends with their own RNG (hardware
+    /// tokens) ignore `rng`.
+    fn generate(&mut self, rng: &mut dyn CryptoRngCore) -> CryptoResult<PublicKeys>;
+
+    /// Export the public keys
+    fn public_keys(&self) -> CryptoResult<PublicKeys>;
+
+    /// Sign data with the Ed25519 key
+    fn sign(&self, data: &[u8]) -> CryptoResult<[u8; SIGNATURE_SIZE]>;
+
+    /// Perform X25519 with a peer's public key
+    fn compute_shared_secret(&self, peer_public_key: &[u8; 32]) -> CryptoResult<[u8; 32]>;
+}
+
+/// In-memory backend
+impl KeyStore for KeyPair {
+    fn generate(&mut self, rng: &mut dyn CryptoRngCore) -> CryptoResult<PublicKeys> {
+        *self = KeyPair::generate_with_rng(&mut &mut *rng)?;
+        KeyStore::public_keys(self)
+    }
+
+    fn public_keys(&self) -> CryptoResult<PublicKeys> {
+        Ok(PublicKeys {
+            public_key: self.public_key,
+            verifying_key: self.verifying_key,
+        })
+    }
+
+    fn sign(&self, data: &[u8]) -> CryptoResult<[u8; SIGNATURE_SIZE]> {
+        KeyPair::sign(self, data)
+    }
+
+    fn compute_shared_secret(&self, peer_public_key: &[u8; 32]) -> CryptoResult<[u8; 32]> {
+        KeyPair::compute_shared_secret(self, peer_public_key)
+    }
+}
+
+/// Identity key kept in a password-encrypted file
+///
+/// The key is decrypted once on `open` and held in memory (registered for
+/// panic wipe) until the store is dropped. The password is kept the same
+/// way so `generate` can re-seal the file under a fresh salt.
+#[cfg(feature = "std")]
+pub struct FileKeyStore {
+    path: PathBuf,
+    password: Secret<Vec<u8>>,
+    params: PasswordParams,
+    keys: KeyPair,
+}
+
+#[cfg(feature = "std")]
+impl fmt::Debug for FileKeyStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileKeyStore")
+            .field("path", &self.path)
+            .field("verifying_key", &hex::encode(self.keys.verifying_key))
+            .finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl FileKeyStore {
+    /// Create a key file holding a freshly generated identity key
+    ///
+    /// Fails if `path` already exists.
+    pub fn create(path: impl AsRef<Path>, password: &[u8]) -> CryptoResult<Self> {
+        Self::import(path, password, KeyPair::generate()?)
+    }
+
+    /// Create a key file holding an existing identity key
+    ///
+    /// # Arguments
+    /// * `path` - Key file to create; must not exist
+    /// * `password` - Password the file key is derived from
+    /// * `keys` - Identity key to store
+    ///
+    /// # Errors
+    /// `InvalidKey` for hybrid identities; the key file format holds classical keys only
+    pub fn import(path: impl AsRef<Path>, password: &[u8], keys: KeyPair) -> CryptoResult<Self> {
+        Self::import_with_params(path, password, keys, PasswordParams::default())
+    }
+
+    /// Create a key file holding an existing identity key at a chosen scrypt cost
+    ///
+    /// The cost is stored in the file and kept when `generate` rewrites it.
+    ///
+    /// # Errors
+    /// * `KeyDerivationError` - `params` outside the bounds of `password`
+    /// * `InvalidKey` - hybrid identities; the key file format holds classical keys only
+    pub fn import_with_params(
+        path: impl AsRef<Path>,
+        password: &[u8],
+        keys: KeyPair,
+        params: PasswordParams,
+    ) -> CryptoResult<Self> {
+        params.validate()?;
+        let store = FileKeyStore {
+            path: path.as_ref().to_path_buf(),
+            password: Secret::new(password.to_vec()),
+            params,
+            keys,
+        };
+
+        let sealed = store.seal(&store.keys, &mut rand::thread_rng())?;
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&store.path)
+            .map_err(io_error)?;
+        file.write_all(&sealed).map_err(io_error)?;
+        file.sync_all().map_err(io_error)?;
+
+        Ok(store)
+    }
+
+    /// Unlock an existing key file
+    ///
+    /// # Errors
+    /// * `InvalidKey` - not a key file, or an unsupported version
+    /// * `DecryptionError` - wrong password or corrupted file
+    /// * `KeyDerivationError` - the stored scrypt cost is outside the bounds of `password`
+    /// * `KeyStoreError` - the file could not be read
+    pub fn open(path: impl AsRef<Path>, password: &[u8]) -> CryptoResult<Self> {
+        let data = Zeroizing::new(fs::read(path.as_ref()).map_err(io_error)?);
+        if data.len() < 9 || &data[..8] != KEY_FILE_MAGIC {
+            return Err(CryptoError::InvalidKey("Not a key file".to_string()));
+        }
+
+        let (secrets, params) = match (data[8], data.len()) {
+            (KEY_FILE_VERSION, KEY_FILE_SIZE) => {
+                let params = password::blob_params(&data[9..])?;
+                params.validate()?;
+                let secrets = password::open_with_password(password, &data[9..]).map_err(|_| {
+                    CryptoError::DecryptionError("Wrong password or corrupted key file".to_string())
+                });
+                (Zeroizing::new(secrets?), params)
+            }
+            (1, V1_KEY_FILE_SIZE) => (open_v1(&data, password)?, PasswordParams::INTERACTIVE),
+            (KEY_FILE_VERSION, _) | (1, _) => {
+                return Err(CryptoError::InvalidKey("Not a key file".to_string()));
+            }
+            (version, _) => {
+                return Err(CryptoError::InvalidKey(format!(
+                    "Unsupported key file version {}",
+                    version
+                )));
+            }
+        };
+
+        let mut private_key = Zeroizing::new([0u8; KEY_SIZE]);
+        let mut signing_key = Zeroizing::new([0u8; KEY_SIZE]);
+        private_key.copy_from_slice(&secrets[..KEY_SIZE]);
+        signing_key.copy_from_slice(&secrets[KEY_SIZE..]);
+
+        Ok(FileKeyStore {
+            path: path.as_ref().to_path_buf(),
+            password: Secret::new(password.to_vec()),
+            params,
+            keys: KeyPair::from_bytes(&private_key, &signing_key)?,
+        })
+    }
+
+    /// Path of the key file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// scrypt cost the key file is sealed with
+    pub fn params(&self) -> PasswordParams {
+        self.params
+    }
+
+    /// Encrypt both private keys into a complete key file
+    fn seal(&self, keys: &KeyPair, rng: &mut impl CryptoRngCore) -> CryptoResult<Vec<u8>> {
+        let secrets = keys.secret_bytes()?;
+        let blob = self.password.with(|password| {
+            password::seal_with_password_with_rng(password, secrets.as_ref(), self.params, rng)
+        })?;
+
+        let mut out = Vec::with_capacity(KEY_FILE_SIZE);
+        out.extend_from_slice(KEY_FILE_MAGIC);
+        out.push(KEY_FILE_VERSION);
+        out.extend_from_slice(&blob);
+        Ok(out)
+    }
+}
+
+/// Decrypt the private keys of a version 1 key file
+///
+/// Version 1 used a fixed scrypt cost (`utils::derive_key_from_password`)
+/// and bound its whole header as associated data.
+#[cfg(feature = "std")]
+fn open_v1(data: &[u8], password: &[u8]) -> CryptoResult<Zeroizing<Vec<u8>>> {
+    let mut salt = [0u8; V1_SALT_SIZE];
+    salt.copy_from_slice(&data[9..9 + V1_SALT_SIZE]);
+    let file_key = Zeroizing::new(derive_key_from_password(password, &salt)?);
+
+    let secrets = XChaCha20Poly1305::new(file_key.as_ref().into())
+        .decrypt(
+            XNonce::from_slice(&data[9 + V1_SALT_SIZE..V1_HEADER_SIZE]),
+            Payload {
+                msg: &data[V1_HEADER_SIZE..],
+                aad: &data[..V1_HEADER_SIZE],
+            },
+        )
+        .map_err(|_| {
+            CryptoError::DecryptionError("Wrong password or corrupted key file".to_string())
+        })?;
+    Ok(Zeroizing::new(secrets))
+}
+
+#[cfg(feature = "std")]
+impl KeyStore for FileKeyStore {
+    /// Generate a new key and atomically replace the key file
+    fn generate(&mut self, rng: &mut dyn CryptoRngCore) -> CryptoResult<PublicKeys> {
+        let keys = KeyPair::generate_with_rng(&mut &mut *rng)?;
+        let sealed = self.seal(&keys, &mut &mut *rng)?;
+
+        replace_file(&self.path, &sealed, rng).map_err(io_error)?;
+
+        self.keys = keys;
+        KeyStore::public_keys(&self.keys)
+    }
+
+    fn public_keys(&self) -> CryptoResult<PublicKeys> {
+        KeyStore::public_keys(&self.keys)
+    }
+
+    fn sign(&self, data: &[u8]) -> CryptoResult<[u8; SIGNATURE_SIZE]> {
+        self.keys.sign(data)
+    }
+
+    fn compute_shared_secret(&self, peer_public_key: &[u8; 32]) -> CryptoResult<[u8; 32]> {
+        self.keys.compute_shared_secret(peer_public_key)
+    }
+}
+
+/// Write `data` to a fresh temporary file next to `path`, then rename it over `path`
+///
+/// The temporary name carries a random suffix and is opened with
+/// `create_new`, so concurrent writers never share a file and an existing
+/// file or symlink at that name is never followed or truncated.
+#[cfg(feature = "std")]
+fn replace_file(path: &Path, data: &[u8], rng: &mut dyn CryptoRngCore) -> io::Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no file name"))?
+        .to_string_lossy();
+
+    let mut attempts = 0;
+    let (tmp, mut file) = loop {
+        let mut suffix = [0u8; 8];
+        rng.fill_bytes(&mut suffix);
+        let tmp = path.with_file_name(format!(".{}.{}.tmp", name, hex::encode(suffix)));
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmp)
+        {
+            Ok(file) => break (tmp, file),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempts < 8 => attempts += 1,
+            Err(e) => return Err(e),
+        }
+    };
+
+    let result = file
+        .write_all(data)
+        .and_then(|_| file.sync_all())
+        .and_then(|_| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+#[cfg(feature = "std")]
+fn io_error(e: io::Error) -> CryptoError {
+    CryptoError::KeyStoreError(format!("Key file: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_exchange::verify_signature;
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
+
+    fn leftover_temp_files(path: &Path) -> bool {
+        let prefix = format!(".{}.", path.file_name().unwrap().to_string_lossy());
+        fs::read_dir(path.parent().unwrap()).unwrap().any(|entry| {
+            entry
+                .unwrap()
+                .file_name()
+                .to_string_lossy()
+                .starts_with(&prefix)
+        })
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("chakchat-keystore-{}-{}", std::process::id(), name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    /// Exercise a backend only through the trait
+    fn check_backend(store: &mut dyn KeyStore) {
+        let mut rng = ChaCha20Rng::seed_from_u64(40);
+        let keys = store.generate(&mut rng).unwrap();
+        assert_eq!(store.public_keys().unwrap(), keys);
+
+        let signature = store.sign(b"hello").unwrap();
+        assert!(verify_signature(&keys.verifying_key, b"hello", &signature).is_ok());
+
+        let peer = KeyPair::generate_with_rng(&mut rng).unwrap();
+        assert_eq!(
+            store.compute_shared_secret(&peer.public_key).unwrap(),
+            peer.compute_shared_secret(&keys.public_key).unwrap()
+        );
+
+        let rotated = store.generate(&mut rng).unwrap();
+        assert_ne!(rotated, keys);
+        assert_eq!(store.public_keys().unwrap(), rotated);
+    }
+
+    #[test]
+    fn test_key_pair_backend() {
+        let mut keys = KeyPair::generate().unwrap();
+        check_backend(&mut keys);
+    }
+
+    #[test]
+    fn test_file_backend() {
+        let path = temp_path("backend");
+        let mut store = FileKeyStore::create(&path, b"password").unwrap();
+
+        // A file at the old fixed temp name is neither reused nor truncated
+        let decoy = path.with_extension("tmp");
+        fs::write(&decoy, b"not ours").unwrap();
+        check_backend(&mut store);
+        assert_eq!(fs::read(&decoy).unwrap(), b"not ours");
+        fs::remove_file(&decoy).unwrap();
+
+        // The rotated key is what was persisted
+        let reopened = FileKeyStore::open(&path, b"password").unwrap();
+        assert_eq!(
+            reopened.public_keys().unwrap(),
+            store.public_keys().unwrap()
+        );
+        assert!(!leftover_temp_files(&path));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_file_import_and_open() {
+        let path = temp_path("import");
+        let keys = KeyPair::generate().unwrap();
+        let store = FileKeyStore::import(&path, b"password", keys.clone()).unwrap();
+        assert_eq!(store.path(), path.as_path());
+
+        let opened = FileKeyStore::open(&path, b"password").unwrap();
+        assert_eq!(
+            opened.public_keys().unwrap().verifying_key,
+            keys.verifying_key
+        );
+        assert_eq!(opened.sign(b"data").unwrap(), keys.sign(b"data").unwrap());
+
+        // Never clobbers an existing file
+        assert!(FileKeyStore::import(&path, b"password", keys).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_file_wrong_password_and_tampering() {
+        let path = temp_path("tamper");
+        FileKeyStore::create(&path, b"password").unwrap();
+
+        assert!(matches!(
+            FileKeyStore::open(&path, b"wrong"),
+            Err(CryptoError::DecryptionError(_))
+        ));
+
+        // Header bytes are authenticated too
+        let original = fs::read(&path).unwrap();
+        for i in [9, 9 + password::HEADER_SIZE - 1, KEY_FILE_SIZE - 1] {
+            let mut data = original.clone();
+            data[i] ^= 1;
+            fs::write(&path, &data).unwrap();
+            assert!(FileKeyStore::open(&path, b"password").is_err());
+        }
+
+        let mut data = original.clone();
+        data[8] = KEY_FILE_VERSION + 1;
+        fs::write(&path, &data).unwrap();
+        assert!(matches!(
+            FileKeyStore::open(&path, b"password"),
+            Err(CryptoError::InvalidKey(_))
+        ));
+
+        fs::write(&path, &original[..KEY_FILE_SIZE - 1]).unwrap();
+        assert!(matches!(
+            FileKeyStore::open(&path, b"password"),
+            Err(CryptoError::InvalidKey(_))
+        ));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_file_kdf_cost_is_stored_and_bounded() {
+        let path = temp_path("kdf");
+        let params = PasswordParams {
+            log_n: 15,
+            r: 8,
+            p: 1,
+        };
+        let mut store = FileKeyStore::import_with_params(
+            &path,
+            b"password",
+            KeyPair::generate().unwrap(),
+            params,
+        )
+        .unwrap();
+        assert_eq!(
+            password::blob_params(&fs::read(&path).unwrap()[9..]).unwrap(),
+            params
+        );
+
+        // Rotation keeps the cost
+        store.generate(&mut rand::thread_rng()).unwrap();
+        assert_eq!(
+            FileKeyStore::open(&path, b"password").unwrap().params(),
+            params
+        );
+
+        // A planted file cannot ask for more than the bounds allow
+        let mut data = fs::read(&path).unwrap();
+        data[9 + 10] = password::MAX_LOG_N + 1;
+        fs::write(&path, &data).unwrap();
+        assert!(matches!(
+            FileKeyStore::open(&path, b"password"),
+            Err(CryptoError::KeyDerivationError(_))
+        ));
+
+        let weak = PasswordParams {
+            log_n: password::MIN_LOG_N - 1,
+            r: 8,
+            p: 1,
+        };
+        fs::remove_file(&path).unwrap();
+        assert!(FileKeyStore::import_with_params(
+            &path,
+            b"password",
+            KeyPair::generate().unwrap(),
+            weak
+        )
+        .is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_file_opens_version_1() {
+        let path = temp_path("v1");
+        let keys = KeyPair::generate().unwrap();
+
+        // Version 1 layout, sealed as the old `seal` did
+        let salt = [7u8; V1_SALT_SIZE];
+        let nonce = [9u8; XCHACHA_NONCE_SIZE];
+        let file_key = derive_key_from_password(b"password", &salt).unwrap();
+        let mut data = KEY_FILE_MAGIC.to_vec();
+        data.push(1);
+        data.extend_from_slice(&salt);
+        data.extend_from_slice(&nonce);
+        let ciphertext = XChaCha20Poly1305::new(file_key.as_ref().into())
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: keys.secret_bytes().unwrap().as_ref(),
+                    aad: &data,
+                },
+            )
+            .unwrap();
+        data.extend_from_slice(&ciphertext);
+        fs::write(&path, &data).unwrap();
+
+        let mut store = FileKeyStore::open(&path, b"password").unwrap();
+        assert_eq!(
+            store.public_keys().unwrap().verifying_key,
+            keys.verifying_key
+        );
+        assert_eq!(store.params(), PasswordParams::INTERACTIVE);
+        assert!(matches!(
+            FileKeyStore::open(&path, b"wrong"),
+            Err(CryptoError::DecryptionError(_))
+        ));
+
+        // The next rotation writes the current format
+        let rotated = store.generate(&mut rand::thread_rng()).unwrap();
+        let data = fs::read(&path).unwrap();
+        assert_eq!((data[8], data.len()), (KEY_FILE_VERSION, KEY_FILE_SIZE));
+        assert_eq!(
+            FileKeyStore::open(&path, b"password")
+                .unwrap()
+                .public_keys()
+                .unwrap(),
+            rotated
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_file_missing() {
+        assert!(matches!(
+            FileKeyStore::open(temp_path("missing"), b"password"),
+            Err(CryptoError::KeyStoreError(_))
+        ));
+    }
+}
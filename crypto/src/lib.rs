@@ -1,3 +1,4 @@
+#![cfg_attr(not(feature = "std"), no_std)]
 #![forbid(unsafe_code)]
 #![deny(unused_must_use)]
 #![warn(missing_docs)]
@@ -6,17 +7,62 @@
 //!
 //! Triple-layer encryption system with post-quantum cryptography support.
 //! **MAXIMAL SICHERHEIT** - The most secure messenger on Earth!
-
+//!
+//! ## Features
+//! - `std` (default): system RNG, local clock and the std-only helpers.
+//!   Without it the crate builds on `no_std + alloc`; use the `_with_rng`
+//!   constructors and `encrypt_with` with a caller-supplied [`clock::Clock`].
+//! - `pq`: CRYSTALS-Kyber1024 key encapsulation (`post_quantum` module) and
+//!   hybrid Ed25519 + ML-DSA-65 identity signatures (`hybrid_signature` module).
+//! - `pkcs11`: identity keys on a PKCS#11 token or HSM (`pkcs11` module).
+//! - `parallel`: `encrypt_batch`/`decrypt_batch` on the rayon thread pool.
+
+extern crate alloc;
+
+use alloc::{string::String, vec::Vec};
+
+pub mod attachment;
+pub mod clock;
+pub mod device;
+pub mod encoding;
 pub mod encryption;
+pub mod envelope;
+pub mod expiring;
+pub mod franking;
+pub mod hpke;
+#[cfg(feature = "pq")]
+pub mod hybrid_signature;
 pub mod key_exchange;
+pub mod keystore;
+pub mod noise;
+pub mod padding;
+pub mod password;
+#[cfg(feature = "pkcs11")]
+pub mod pkcs11;
+#[cfg(feature = "pq")]
+pub mod post_quantum;
+pub mod psi;
+pub mod registry;
+pub mod stream;
+pub mod transparency;
 pub mod utils;
-// pub mod post_quantum;  // TODO: Add after Kyber support
 
-pub use encryption::{TripleLayerEncryption, EncryptedMessage};
-pub use key_exchange::{KeyPair, EphemeralDH};
+pub use clock::Clock;
+pub use encryption::{EncryptedMessage, TripleLayerEncryption};
+pub use envelope::SignedEnvelope;
+#[cfg(feature = "pq")]
+pub use hybrid_signature::{IdentityKey, IdentitySignature};
+pub use key_exchange::{EphemeralDH, KeyPair};
+pub use keystore::{KeyStore, PublicKeys};
 
 /// Current protocol version
-pub const PROTOCOL_VERSION: u8 = 1;
+///
+/// Version 2: plaintexts are padded (`padding` module) inside layer 1 and the
+/// version byte is authenticated as layer-1 associated data.
+///
+/// Version 3: messages carry a key commitment (`EncryptedMessage::key_commitment`)
+/// that is checked before any layer is decrypted.
+pub const PROTOCOL_VERSION: u8 = 3;
 
 /// Result type for cryptographic operations
 pub type CryptoResult<T> = Result<T, CryptoError>;
@@ -24,35 +70,81 @@ pub type CryptoResult<T> = Result<T, CryptoError>;
 /// Cryptographic error types
 #[derive(Debug, thiserror::Error)]
 pub enum CryptoError {
+    /// Encryption of a layer or message failed
     #[error("Encryption failed: {0}")]
     EncryptionError(String),
 
+    /// Decryption of a layer or message failed
     #[error("Decryption failed: {0}")]
     DecryptionError(String),
 
+    /// HKDF or password-based key derivation failed
     #[error("Key derivation failed: {0}")]
     KeyDerivationError(String),
 
+    /// Key material has the wrong length or format
     #[error("Invalid key: {0}")]
     InvalidKey(String),
 
+    /// Nonce has the wrong length or format
     #[error("Invalid nonce: {0}")]
     InvalidNonce(String),
 
+    /// HMAC tag did not match
     #[error("HMAC verification failed")]
     HmacVerificationFailed,
 
+    /// Ed25519 signature did not verify
     #[error("Signature verification failed")]
     SignatureVerificationFailed,
 
+    /// Signatures at these batch indices did not verify
+    #[error("Batch signature verification failed at indices {0:?}")]
+    BatchVerificationFailed(Vec<usize>),
+
+    /// The random number generator failed
     #[error("Random generation failed")]
     RandomGenerationFailed,
 
+    /// Encoding or decoding failed
     #[error("Serialization error: {0}")]
     SerializationError(String),
 
+    /// Key agreement or encapsulation failed
     #[error("Key agreement failed: {0}")]
     KeyAgreementFailed(String),
+
+    /// Downloaded data does not match its expected digest
+    #[error("Digest mismatch")]
+    DigestMismatch,
+
+    /// Message is past its expiry or its key was destroyed
+    #[error("Message expired")]
+    MessageExpired,
+
+    /// Envelope is addressed to a different recipient
+    #[error("Recipient mismatch")]
+    RecipientMismatch,
+
+    /// Merkle proof or tree head did not verify
+    #[error("Invalid proof: {0}")]
+    InvalidProof(String),
+
+    /// Device certificate is malformed, mis-signed or outside its validity period
+    #[error("Invalid certificate: {0}")]
+    InvalidCertificate(String),
+
+    /// Device certificate has been revoked
+    #[error("Certificate revoked")]
+    CertificateRevoked,
+
+    /// Key store backend (key file or PKCS#11 token) failed
+    #[error("Key store error: {0}")]
+    KeyStoreError(String),
+
+    /// Key material was destroyed by `registry::panic_wipe()`
+    #[error("Secrets wiped")]
+    Wiped,
 }
 
 #[cfg(test)]
@@ -61,6 +153,6 @@ mod tests {
 
     #[test]
     fn test_protocol_version() {
-        assert_eq!(PROTOCOL_VERSION, 1);
+        assert_eq!(PROTOCOL_VERSION, 3);
     }
 }
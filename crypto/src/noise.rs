@@ -0,0 +1,829 @@
+//! Noise Protocol Handshakes
+//!
+//! Noise_XX and Noise_IK over 25519, ChaChaPoly and SHA256 (Noise revision
+//! 34) for mutually authenticated, forward-secret channels over raw
+//! transports. XX sends both static keys encrypted and suits first contact;
+//! IK saves a round trip when the initiator already knows the responder's
+//! static key, e.g. from the DHT.
+//!
+//! The prologue binds context such as protocol versions into the handshake
+//! hash: both sides must pass the same bytes or the handshake fails. Static
+//! keys are any `KeyStore`, so the X25519 identity key can stay on a token.
+//! After the last handshake message `into_transport` splits into one
+//! `CipherState` per direction.
+
+use crate::keystore::KeyStore;
+use crate::registry::Secret;
+use crate::{CryptoError, CryptoResult};
+use alloc::{string::ToString, vec::Vec};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305,
+};
+use hkdf::Hkdf;
+use rand_core::CryptoRngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+/// Maximum size of any Noise message, handshake or transport
+pub const MAX_MESSAGE_LEN: usize = 65535;
+
+/// X25519 public key size
+pub const DH_LEN: usize = 32;
+
+/// SHA-256 output size
+pub const HASH_LEN: usize = 32;
+
+/// ChaChaPoly tag size
+pub const TAG_LEN: usize = 16;
+
+/// ChaChaPoly key size
+const KEY_LEN: usize = 32;
+
+/// Handshake pattern
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HandshakePattern {
+    /// `-> e`, `<- e, ee, s, es`, `-> s, se`
+    XX,
+
+    /// `<- s` known in advance; `-> e, es, s, ss`, `<- e, ee, se`
+    IK,
+}
+
+/// Handshake message token
+#[derive(Debug, Clone, Copy)]
+enum Token {
+    E,
+    S,
+    Ee,
+    Es,
+    Se,
+    Ss,
+}
+
+impl HandshakePattern {
+    /// Full Noise protocol name, hashed into the initial state
+    pub fn protocol_name(&self) -> &'static str {
+        match self {
+            HandshakePattern::XX => "Noise_XX_25519_ChaChaPoly_SHA256",
+            HandshakePattern::IK => "Noise_IK_25519_ChaChaPoly_SHA256",
+        }
+    }
+
+    /// Token lists of the handshake messages, initiator first
+    fn messages(&self) -> &'static [&'static [Token]] {
+        use Token::*;
+        match self {
+            HandshakePattern::XX => &[&[E], &[E, Ee, S, Es], &[S, Se]],
+            HandshakePattern::IK => &[&[E, Es, S, Ss], &[E, Ee, Se]],
+        }
+    }
+}
+
+/// Key and nonce for one direction (`CipherState`)
+///
+/// The key is registered for panic wipe.
+#[derive(Debug)]
+pub struct CipherState {
+    key: Option<Secret<[u8; KEY_LEN]>>,
+    nonce: u64,
+}
+
+/// Chaining key, handshake hash and handshake cipher (`SymmetricState`)
+struct SymmetricState {
+    ck: Zeroizing<[u8; HASH_LEN]>,
+    h: [u8; HASH_LEN],
+    cipher: CipherState,
+}
+
+/// Handshake in progress (`HandshakeState`)
+///
+/// Drop the state after any error; the peer has to start over.
+pub struct HandshakeState<'a, K: KeyStore + ?Sized = dyn KeyStore> {
+    pattern: HandshakePattern,
+    initiator: bool,
+    symmetric: SymmetricState,
+    s: &'a K,
+    e: StaticSecret,
+    re: Option<[u8; DH_LEN]>,
+    rs: Option<[u8; DH_LEN]>,
+    message: usize,
+}
+
+/// Established channel after the handshake
+#[derive(Debug)]
+pub struct TransportState {
+    send: CipherState,
+    receive: CipherState,
+    handshake_hash: [u8; HASH_LEN],
+    remote_static: [u8; DH_LEN],
+}
+
+impl CipherState {
+    fn empty() -> Self {
+        CipherState {
+            key: None,
+            nonce: 0,
+        }
+    }
+
+    fn new(key: [u8; KEY_LEN]) -> Self {
+        CipherState {
+            key: Some(Secret::new(key)),
+            nonce: 0,
+        }
+    }
+
+    /// Whether a key is set; without one, messages pass through in the clear
+    pub fn has_key(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Nonce of the next message
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    /// Encrypt the next message (`EncryptWithAd`)
+    ///
+    /// # Errors
+    /// `EncryptionError` once the nonce reaches 2^64 - 1, `Wiped` after panic wipe
+    pub fn encrypt_with_ad(&mut self, ad: &[u8], plaintext: &[u8]) -> CryptoResult<Vec<u8>> {
+        let Some(key) = &self.key else {
+            return Ok(plaintext.to_vec());
+        };
+        if self.nonce == u64::MAX {
+            return Err(CryptoError::EncryptionError(
+                "Noise nonce exhausted".to_string(),
+            ));
+        }
+
+        let ciphertext = key.with(|key| {
+            ChaCha20Poly1305::new(key.into())
+                .encrypt(
+                    &nonce_bytes(self.nonce).into(),
+                    Payload {
+                        msg: plaintext,
+                        aad: ad,
+                    },
+                )
+                .map_err(|_| CryptoError::EncryptionError("Noise encryption failed".to_string()))
+        })?;
+        self.nonce += 1;
+        Ok(ciphertext)
+    }
+
+    /// Decrypt the next message (`DecryptWithAd`)
+    ///
+    /// A message that does not authenticate leaves the nonce unchanged.
+    ///
+    /// # Errors
+    /// `DecryptionError` if the message does not authenticate or the nonce is
+    /// exhausted, `Wiped` after panic wipe
+    pub fn decrypt_with_ad(&mut self, ad: &[u8], ciphertext: &[u8]) -> CryptoResult<Vec<u8>> {
+        let Some(key) = &self.key else {
+            return Ok(ciphertext.to_vec());
+        };
+        if self.nonce == u64::MAX {
+            return Err(CryptoError::DecryptionError(
+                "Noise nonce exhausted".to_string(),
+            ));
+        }
+
+        let plaintext = key.with(|key| {
+            ChaCha20Poly1305::new(key.into())
+                .decrypt(
+                    &nonce_bytes(self.nonce).into(),
+                    Payload {
+                        msg: ciphertext,
+                        aad: ad,
+                    },
+                )
+                .map_err(|_| CryptoError::DecryptionError("Noise decryption failed".to_string()))
+        })?;
+        self.nonce += 1;
+        Ok(plaintext)
+    }
+
+    /// Replace the key with one derived from it (`Rekey`)
+    ///
+    /// The nonce is kept. Both sides must rekey at the same message.
+    ///
+    /// # Errors
+    /// `InvalidKey` without a key, `Wiped` after panic wipe
+    pub fn rekey(&mut self) -> CryptoResult<()> {
+        let key = self
+            .key
+            .as_ref()
+            .ok_or_else(|| CryptoError::InvalidKey("Noise cipher has no key".to_string()))?;
+
+        let new_key = key.with(|key| {
+            let zeros = Zeroizing::new([0u8; KEY_LEN]);
+            let ciphertext = Zeroizing::new(
+                ChaCha20Poly1305::new(key.into())
+                    .encrypt(&nonce_bytes(u64::MAX).into(), zeros.as_ref())
+                    .map_err(|_| CryptoError::EncryptionError("Noise rekey failed".to_string()))?,
+            );
+            let mut new_key = [0u8; KEY_LEN];
+            new_key.copy_from_slice(&ciphertext[..KEY_LEN]);
+            Ok(new_key)
+        })?;
+        self.key = Some(Secret::new(new_key));
+        Ok(())
+    }
+}
+
+impl SymmetricState {
+    /// `InitializeSymmetric(protocol_name)`
+    fn new(protocol_name: &str) -> Self {
+        let name = protocol_name.as_bytes();
+        let mut h = [0u8; HASH_LEN];
+        if name.len() <= HASH_LEN {
+            h[..name.len()].copy_from_slice(name);
+        } else {
+            h.copy_from_slice(&Sha256::digest(name));
+        }
+
+        SymmetricState {
+            ck: Zeroizing::new(h),
+            h,
+            cipher: CipherState::empty(),
+        }
+    }
+
+    /// `MixKey(input_key_material)`
+    fn mix_key(&mut self, ikm: &[u8]) -> CryptoResult<()> {
+        let output = hkdf(self.ck.as_ref(), ikm)?;
+        self.ck.copy_from_slice(&output[..HASH_LEN]);
+
+        let mut key = [0u8; KEY_LEN];
+        key.copy_from_slice(&output[HASH_LEN..]);
+        self.cipher = CipherState::new(key);
+        Ok(())
+    }
+
+    /// `MixHash(data)`
+    fn mix_hash(&mut self, data: &[u8]) {
+        let mut hasher = Sha256::new();
+        hasher.update(self.h);
+        hasher.update(data);
+        self.h = hasher.finalize().into();
+    }
+
+    /// `EncryptAndHash(plaintext)`
+    fn encrypt_and_hash(&mut self, plaintext: &[u8]) -> CryptoResult<Vec<u8>> {
+        let ciphertext = self.cipher.encrypt_with_ad(&self.h, plaintext)?;
+        self.mix_hash(&ciphertext);
+        Ok(ciphertext)
+    }
+
+    /// `DecryptAndHash(ciphertext)`
+    fn decrypt_and_hash(&mut self, ciphertext: &[u8]) -> CryptoResult<Vec<u8>> {
+        let plaintext = self.cipher.decrypt_with_ad(&self.h, ciphertext)?;
+        self.mix_hash(ciphertext);
+        Ok(plaintext)
+    }
+
+    /// `Split()`: initiator-to-responder and responder-to-initiator ciphers
+    fn split(&self) -> CryptoResult<(CipherState, CipherState)> {
+        let output = hkdf(self.ck.as_ref(), &[])?;
+        let mut k1 = [0u8; KEY_LEN];
+        let mut k2 = [0u8; KEY_LEN];
+        k1.copy_from_slice(&output[..KEY_LEN]);
+        k2.copy_from_slice(&output[KEY_LEN..]);
+        Ok((CipherState::new(k1), CipherState::new(k2)))
+    }
+}
+
+impl<'a, K: KeyStore + ?Sized> HandshakeState<'a, K> {
+    /// Start a handshake as initiator
+    ///
+    /// # Arguments
+    /// * `pattern` - XX or IK
+    /// * `prologue` - Context both sides must agree on, e.g. protocol versions
+    /// * `local` - Our static X25519 key
+    /// * `remote_static` - Responder's static key; required for IK, not allowed for XX
+    #[cfg(feature = "std")]
+    pub fn initiator(
+        pattern: HandshakePattern,
+        prologue: &[u8],
+        local: &'a K,
+        remote_static: Option<&[u8; DH_LEN]>,
+    ) -> CryptoResult<Self> {
+        Self::initiator_with(
+            pattern,
+            prologue,
+            local,
+            remote_static,
+            &mut rand::thread_rng(),
+        )
+    }
+
+    /// Start a handshake as initiator with a caller-supplied RNG
+    ///
+    /// The ephemeral private key is the first 32 bytes from `rng`.
+    pub fn initiator_with(
+        pattern: HandshakePattern,
+        prologue: &[u8],
+        local: &'a K,
+        remote_static: Option<&[u8; DH_LEN]>,
+        rng: &mut impl CryptoRngCore,
+    ) -> CryptoResult<Self> {
+        let mut state = Self::new(pattern, true, prologue, local, rng);
+        match (pattern, remote_static) {
+            (HandshakePattern::IK, Some(remote_static)) => {
+                state.symmetric.mix_hash(remote_static);
+                state.rs = Some(*remote_static);
+            }
+            (HandshakePattern::XX, None) => {}
+            (HandshakePattern::IK, None) => {
+                return Err(CryptoError::InvalidKey(
+                    "IK needs the responder's static key".to_string(),
+                ))
+            }
+            (HandshakePattern::XX, Some(_)) => {
+                return Err(CryptoError::InvalidKey(
+                    "XX learns the responder's static key".to_string(),
+                ))
+            }
+        }
+        Ok(state)
+    }
+
+    /// Start a handshake as responder
+    #[cfg(feature = "std")]
+    pub fn responder(
+        pattern: HandshakePattern,
+        prologue: &[u8],
+        local: &'a K,
+    ) -> CryptoResult<Self> {
+        Self::responder_with(pattern, prologue, local, &mut rand::thread_rng())
+    }
+
+    /// Start a handshake as responder with a caller-supplied RNG
+    pub fn responder_with(
+        pattern: HandshakePattern,
+        prologue: &[u8],
+        local: &'a K,
+        rng: &mut impl CryptoRngCore,
+    ) -> CryptoResult<Self> {
+        let mut state = Self::new(pattern, false, prologue, local, rng);
+        if pattern == HandshakePattern::IK {
+            state.symmetric.mix_hash(&local.public_keys()?.public_key);
+        }
+        Ok(state)
+    }
+
+    fn new(
+        pattern: HandshakePattern,
+        initiator: bool,
+        prologue: &[u8],
+        local: &'a K,
+        rng: &mut impl CryptoRngCore,
+    ) -> Self {
+        let mut symmetric = SymmetricState::new(pattern.protocol_name());
+        symmetric.mix_hash(prologue);
+
+        let mut ephemeral = Zeroizing::new([0u8; 32]);
+        rng.fill_bytes(ephemeral.as_mut());
+
+        HandshakeState {
+            pattern,
+            initiator,
+            symmetric,
+            s: local,
+            e: StaticSecret::from(*ephemeral),
+            re: None,
+            rs: None,
+            message: 0,
+        }
+    }
+
+    /// Whether all handshake messages have been sent and received
+    pub fn is_finished(&self) -> bool {
+        self.message == self.pattern.messages().len()
+    }
+
+    /// Whether the next handshake message is ours to write
+    pub fn is_my_turn(&self) -> bool {
+        !self.is_finished() && self.message.is_multiple_of(2) == self.initiator
+    }
+
+    /// Remote static key, once received (or given, for IK initiators)
+    pub fn remote_static(&self) -> Option<&[u8; DH_LEN]> {
+        self.rs.as_ref()
+    }
+
+    /// Current handshake hash
+    pub fn handshake_hash(&self) -> &[u8; HASH_LEN] {
+        &self.symmetric.h
+    }
+
+    /// Write the next handshake message (`WriteMessage`)
+    ///
+    /// The payload is encrypted once a DH has been mixed in; the first XX
+    /// message sends it in the clear.
+    ///
+    /// # Errors
+    /// `KeyAgreementFailed` out of turn or for a small-order peer key,
+    /// `EncryptionError` if the message would exceed `MAX_MESSAGE_LEN`
+    pub fn write_message(&mut self, payload: &[u8]) -> CryptoResult<Vec<u8>> {
+        if !self.is_my_turn() {
+            return Err(CryptoError::KeyAgreementFailed(
+                "Not our turn to write".to_string(),
+            ));
+        }
+        let tokens = self.pattern.messages()[self.message];
+        if self.message_len(tokens, payload.len()) > MAX_MESSAGE_LEN {
+            return Err(CryptoError::EncryptionError(
+                "Noise message too long".to_string(),
+            ));
+        }
+
+        let mut message = Vec::new();
+        for token in tokens {
+            match token {
+                Token::E => {
+                    let e = *PublicKey::from(&self.e).as_bytes();
+                    message.extend_from_slice(&e);
+                    self.symmetric.mix_hash(&e);
+                }
+                Token::S => {
+                    let s = self.s.public_keys()?.public_key;
+                    message.extend(self.symmetric.encrypt_and_hash(&s)?);
+                }
+                dh => {
+                    let shared = self.dh(*dh)?;
+                    self.symmetric.mix_key(shared.as_ref())?;
+                }
+            }
+        }
+        message.extend(self.symmetric.encrypt_and_hash(payload)?);
+
+        self.message += 1;
+        Ok(message)
+    }
+
+    /// Read the next handshake message and return its payload (`ReadMessage`)
+    ///
+    /// # Errors
+    /// `DecryptionError` for a malformed or forged message (including a
+    /// prologue mismatch), `KeyAgreementFailed` out of turn or for a
+    /// small-order peer key
+    pub fn read_message(&mut self, message: &[u8]) -> CryptoResult<Vec<u8>> {
+        if self.is_finished() || self.is_my_turn() {
+            return Err(CryptoError::KeyAgreementFailed(
+                "Not our turn to read".to_string(),
+            ));
+        }
+        if message.len() > MAX_MESSAGE_LEN {
+            return Err(CryptoError::DecryptionError(
+                "Noise message too long".to_string(),
+            ));
+        }
+
+        let mut rest = message;
+        for token in self.pattern.messages()[self.message] {
+            match token {
+                Token::E => {
+                    let re: [u8; DH_LEN] =
+                        take(&mut rest, DH_LEN)?.try_into().expect("DH_LEN bytes");
+                    self.symmetric.mix_hash(&re);
+                    self.re = Some(re);
+                }
+                Token::S => {
+                    let len = if self.symmetric.cipher.has_key() {
+                        DH_LEN + TAG_LEN
+                    } else {
+                        DH_LEN
+                    };
+                    let rs = self.symmetric.decrypt_and_hash(take(&mut rest, len)?)?;
+                    self.rs = Some(rs.try_into().expect("DH_LEN bytes"));
+                }
+                dh => {
+                    let shared = self.dh(*dh)?;
+                    self.symmetric.mix_key(shared.as_ref())?;
+                }
+            }
+        }
+        let payload = self.symmetric.decrypt_and_hash(rest)?;
+
+        self.message += 1;
+        Ok(payload)
+    }
+
+    /// Finish the handshake and split into transport ciphers
+    ///
+    /// # Errors
+    /// `KeyAgreementFailed` if handshake messages are still outstanding
+    pub fn into_transport(self) -> CryptoResult<TransportState> {
+        if !self.is_finished() {
+            return Err(CryptoError::KeyAgreementFailed(
+                "Noise handshake not finished".to_string(),
+            ));
+        }
+
+        let (initiator_to_responder, responder_to_initiator) = self.symmetric.split()?;
+        let (send, receive) = if self.initiator {
+            (initiator_to_responder, responder_to_initiator)
+        } else {
+            (responder_to_initiator, initiator_to_responder)
+        };
+
+        Ok(TransportState {
+            send,
+            receive,
+            handshake_hash: self.symmetric.h,
+            remote_static: self
+                .rs
+                .expect("both patterns transmit or pre-share the remote static key"),
+        })
+    }
+
+    /// Length of the message `tokens` produce around a payload
+    fn message_len(&self, tokens: &[Token], payload_len: usize) -> usize {
+        let mut has_key = self.symmetric.cipher.has_key();
+        let mut len = payload_len;
+        for token in tokens {
+            match token {
+                Token::E => len += DH_LEN,
+                Token::S => len += DH_LEN + if has_key { TAG_LEN } else { 0 },
+                _ => has_key = true,
+            }
+        }
+        len + if has_key { TAG_LEN } else { 0 }
+    }
+
+    /// DH for a token, from our side of the handshake
+    fn dh(&self, token: Token) -> CryptoResult<Zeroizing<[u8; 32]>> {
+        let missing = || CryptoError::KeyAgreementFailed("Missing remote key".to_string());
+        let ephemeral = |peer: Option<[u8; DH_LEN]>| -> CryptoResult<[u8; 32]> {
+            let peer = PublicKey::from(peer.ok_or_else(missing)?);
+            Ok(*self.e.diffie_hellman(&peer).as_bytes())
+        };
+        let stat =
+            |peer: Option<[u8; DH_LEN]>| self.s.compute_shared_secret(&peer.ok_or_else(missing)?);
+
+        let shared = match (token, self.initiator) {
+            (Token::Ee, _) => ephemeral(self.re)?,
+            (Token::Es, true) | (Token::Se, false) => ephemeral(self.rs)?,
+            (Token::Es, false) | (Token::Se, true) => stat(self.re)?,
+            (Token::Ss, _) => stat(self.rs)?,
+            (Token::E | Token::S, _) => unreachable!("not a DH token"),
+        };
+        checked(shared)
+    }
+}
+
+impl TransportState {
+    /// Encrypt a transport message
+    ///
+    /// # Errors
+    /// `EncryptionError` if the message would exceed `MAX_MESSAGE_LEN` or the
+    /// nonce is exhausted
+    pub fn encrypt(&mut self, payload: &[u8]) -> CryptoResult<Vec<u8>> {
+        if payload.len() + TAG_LEN > MAX_MESSAGE_LEN {
+            return Err(CryptoError::EncryptionError(
+                "Noise message too long".to_string(),
+            ));
+        }
+        self.send.encrypt_with_ad(&[], payload)
+    }
+
+    /// Decrypt a transport message; messages must arrive in order
+    ///
+    /// # Errors
+    /// `DecryptionError` if the message is too long or does not authenticate
+    pub fn decrypt(&mut self, message: &[u8]) -> CryptoResult<Vec<u8>> {
+        if message.len() > MAX_MESSAGE_LEN {
+            return Err(CryptoError::DecryptionError(
+                "Noise message too long".to_string(),
+            ));
+        }
+        self.receive.decrypt_with_ad(&[], message)
+    }
+
+    /// Rekey our sending direction; the peer calls `rekey_incoming` at the same point
+    pub fn rekey_outgoing(&mut self) -> CryptoResult<()> {
+        self.send.rekey()
+    }
+
+    /// Rekey the receiving direction
+    pub fn rekey_incoming(&mut self) -> CryptoResult<()> {
+        self.receive.rekey()
+    }
+
+    /// Final handshake hash, identical on both sides (channel binding)
+    pub fn handshake_hash(&self) -> &[u8; HASH_LEN] {
+        &self.handshake_hash
+    }
+
+    /// Peer's authenticated static X25519 key
+    pub fn remote_static(&self) -> &[u8; DH_LEN] {
+        &self.remote_static
+    }
+
+    /// Sending and receiving ciphers, e.g. for separate reader and writer tasks
+    pub fn into_cipher_states(self) -> (CipherState, CipherState) {
+        (self.send, self.receive)
+    }
+}
+
+/// ChaChaPoly nonce: 32 zero bits, then the counter little-endian
+fn nonce_bytes(nonce: u64) -> [u8; 12] {
+    let mut bytes = [0u8; 12];
+    bytes[4..].copy_from_slice(&nonce.to_le_bytes());
+    bytes
+}
+
+/// Noise `HKDF(chaining_key, input_key_material, 2)`
+///
+/// HKDF-SHA256 with the chaining key as salt and empty info yields exactly
+/// `output1 || output2`.
+fn hkdf(chaining_key: &[u8], ikm: &[u8]) -> CryptoResult<Zeroizing<[u8; 2 * HASH_LEN]>> {
+    let mut output = Zeroizing::new([0u8; 2 * HASH_LEN]);
+    Hkdf::<Sha256>::new(Some(chaining_key), ikm)
+        .expand(&[], output.as_mut())
+        .map_err(|e| CryptoError::KeyDerivationError(e.to_string()))?;
+    Ok(output)
+}
+
+/// Split `len` bytes off the front of a handshake message
+fn take<'m>(rest: &mut &'m [u8], len: usize) -> CryptoResult<&'m [u8]> {
+    if rest.len() < len {
+        return Err(CryptoError::DecryptionError(
+            "Noise message too short".to_string(),
+        ));
+    }
+    let (head, tail) = rest.split_at(len);
+    *rest = tail;
+    Ok(head)
+}
+
+/// Reject the all-zero X25519 output (small-order peer key)
+fn checked(dh: [u8; 32]) -> CryptoResult<Zeroizing<[u8; 32]>> {
+    let dh = Zeroizing::new(dh);
+    if bool::from(dh.ct_eq(&[0u8; 32])) {
+        return Err(CryptoError::KeyAgreementFailed(
+            "Small-order public key".to_string(),
+        ));
+    }
+    Ok(dh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyPair;
+
+    const PROLOGUE: &[u8] = b"chakchat test v1";
+
+    /// Run a handshake to completion, checking each payload arrives
+    fn handshake<'a>(
+        mut initiator: HandshakeState<'a>,
+        mut responder: HandshakeState<'a>,
+    ) -> CryptoResult<(TransportState, TransportState)> {
+        let mut i = 0u8;
+        while !initiator.is_finished() {
+            let (writer, reader) = if initiator.is_my_turn() {
+                (&mut initiator, &mut responder)
+            } else {
+                (&mut responder, &mut initiator)
+            };
+            let message = writer.write_message(&[i; 5])?;
+            assert_eq!(reader.read_message(&message)?, [i; 5]);
+            i += 1;
+        }
+        assert!(responder.is_finished());
+        Ok((initiator.into_transport()?, responder.into_transport()?))
+    }
+
+    #[test]
+    fn test_xx_and_ik_round_trip() {
+        let alice = KeyPair::generate().unwrap();
+        let bob = KeyPair::generate().unwrap();
+
+        for (pattern, remote) in [
+            (HandshakePattern::XX, None),
+            (HandshakePattern::IK, Some(&bob.public_key)),
+        ] {
+            let (mut a, mut b) = handshake(
+                HandshakeState::initiator(pattern, PROLOGUE, &alice as &dyn KeyStore, remote)
+                    .unwrap(),
+                HandshakeState::responder(pattern, PROLOGUE, &bob as &dyn KeyStore).unwrap(),
+            )
+            .unwrap();
+
+            assert_eq!(a.remote_static(), &bob.public_key);
+            assert_eq!(b.remote_static(), &alice.public_key);
+            assert_eq!(a.handshake_hash(), b.handshake_hash());
+
+            let ciphertext = a.encrypt(b"hello bob").unwrap();
+            assert_eq!(ciphertext.len(), 9 + TAG_LEN);
+            assert_eq!(b.decrypt(&ciphertext).unwrap(), b"hello bob");
+            let reply = b.encrypt(b"hello alice").unwrap();
+            assert_eq!(a.decrypt(&reply).unwrap(), b"hello alice");
+        }
+    }
+
+    #[test]
+    fn test_handshake_failures() {
+        let alice = KeyPair::generate().unwrap();
+        let bob = KeyPair::generate().unwrap();
+        let eve = KeyPair::generate().unwrap();
+
+        // Different prologues, e.g. mismatched protocol versions
+        let result = handshake(
+            HandshakeState::initiator(HandshakePattern::XX, b"v1", &alice as &dyn KeyStore, None)
+                .unwrap(),
+            HandshakeState::responder(HandshakePattern::XX, b"v2", &bob as &dyn KeyStore).unwrap(),
+        );
+        assert!(matches!(result, Err(CryptoError::DecryptionError(_))));
+
+        // IK to the wrong responder key
+        let result = handshake(
+            HandshakeState::initiator(
+                HandshakePattern::IK,
+                PROLOGUE,
+                &alice as &dyn KeyStore,
+                Some(&eve.public_key),
+            )
+            .unwrap(),
+            HandshakeState::responder(HandshakePattern::IK, PROLOGUE, &bob as &dyn KeyStore)
+                .unwrap(),
+        );
+        assert!(matches!(result, Err(CryptoError::DecryptionError(_))));
+
+        assert!(HandshakeState::initiator(HandshakePattern::IK, PROLOGUE, &alice, None).is_err());
+        assert!(HandshakeState::initiator(
+            HandshakePattern::XX,
+            PROLOGUE,
+            &alice,
+            Some(&bob.public_key)
+        )
+        .is_err());
+
+        // Out of turn, and splitting early
+        let mut responder =
+            HandshakeState::responder(HandshakePattern::XX, PROLOGUE, &bob).unwrap();
+        assert!(responder.write_message(b"").is_err());
+        let mut initiator =
+            HandshakeState::initiator(HandshakePattern::XX, PROLOGUE, &alice, None).unwrap();
+        let first = initiator.write_message(b"").unwrap();
+        assert!(initiator.write_message(b"").is_err());
+        assert!(initiator.into_transport().is_err());
+
+        // Truncated and oversized messages
+        assert!(responder.read_message(&first[..DH_LEN - 1]).is_err());
+        assert!(responder.read_message(&[0u8; MAX_MESSAGE_LEN + 1]).is_err());
+        let mut initiator =
+            HandshakeState::initiator(HandshakePattern::XX, PROLOGUE, &alice, None).unwrap();
+        assert!(initiator.write_message(&[0u8; MAX_MESSAGE_LEN]).is_err());
+    }
+
+    #[test]
+    fn test_transport_rekey_and_nonces() {
+        let alice = KeyPair::generate().unwrap();
+        let bob = KeyPair::generate().unwrap();
+        let (mut a, mut b) = handshake(
+            HandshakeState::initiator(
+                HandshakePattern::XX,
+                PROLOGUE,
+                &alice as &dyn KeyStore,
+                None,
+            )
+            .unwrap(),
+            HandshakeState::responder(HandshakePattern::XX, PROLOGUE, &bob as &dyn KeyStore)
+                .unwrap(),
+        )
+        .unwrap();
+
+        let before = a.encrypt(b"same").unwrap();
+        assert_eq!(b.decrypt(&before).unwrap(), b"same");
+
+        // A forged message does not consume a nonce
+        let mut forged = a.encrypt(b"next").unwrap();
+        forged[0] ^= 1;
+        assert!(b.decrypt(&forged).is_err());
+        forged[0] ^= 1;
+        assert_eq!(b.decrypt(&forged).unwrap(), b"next");
+
+        a.rekey_outgoing().unwrap();
+        let after = a.encrypt(b"rekeyed").unwrap();
+        assert!(b.decrypt(&after).is_err());
+        b.rekey_incoming().unwrap();
+        assert_eq!(b.decrypt(&after).unwrap(), b"rekeyed");
+
+        let (mut send, _) = a.into_cipher_states();
+        assert_eq!(send.nonce(), 3);
+        send.nonce = u64::MAX - 1;
+        assert!(send.encrypt_with_ad(&[], b"last").is_ok());
+        assert!(matches!(
+            send.encrypt_with_ad(&[], b"one too many"),
+            Err(CryptoError::EncryptionError(_))
+        ));
+    }
+}
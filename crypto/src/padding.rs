@@ -0,0 +1,302 @@
+//! Length-Hiding Padding
+//!
+//! Plaintexts are padded before the innermost layer so the ciphertext length
+//! only reveals a size class, not the exact message length.
+//!
+//! Padding is ISO/IEC 7816-4 style: a single `0x80` marker followed by zero
+//! bytes. It is unambiguous for any plaintext, so the receiver does not need
+//! to know which policy the sender used. Removal runs in constant time with
+//! respect to the padded buffer contents.
+
+use crate::encryption::MAX_MESSAGE_SIZE;
+use crate::{CryptoError, CryptoResult};
+use alloc::{string::ToString, vec::Vec};
+use rand_core::CryptoRngCore;
+use subtle::{Choice, ConstantTimeEq};
+
+/// Padding marker byte
+pub const PADDING_MARKER: u8 = 0x80;
+
+/// How far plaintexts are padded before encryption
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PaddingPolicy {
+    /// Marker byte only (length is still revealed)
+    None,
+
+    /// Padmé: at most ~12% overhead, leaks O(log log n) bits of length
+    #[default]
+    Padme,
+
+    /// Round up to the next power of two, at least `min_bucket` bytes
+    PowerOfTwo {
+        /// Smallest bucket size in bytes
+        min_bucket: usize,
+    },
+
+    /// Add a uniformly random number of bytes in `0..=max_extra`
+    ///
+    /// Padded lengths above `MAX_MESSAGE_SIZE` are rejected, so `max_extra`
+    /// is effectively capped there.
+    RandomRange {
+        /// Largest number of extra bytes
+        max_extra: usize,
+    },
+}
+
+impl PaddingPolicy {
+    /// Padded length for a plaintext of `len` bytes
+    ///
+    /// Always at least `len + 1` to leave room for the marker.
+    ///
+    /// # Errors
+    /// `EncryptionError` if the padded length exceeds `MAX_MESSAGE_SIZE`
+    pub fn padded_len(&self, len: usize, rng: &mut impl CryptoRngCore) -> CryptoResult<usize> {
+        let padded_len = match *self {
+            PaddingPolicy::RandomRange { max_extra } => len
+                .checked_add(1)
+                .and_then(|min| min.checked_add(uniform(rng, max_extra))),
+            _ => self.max_padded_len(len),
+        };
+        padded_len
+            .filter(|&padded_len| padded_len <= MAX_MESSAGE_SIZE)
+            .ok_or_else(|| {
+                CryptoError::EncryptionError("Padded message exceeds maximum size".to_string())
+            })
+    }
+
+    /// Largest length `padded_len` can return for `len` bytes
+    ///
+    /// `None` if that length does not fit in `usize`.
+    pub fn max_padded_len(&self, len: usize) -> Option<usize> {
+        let min = len.checked_add(1)?;
+        match *self {
+            PaddingPolicy::None => Some(min),
+            PaddingPolicy::Padme => padme(min),
+            PaddingPolicy::PowerOfTwo { min_bucket } => {
+                min.max(min_bucket).checked_next_power_of_two()
+            }
+            PaddingPolicy::RandomRange { max_extra } => min.checked_add(max_extra),
+        }
+    }
+}
+
+/// Padmé length (Nikitin et al., "Reducing Metadata Leakage from Encrypted Files")
+fn padme(len: usize) -> Option<usize> {
+    if len < 2 {
+        return Some(len);
+    }
+    let e = usize::BITS - 1 - len.leading_zeros();
+    let s = u32::BITS - e.leading_zeros();
+    let last_bits = e - s;
+    let mask = (1usize << last_bits) - 1;
+    len.checked_add(mask).map(|padded| padded & !mask)
+}
+
+/// Uniform integer in `0..=max` without modulo bias
+fn uniform(rng: &mut impl CryptoRngCore, max: usize) -> usize {
+    if max == 0 {
+        return 0;
+    }
+    // `max` covers the whole u64 range: every value is already uniform
+    let Some(range) = (max as u64).checked_add(1) else {
+        return rng.next_u64() as usize;
+    };
+    let zone = u64::MAX - (u64::MAX % range);
+    loop {
+        let v = rng.next_u64();
+        if v < zone {
+            return (v % range) as usize;
+        }
+    }
+}
+
+/// Pad plaintext according to policy
+///
+/// # Errors
+/// `EncryptionError` if the padded length exceeds `MAX_MESSAGE_SIZE`
+pub fn pad(
+    plaintext: &[u8],
+    policy: PaddingPolicy,
+    rng: &mut impl CryptoRngCore,
+) -> CryptoResult<Vec<u8>> {
+    let padded_len = policy.padded_len(plaintext.len(), rng)?;
+    let mut padded = Vec::with_capacity(padded_len);
+    padded.extend_from_slice(plaintext);
+    pad_in_place(&mut padded, padded_len);
+    Ok(padded)
+}
+
+/// Pad `buffer` in place to `padded_len`, from `PaddingPolicy::padded_len`
+pub fn pad_in_place(buffer: &mut Vec<u8>, padded_len: usize) {
+    debug_assert!(padded_len > buffer.len(), "no room for the marker");
+    buffer.push(PADDING_MARKER);
+    buffer.resize(padded_len, 0);
+}
+
+/// All ones if `word` is non-zero, else zero, without branching
+fn nonzero_mask(word: u64) -> u64 {
+    ((word | word.wrapping_neg()) >> 63).wrapping_neg()
+}
+
+/// Remove padding in place
+///
+/// Scans the whole buffer regardless of where the marker is, and fails
+/// if the last non-zero byte is not the marker. The scan runs over
+/// 8-byte words with branch-free masks; the highest non-zero byte of a
+/// word is located with `leading_zeros`, a single constant-time
+/// instruction on the supported targets.
+pub fn unpad(mut padded: Vec<u8>) -> CryptoResult<Vec<u8>> {
+    let len = padded.len();
+    let mut found = 0u64;
+    let mut marker_pos = 0u64;
+    let mut marker = 0u64;
+
+    for (i, chunk) in padded.rchunks(8).enumerate() {
+        // The front chunk may be short; zero-filling its high bytes
+        // leaves the last non-zero byte unchanged
+        let mut bytes = [0u8; 8];
+        bytes[..chunk.len()].copy_from_slice(chunk);
+        let word = u64::from_le_bytes(bytes);
+        let start = len.saturating_sub((i + 1) * 8) as u64;
+
+        let shift = 56 - ((word | 1).leading_zeros() as u64 & !7);
+        let take = core::hint::black_box(nonzero_mask(word) & !found);
+
+        marker_pos = (marker_pos & !take) | ((start + shift / 8) & take);
+        marker = (marker & !take) | ((word >> shift) & 0xff & take);
+        found |= take;
+    }
+
+    let valid = Choice::from((found & 1) as u8) & marker.ct_eq(&u64::from(PADDING_MARKER));
+    if !bool::from(valid) {
+        return Err(CryptoError::DecryptionError("Invalid padding".to_string()));
+    }
+
+    padded.truncate(marker_pos as usize);
+    Ok(padded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_padme_lengths() {
+        // Reference values from the Padmé paper
+        assert_eq!(padme(1), Some(1));
+        assert_eq!(padme(9), Some(10));
+        assert_eq!(padme(100), Some(104));
+        assert_eq!(padme(1000), Some(1024));
+        assert_eq!(padme(1025), Some(1088));
+        for len in 1..5000 {
+            let p = padme(len).unwrap();
+            assert!(p >= len);
+            assert!(p - len <= len / 8 + 1);
+        }
+    }
+
+    #[test]
+    fn test_pad_unpad_roundtrip_all_policies() {
+        let policies = [
+            PaddingPolicy::None,
+            PaddingPolicy::Padme,
+            PaddingPolicy::PowerOfTwo { min_bucket: 256 },
+            PaddingPolicy::RandomRange { max_extra: 64 },
+        ];
+        for policy in policies {
+            for len in [0, 1, 2, 127, 128, 255, 1000] {
+                let plaintext = vec![0xAAu8; len];
+                let padded = pad(&plaintext, policy, &mut rand::thread_rng()).unwrap();
+                assert!(padded.len() > len);
+                assert_eq!(unpad(padded).unwrap(), plaintext);
+            }
+        }
+    }
+
+    #[test]
+    fn test_plaintext_ending_in_marker_or_zero() {
+        for plaintext in [vec![0x80u8, 0x80], vec![1, 0, 0], vec![0u8; 5]] {
+            let padded = pad(&plaintext, PaddingPolicy::Padme, &mut rand::thread_rng()).unwrap();
+            assert_eq!(unpad(padded).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn test_power_of_two_buckets_hide_length() {
+        let short = pad(
+            b"ok",
+            PaddingPolicy::PowerOfTwo { min_bucket: 256 },
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+        let long = pad(
+            &[b'x'; 200],
+            PaddingPolicy::PowerOfTwo { min_bucket: 256 },
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+        assert_eq!(short.len(), 256);
+        assert_eq!(long.len(), 256);
+    }
+
+    #[test]
+    fn test_malformed_padding_rejected() {
+        assert!(unpad(vec![]).is_err());
+        assert!(unpad(vec![0, 0, 0]).is_err());
+        assert!(unpad(vec![1, 2, 3]).is_err());
+        assert!(unpad(vec![1, 0x80, 0x01, 0]).is_err());
+        assert_eq!(unpad(vec![0x80]).unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_unpad_across_word_boundaries() {
+        use rand::Rng;
+
+        let mut rng = rand::thread_rng();
+        for len in 0..40 {
+            for _ in 0..50 {
+                let mut buffer: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+                // Zero a random-length tail so the last non-zero byte moves around
+                let tail = rng.gen_range(0..=len);
+                buffer[len - tail..].fill(0);
+
+                let expected = match buffer.iter().rposition(|&b| b != 0) {
+                    Some(pos) if buffer[pos] == PADDING_MARKER => Some(buffer[..pos].to_vec()),
+                    _ => None,
+                };
+                assert_eq!(unpad(buffer).ok(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_padded_len_overflow() {
+        let huge = PaddingPolicy::RandomRange {
+            max_extra: usize::MAX,
+        };
+        assert_eq!(huge.max_padded_len(1), None);
+        assert_eq!(PaddingPolicy::Padme.max_padded_len(usize::MAX - 1), None);
+        assert_eq!(PaddingPolicy::None.max_padded_len(usize::MAX), None);
+        assert_eq!(
+            PaddingPolicy::PowerOfTwo {
+                min_bucket: usize::MAX
+            }
+            .max_padded_len(1),
+            None
+        );
+        assert!(matches!(
+            PaddingPolicy::None.padded_len(usize::MAX, &mut rand::thread_rng()),
+            Err(CryptoError::EncryptionError(_))
+        ));
+        for _ in 0..16 {
+            assert!(matches!(
+                huge.padded_len(2, &mut rand::thread_rng()),
+                Err(CryptoError::EncryptionError(_))
+            ));
+        }
+        assert!(matches!(
+            pad(b"hi", huge, &mut rand::thread_rng()),
+            Err(CryptoError::EncryptionError(_))
+        ));
+    }
+}
@@ -0,0 +1,339 @@
+//! Password-Based Encryption
+//!
+//! Seals arbitrary blobs (settings exports, notes, backups) under a
+//! password. The output is self-describing, so the KDF cost can be raised
+//! later without breaking old blobs:
+//!
+//! `magic (8) || version (1) || kdf (1) || log_n (1) || r (4) || p (4) || salt (32) || nonce (24) || XChaCha20-Poly1305(plaintext || tag)`
+//!
+//! The whole header is bound as associated data. KDF parameters are checked
+//! against fixed bounds before scrypt runs, on both seal and open, so an
+//! imported blob can neither downgrade the KDF below the minimum nor make
+//! the importer allocate gigabytes of memory.
+
+use crate::encryption::{KEY_SIZE, TAG_SIZE, XCHACHA_NONCE_SIZE};
+use crate::{CryptoError, CryptoResult};
+use alloc::{format, string::ToString, vec::Vec};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand_core::CryptoRngCore;
+use scrypt::{scrypt, Params};
+use zeroize::Zeroizing;
+
+/// Password blob magic
+pub const PASSWORD_BLOB_MAGIC: &[u8; 8] = b"CHAKPWE\0";
+
+/// Password blob format version
+pub const PASSWORD_BLOB_VERSION: u8 = 1;
+
+/// KDF identifier for scrypt
+pub const KDF_SCRYPT: u8 = 1;
+
+/// Smallest accepted scrypt cost (log2 N)
+pub const MIN_LOG_N: u8 = 14;
+
+/// Largest accepted scrypt cost (log2 N)
+pub const MAX_LOG_N: u8 = 20;
+
+/// Largest accepted scrypt parallelism
+pub const MAX_P: u32 = 4;
+
+/// Largest scrypt working set accepted (1 GiB)
+pub const MAX_KDF_MEMORY: u64 = 1 << 30;
+
+const SALT_SIZE: usize = 32;
+
+/// Blob header size (magic, version, kdf, log_n, r, p, salt, nonce)
+pub const HEADER_SIZE: usize = 8 + 1 + 1 + 1 + 4 + 4 + SALT_SIZE + XCHACHA_NONCE_SIZE;
+
+/// scrypt cost parameters stored in a blob header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordParams {
+    /// log2 of the scrypt CPU/memory cost N
+    pub log_n: u8,
+
+    /// scrypt block size
+    pub r: u32,
+
+    /// scrypt parallelism
+    pub p: u32,
+}
+
+impl PasswordParams {
+    /// Same cost as `utils::derive_key_from_password` (16 MiB)
+    pub const INTERACTIVE: Self = PasswordParams {
+        log_n: 14,
+        r: 8,
+        p: 1,
+    };
+
+    /// Higher cost for rarely opened blobs such as backups (1 GiB)
+    pub const SENSITIVE: Self = PasswordParams {
+        log_n: 20,
+        r: 8,
+        p: 1,
+    };
+
+    /// Bytes of memory scrypt needs for these parameters
+    pub fn memory(&self) -> u64 {
+        (128 * u64::from(self.r)) << self.log_n
+    }
+
+    /// Check the parameters against the accepted bounds
+    ///
+    /// # Errors
+    /// `KeyDerivationError` if the cost is below the minimum or the memory
+    /// or parallelism exceeds the maximum
+    pub fn validate(&self) -> CryptoResult<()> {
+        if self.log_n < MIN_LOG_N || self.r < 8 {
+            return Err(CryptoError::KeyDerivationError(
+                "scrypt parameters below minimum cost".to_string(),
+            ));
+        }
+        if self.log_n > MAX_LOG_N
+            || self.p == 0
+            || self.p > MAX_P
+            || self.r > 32
+            || self.memory() > MAX_KDF_MEMORY
+        {
+            return Err(CryptoError::KeyDerivationError(
+                "scrypt parameters exceed maximum cost".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Run scrypt over the password and salt
+    fn derive(&self, password: &[u8], salt: &[u8]) -> CryptoResult<Zeroizing<[u8; KEY_SIZE]>> {
+        self.validate()?;
+        let params = Params::new(self.log_n, self.r, self.p).map_err(|e| {
+            CryptoError::KeyDerivationError(format!("Invalid scrypt params: {}", e))
+        })?;
+
+        let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+        scrypt(password, salt, &params, key.as_mut())
+            .map_err(|e| CryptoError::KeyDerivationError(format!("Scrypt failed: {}", e)))?;
+        Ok(key)
+    }
+}
+
+impl Default for PasswordParams {
+    fn default() -> Self {
+        PasswordParams::INTERACTIVE
+    }
+}
+
+/// Encrypt a blob under a password
+///
+/// # Arguments
+/// * `password` - Password the blob key is derived from
+/// * `plaintext` - Data to seal
+/// * `params` - scrypt cost, stored in the header
+///
+/// # Errors
+/// `KeyDerivationError` if `params` is outside the accepted bounds
+#[cfg(feature = "std")]
+pub fn seal_with_password(
+    password: &[u8],
+    plaintext: &[u8],
+    params: PasswordParams,
+) -> CryptoResult<Vec<u8>> {
+    seal_with_password_with_rng(password, plaintext, params, &mut rand::thread_rng())
+}
+
+/// Encrypt a blob under a password using a caller-supplied RNG
+pub fn seal_with_password_with_rng(
+    password: &[u8],
+    plaintext: &[u8],
+    params: PasswordParams,
+    rng: &mut impl CryptoRngCore,
+) -> CryptoResult<Vec<u8>> {
+    let mut salt = [0u8; SALT_SIZE];
+    let mut nonce = [0u8; XCHACHA_NONCE_SIZE];
+    rng.fill_bytes(&mut salt);
+    rng.fill_bytes(&mut nonce);
+
+    let key = params.derive(password, &salt)?;
+
+    let mut out = Vec::with_capacity(HEADER_SIZE + plaintext.len() + TAG_SIZE);
+    out.extend_from_slice(PASSWORD_BLOB_MAGIC);
+    out.push(PASSWORD_BLOB_VERSION);
+    out.push(KDF_SCRYPT);
+    out.push(params.log_n);
+    out.extend_from_slice(&params.r.to_be_bytes());
+    out.extend_from_slice(&params.p.to_be_bytes());
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+
+    let ciphertext = XChaCha20Poly1305::new(key.as_ref().into())
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad: &out,
+            },
+        )
+        .map_err(|_| CryptoError::EncryptionError("Password blob encryption failed".to_string()))?;
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// Read the KDF parameters from a blob header without decrypting it
+///
+/// # Errors
+/// `DecryptionError` if `blob` is not a password blob of a supported
+/// version and KDF
+pub fn blob_params(blob: &[u8]) -> CryptoResult<PasswordParams> {
+    if blob.len() < HEADER_SIZE + TAG_SIZE || &blob[..8] != PASSWORD_BLOB_MAGIC {
+        return Err(CryptoError::DecryptionError(
+            "Not a password blob".to_string(),
+        ));
+    }
+    if blob[8] != PASSWORD_BLOB_VERSION {
+        return Err(CryptoError::DecryptionError(format!(
+            "Unsupported password blob version {}",
+            blob[8]
+        )));
+    }
+    if blob[9] != KDF_SCRYPT {
+        return Err(CryptoError::DecryptionError(format!(
+            "Unsupported password blob KDF {}",
+            blob[9]
+        )));
+    }
+
+    let mut r = [0u8; 4];
+    let mut p = [0u8; 4];
+    r.copy_from_slice(&blob[11..15]);
+    p.copy_from_slice(&blob[15..19]);
+    Ok(PasswordParams {
+        log_n: blob[10],
+        r: u32::from_be_bytes(r),
+        p: u32::from_be_bytes(p),
+    })
+}
+
+/// Decrypt a blob sealed with `seal_with_password`
+///
+/// The header's KDF parameters are validated before the password is
+/// stretched, so a hostile blob cannot force a derivation beyond
+/// `MAX_KDF_MEMORY` or below `MIN_LOG_N`.
+///
+/// # Errors
+/// * `DecryptionError` - not a password blob, unsupported format, wrong
+///   password or corrupted data
+/// * `KeyDerivationError` - KDF parameters outside the accepted bounds
+pub fn open_with_password(password: &[u8], blob: &[u8]) -> CryptoResult<Vec<u8>> {
+    let params = blob_params(blob)?;
+    let key = params.derive(password, &blob[19..19 + SALT_SIZE])?;
+
+    XChaCha20Poly1305::new(key.as_ref().into())
+        .decrypt(
+            XNonce::from_slice(&blob[19 + SALT_SIZE..HEADER_SIZE]),
+            Payload {
+                msg: &blob[HEADER_SIZE..],
+                aad: &blob[..HEADER_SIZE],
+            },
+        )
+        .map_err(|_| CryptoError::DecryptionError("Wrong password or corrupted data".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let blob = seal_with_password(b"hunter2", b"settings", PasswordParams::default()).unwrap();
+        assert_eq!(blob.len(), HEADER_SIZE + 8 + TAG_SIZE);
+        assert_eq!(blob_params(&blob).unwrap(), PasswordParams::INTERACTIVE);
+        assert_eq!(open_with_password(b"hunter2", &blob).unwrap(), b"settings");
+    }
+
+    #[test]
+    fn test_wrong_password() {
+        let blob = seal_with_password(b"hunter2", b"settings", PasswordParams::default()).unwrap();
+        assert!(matches!(
+            open_with_password(b"hunter3", &blob),
+            Err(CryptoError::DecryptionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_header_is_authenticated() {
+        let mut blob =
+            seal_with_password(b"hunter2", b"settings", PasswordParams::default()).unwrap();
+        // Raise r within bounds: the header still parses but no longer matches the AAD
+        blob[14] = 9;
+        assert!(matches!(
+            open_with_password(b"hunter2", &blob),
+            Err(CryptoError::DecryptionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_params_bounds() {
+        let weak = PasswordParams {
+            log_n: 10,
+            r: 8,
+            p: 1,
+        };
+        assert!(matches!(
+            seal_with_password(b"pw", b"data", weak),
+            Err(CryptoError::KeyDerivationError(_))
+        ));
+
+        let mut blob = seal_with_password(b"pw", b"data", PasswordParams::default()).unwrap();
+        for log_n in [MIN_LOG_N - 1, MAX_LOG_N + 1, 63] {
+            blob[10] = log_n;
+            assert!(matches!(
+                open_with_password(b"pw", &blob),
+                Err(CryptoError::KeyDerivationError(_))
+            ));
+        }
+
+        assert!(PasswordParams::SENSITIVE.validate().is_ok());
+        assert!(PasswordParams {
+            log_n: 20,
+            r: 16,
+            p: 1
+        }
+        .validate()
+        .is_err());
+        assert!(PasswordParams {
+            log_n: 14,
+            r: 8,
+            p: 0
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_rejects_foreign_data() {
+        let blob = seal_with_password(b"pw", b"data", PasswordParams::default()).unwrap();
+
+        assert!(open_with_password(b"pw", &blob[..HEADER_SIZE]).is_err());
+
+        let mut bad = blob.clone();
+        bad[0] ^= 1;
+        assert!(matches!(
+            blob_params(&bad),
+            Err(CryptoError::DecryptionError(_))
+        ));
+
+        let mut bad = blob.clone();
+        bad[8] = 2;
+        assert!(matches!(
+            blob_params(&bad),
+            Err(CryptoError::DecryptionError(_))
+        ));
+
+        let mut bad = blob;
+        bad[9] = 2;
+        assert!(matches!(
+            blob_params(&bad),
+            Err(CryptoError::DecryptionError(_))
+        ));
+    }
+}
@@ -0,0 +1,343 @@
+//! PKCS#11 Key Store
+//!
+//! `Pkcs11KeyStore` keeps the identity key on a hardware token or HSM. Both
+//! private keys are generated on the token as sensitive, non-extractable
+//! objects; Ed25519 signing (`CKM_EDDSA`) and X25519 agreement
+//! (`CKM_ECDH1_DERIVE`) run on the token and only signatures and shared
+//! secrets come back.
+//!
+//! Key objects share a label and are told apart by `CKA_ID` (`ed25519` or
+//! `x25519`). Tokens without `CKM_EC_MONTGOMERY_KEY_PAIR_GEN` (SoftHSM v2)
+//! generate the X25519 key through `CKM_EC_EDWARDS_KEY_PAIR_GEN` with
+//! Curve25519 parameters instead.
+//!
+//! `generate` creates the new pairs under a temporary label and only then
+//! destroys the old objects and relabels the new ones, so a failed
+//! generation leaves the existing identity key in place.
+
+use crate::key_exchange::SIGNATURE_SIZE;
+use crate::keystore::{KeyStore, PublicKeys};
+use crate::{CryptoError, CryptoResult};
+use cryptoki::context::{CInitializeArgs, CInitializeFlags, Function, Pkcs11};
+use cryptoki::error::{Error, RvError};
+use cryptoki::mechanism::eddsa::{EddsaParams, EddsaSignatureScheme};
+use cryptoki::mechanism::elliptic_curve::{EcKdf, Ecdh1DeriveParams};
+use cryptoki::mechanism::{Mechanism, MechanismType};
+use cryptoki::object::{Attribute, AttributeType, KeyType, ObjectClass, ObjectHandle};
+use cryptoki::session::{Session, UserType};
+use cryptoki::types::AuthPin;
+use rand_core::CryptoRngCore;
+use std::fmt;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// `CKA_EC_PARAMS` for Ed25519 (PrintableString "edwards25519")
+const ED25519_PARAMS: &[u8] = b"\x13\x0cedwards25519";
+
+/// `CKA_EC_PARAMS` for X25519 (PrintableString "curve25519")
+const X25519_PARAMS: &[u8] = b"\x13\x0acurve25519";
+
+/// `CKA_ID` of the signing key objects
+const SIGNING_ID: &[u8] = b"ed25519";
+
+/// `CKA_ID` of the key-agreement key objects
+const AGREEMENT_ID: &[u8] = b"x25519";
+
+/// Identity key held on a PKCS#11 token
+pub struct Pkcs11KeyStore {
+    session: Session,
+    label: String,
+    montgomery: bool,
+}
+
+impl fmt::Debug for Pkcs11KeyStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pkcs11KeyStore")
+            .field("label", &self.label)
+            .finish()
+    }
+}
+
+impl Pkcs11KeyStore {
+    /// Log in to a token and use the key stored under `label`
+    ///
+    /// The key need not exist yet; `generate` creates it.
+    ///
+    /// # Arguments
+    /// * `module` - PKCS#11 module, e.g. `/usr/lib/softhsm/libsofthsm2.so`
+    /// * `token_label` - Label of the token to use
+    /// * `pin` - User PIN
+    /// * `label` - Label of the key objects
+    pub fn open(
+        module: impl AsRef<Path>,
+        token_label: &str,
+        pin: &str,
+        label: &str,
+    ) -> CryptoResult<Self> {
+        let pkcs11 = Pkcs11::new(module.as_ref()).map_err(token_error)?;
+        // Another store in this process may have initialized the module already
+        match pkcs11.initialize(CInitializeArgs::new(CInitializeFlags::OS_LOCKING_OK)) {
+            Ok(())
+            | Err(Error::Pkcs11(RvError::CryptokiAlreadyInitialized, Function::Initialize)) => {}
+            Err(e) => return Err(token_error(e)),
+        }
+
+        let mut slot = None;
+        for candidate in pkcs11
+            .get_slots_with_initialized_token()
+            .map_err(token_error)?
+        {
+            if pkcs11
+                .get_token_info(candidate)
+                .map_err(token_error)?
+                .label()
+                == token_label
+            {
+                slot = Some(candidate);
+                break;
+            }
+        }
+        let slot = slot.ok_or_else(|| {
+            CryptoError::KeyStoreError(format!("No token labelled {}", token_label))
+        })?;
+
+        let montgomery = pkcs11
+            .get_mechanism_list(slot)
+            .map_err(token_error)?
+            .contains(&MechanismType::ECC_MONTGOMERY_KEY_PAIR_GEN);
+
+        let session = pkcs11.open_rw_session(slot).map_err(token_error)?;
+        match session.login(UserType::User, Some(&AuthPin::new(pin.into()))) {
+            Ok(()) | Err(Error::Pkcs11(RvError::UserAlreadyLoggedIn, Function::Login)) => {}
+            Err(e) => return Err(token_error(e)),
+        }
+
+        Ok(Pkcs11KeyStore {
+            session,
+            label: label.to_string(),
+            montgomery,
+        })
+    }
+
+    /// Label of the key objects
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Find the single object of `class` with our label and `id`
+    fn find(&self, class: ObjectClass, id: &[u8]) -> CryptoResult<ObjectHandle> {
+        let template = [
+            Attribute::Class(class),
+            Attribute::Label(self.label.as_bytes().to_vec()),
+            Attribute::Id(id.to_vec()),
+        ];
+        match self
+            .session
+            .find_objects(&template)
+            .map_err(token_error)?
+            .as_slice()
+        {
+            [handle] => Ok(*handle),
+            [] => Err(CryptoError::KeyStoreError(format!(
+                "No key labelled {} on token",
+                self.label
+            ))),
+            _ => Err(CryptoError::KeyStoreError(format!(
+                "Several keys labelled {} on token",
+                self.label
+            ))),
+        }
+    }
+
+    /// Raw 32-byte public key of the public-key object `id`
+    fn public_key(&self, id: &[u8]) -> CryptoResult<[u8; 32]> {
+        let handle = self.find(ObjectClass::PUBLIC_KEY, id)?;
+        let point = match self
+            .session
+            .get_attributes(handle, &[AttributeType::EcPoint])
+            .map_err(token_error)?
+            .pop()
+        {
+            Some(Attribute::EcPoint(point)) => point,
+            _ => {
+                return Err(CryptoError::KeyStoreError(
+                    "Public key has no CKA_EC_POINT".to_string(),
+                ))
+            }
+        };
+
+        // PKCS#11 3.0 wraps the key in a DER OCTET STRING; some tokens return it bare
+        let raw = match point.as_slice() {
+            [0x04, 0x20, raw @ ..] if raw.len() == 32 => raw,
+            raw => raw,
+        };
+        <[u8; 32]>::try_from(raw).map_err(|_| {
+            CryptoError::InvalidKey("Token returned a malformed public key".to_string())
+        })
+    }
+
+    /// Every object labelled `label`
+    fn objects(&self, label: &str) -> CryptoResult<Vec<ObjectHandle>> {
+        let template = [Attribute::Label(label.as_bytes().to_vec())];
+        self.session.find_objects(&template).map_err(token_error)
+    }
+
+    /// Destroy every object labelled `label`
+    fn destroy(&self, label: &str) -> CryptoResult<()> {
+        for handle in self.objects(label)? {
+            self.session.destroy_object(handle).map_err(token_error)?;
+        }
+        Ok(())
+    }
+
+    /// Generate both key pairs under `label`
+    fn generate_pairs(&self, label: &str) -> CryptoResult<()> {
+        self.generate_pair(
+            label,
+            Mechanism::EccEdwardsKeyPairGen,
+            ED25519_PARAMS,
+            SIGNING_ID,
+            Attribute::Verify(true),
+            Attribute::Sign(true),
+        )?;
+
+        let agreement = if self.montgomery {
+            Mechanism::EccMontgomeryKeyPairGen
+        } else {
+            Mechanism::EccEdwardsKeyPairGen
+        };
+        self.generate_pair(
+            label,
+            agreement,
+            X25519_PARAMS,
+            AGREEMENT_ID,
+            Attribute::Derive(false),
+            Attribute::Derive(true),
+        )
+    }
+
+    /// Generate a token key pair under `label` with `id`
+    fn generate_pair(
+        &self,
+        label: &str,
+        mechanism: Mechanism,
+        params: &[u8],
+        id: &[u8],
+        public_usage: Attribute,
+        private_usage: Attribute,
+    ) -> CryptoResult<()> {
+        let label = Attribute::Label(label.as_bytes().to_vec());
+        let public_template = [
+            Attribute::Token(true),
+            Attribute::Private(false),
+            Attribute::EcParams(params.to_vec()),
+            label.clone(),
+            Attribute::Id(id.to_vec()),
+            public_usage,
+        ];
+        let private_template = [
+            Attribute::Token(true),
+            Attribute::Private(true),
+            Attribute::Sensitive(true),
+            Attribute::Extractable(false),
+            label,
+            Attribute::Id(id.to_vec()),
+            private_usage,
+        ];
+        self.session
+            .generate_key_pair(&mechanism, &public_template, &private_template)
+            .map_err(token_error)?;
+        Ok(())
+    }
+}
+
+impl KeyStore for Pkcs11KeyStore {
+    /// Generate both key pairs on the token, then replace the old ones
+    ///
+    /// The new pairs are generated under a temporary label; if that fails
+    /// they are discarded and the current key stays usable. `rng` only
+    /// picks the temporary label; the keys come from the token.
+    fn generate(&mut self, rng: &mut dyn CryptoRngCore) -> CryptoResult<PublicKeys> {
+        let mut suffix = [0u8; 8];
+        rng.fill_bytes(&mut suffix);
+        let pending = format!("{}.pending.{}", self.label, hex::encode(suffix));
+
+        if let Err(e) = self.generate_pairs(&pending) {
+            let _ = self.destroy(&pending);
+            return Err(e);
+        }
+
+        self.destroy(&self.label)?;
+        let label = [Attribute::Label(self.label.as_bytes().to_vec())];
+        for handle in self.objects(&pending)? {
+            self.session
+                .update_attributes(handle, &label)
+                .map_err(token_error)?;
+        }
+
+        self.public_keys()
+    }
+
+    fn public_keys(&self) -> CryptoResult<PublicKeys> {
+        Ok(PublicKeys {
+            public_key: self.public_key(AGREEMENT_ID)?,
+            verifying_key: self.public_key(SIGNING_ID)?,
+        })
+    }
+
+    fn sign(&self, data: &[u8]) -> CryptoResult<[u8; SIGNATURE_SIZE]> {
+        let key = self.find(ObjectClass::PRIVATE_KEY, SIGNING_ID)?;
+        let mechanism = Mechanism::Eddsa(EddsaParams::new(EddsaSignatureScheme::Pure));
+        let signature = self
+            .session
+            .sign(&mechanism, key, data)
+            .map_err(token_error)?;
+
+        <[u8; SIGNATURE_SIZE]>::try_from(signature.as_slice()).map_err(|_| {
+            CryptoError::KeyStoreError("Token returned a malformed signature".to_string())
+        })
+    }
+
+    fn compute_shared_secret(&self, peer_public_key: &[u8; 32]) -> CryptoResult<[u8; 32]> {
+        let key = self.find(ObjectClass::PRIVATE_KEY, AGREEMENT_ID)?;
+        let mechanism =
+            Mechanism::Ecdh1Derive(Ecdh1DeriveParams::new(EcKdf::null(), peer_public_key));
+
+        // Session object readable by us only long enough to copy the value out
+        let derived = self
+            .session
+            .derive_key(
+                &mechanism,
+                key,
+                &[
+                    Attribute::Class(ObjectClass::SECRET_KEY),
+                    Attribute::KeyType(KeyType::GENERIC_SECRET),
+                    Attribute::ValueLen(32.into()),
+                    Attribute::Token(false),
+                    Attribute::Sensitive(false),
+                    Attribute::Extractable(true),
+                ],
+            )
+            .map_err(token_error)?;
+        let value = self
+            .session
+            .get_attributes(derived, &[AttributeType::Value]);
+        self.session.destroy_object(derived).map_err(token_error)?;
+
+        let value = match value.map_err(token_error)?.pop() {
+            Some(Attribute::Value(value)) => Zeroizing::new(value),
+            _ => {
+                return Err(CryptoError::KeyAgreementFailed(
+                    "Token returned no shared secret".to_string(),
+                ))
+            }
+        };
+        <[u8; 32]>::try_from(value.as_slice()).map_err(|_| {
+            CryptoError::KeyAgreementFailed("Token returned a malformed shared secret".to_string())
+        })
+    }
+}
+
+fn token_error(e: Error) -> CryptoError {
+    CryptoError::KeyStoreError(format!("PKCS#11: {}", e))
+}
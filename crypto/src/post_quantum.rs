@@ -3,9 +3,10 @@
 //! CRYSTALS-Kyber1024 (ML-KEM) for quantum-resistant key encapsulation.
 //! Provides 256-bit security against both classical and quantum computers.
 
+use crate::registry::Secret;
 use crate::{CryptoError, CryptoResult};
 use pqcrypto_kyber::kyber1024;
-use pqcrypto_traits::kem::{Ciphertext, PublicKey, SecretKey};
+use pqcrypto_traits::kem::{Ciphertext, PublicKey, SecretKey, SharedSecret};
 use serde::{Deserialize, Serialize};
 
 /// Kyber encapsulation key size
@@ -15,7 +16,7 @@ pub const KYBER_EK_SIZE: usize = 1568;
 pub const KYBER_DK_SIZE: usize = 3168;
 
 /// Kyber ciphertext size
-pub const KYBER_CT_SIZE: usize = 1120;
+pub const KYBER_CT_SIZE: usize = 1568;
 
 /// Kyber shared secret size (256-bit)
 pub const KYBER_SS_SIZE: usize = 32;
@@ -24,14 +25,15 @@ pub const KYBER_SS_SIZE: usize = 32;
 #[derive(Clone, Serialize, Deserialize)]
 pub struct PostQuantumKeyPair {
     /// Public key (encapsulation key)
+    #[serde(with = "crate::encoding::base64_bytes")]
     pub public_key: Vec<u8>,
 
-    /// Secret key (decapsulation key)
-    secret_key: Vec<u8>,
+    /// Secret key (decapsulation key); wiped by `registry::panic_wipe()`
+    secret_key: Secret<Vec<u8>>,
 }
 
 /// Hybrid key agreement combining classical and post-quantum
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone)]
 pub struct HybridKeyAgreement {
     /// Classical ECDH shared secret (32 bytes)
     pub classical_secret: [u8; 32],
@@ -53,7 +55,7 @@ impl PostQuantumKeyPair {
 
         Ok(PostQuantumKeyPair {
             public_key: pk.as_bytes().to_vec(),
-            secret_key: sk.as_bytes().to_vec(),
+            secret_key: Secret::new(sk.as_bytes().to_vec()),
         })
     }
 
@@ -78,11 +80,11 @@ impl PostQuantumKeyPair {
         }
 
         let pk = kyber1024::PublicKey::from_bytes(peer_public_key)
-            .ok_or_else(|| CryptoError::KeyAgreementFailed("Invalid public key".to_string()))?;
+            .map_err(|_| CryptoError::KeyAgreementFailed("Invalid public key".to_string()))?;
 
         let (ss, ct) = kyber1024::encapsulate(&pk);
 
-        Ok((*ss.as_bytes(), ct.as_bytes().to_vec()))
+        Ok((Self::shared_secret_bytes(&ss)?, ct.as_bytes().to_vec()))
     }
 
     /// Decapsulate: extract shared secret from ciphertext
@@ -100,15 +102,23 @@ impl PostQuantumKeyPair {
             )));
         }
 
-        let sk = kyber1024::SecretKey::from_bytes(self.secret_key.as_slice())
-            .ok_or_else(|| CryptoError::KeyAgreementFailed("Invalid secret key".to_string()))?;
+        let sk = self.secret_key.with(|secret_key| {
+            kyber1024::SecretKey::from_bytes(secret_key)
+                .map_err(|_| CryptoError::KeyAgreementFailed("Invalid secret key".to_string()))
+        })?;
 
         let ct = kyber1024::Ciphertext::from_bytes(ciphertext)
-            .ok_or_else(|| CryptoError::KeyAgreementFailed("Invalid ciphertext".to_string()))?;
+            .map_err(|_| CryptoError::KeyAgreementFailed("Invalid ciphertext".to_string()))?;
 
         let ss = kyber1024::decapsulate(&ct, &sk);
 
-        Ok(*ss.as_bytes())
+        Self::shared_secret_bytes(&ss)
+    }
+
+    /// Copy a Kyber shared secret into a fixed-size array
+    fn shared_secret_bytes(ss: &kyber1024::SharedSecret) -> CryptoResult<[u8; KYBER_SS_SIZE]> {
+        <[u8; KYBER_SS_SIZE]>::try_from(ss.as_bytes())
+            .map_err(|_| CryptoError::KeyAgreementFailed("Invalid shared secret size".to_string()))
     }
 }
 
@@ -149,7 +159,10 @@ mod tests {
     fn test_kyber_keypair_generation() {
         let keypair = PostQuantumKeyPair::generate().unwrap();
         assert_eq!(keypair.public_key.len(), KYBER_EK_SIZE);
-        assert_eq!(keypair.secret_key.len(), KYBER_DK_SIZE);
+        assert_eq!(
+            keypair.secret_key.with(|sk| Ok(sk.len())).unwrap(),
+            KYBER_DK_SIZE
+        );
     }
 
     #[test]
@@ -0,0 +1,367 @@
+//! Private Contact Discovery (ECDH-PSI)
+//!
+//! Uploading hashed phone numbers leaks them: the number space is small
+//! enough to brute-force every hash. This module implements Diffie-Hellman
+//! private set intersection over Ristretto255 instead:
+//! 1. The server publishes its registered identifiers as `b·H(y)` under a
+//!    long-term server key `b` (`PsiServer::encode_set`)
+//! 2. The client blinds each contact with a fresh scalar `a` and sends
+//!    `a·H(x)` (`PsiClient::new`)
+//! 3. The server returns `b·(a·H(x))` (`PsiServer::evaluate`)
+//! 4. The client unblinds to `b·H(x)` and looks it up in the published set
+//!    (`PsiClient::intersect`)
+//!
+//! The server never sees `H(x)` and the client cannot evaluate `b·H(y)` for
+//! identifiers it did not ask about, so neither side can run a dictionary
+//! attack offline. `H` is `RistrettoPoint::from_uniform_bytes` over a
+//! domain-separated SHA-512. Identifiers must be normalised (e.g. E.164
+//! phone numbers) by the caller before they are passed in.
+
+use crate::registry::Secret;
+use crate::utils::hash_sha512;
+use crate::{CryptoError, CryptoResult};
+use alloc::{collections::BTreeSet, format, string::ToString, vec::Vec};
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::Identity;
+use rand_core::CryptoRngCore;
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, Zeroizing};
+
+/// Encoded Ristretto255 point size
+pub const POINT_SIZE: usize = 32;
+
+/// Most identifiers accepted in a single request
+pub const MAX_PSI_BATCH: usize = 10_000;
+
+/// Domain separator for hashing identifiers to the group
+const HASH_DOMAIN: &[u8] = b"chakchat_psi_v1";
+
+/// Blinded identifiers sent from client to server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PsiRequest {
+    /// `a·H(x)` for each client identifier, in input order
+    #[serde(with = "crate::encoding::base64_array_vec")]
+    pub blinded: Vec<[u8; POINT_SIZE]>,
+}
+
+/// Server key applied to a `PsiRequest`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PsiResponse {
+    /// `b·a·H(x)` for each requested point, in request order
+    #[serde(with = "crate::encoding::base64_array_vec")]
+    pub evaluated: Vec<[u8; POINT_SIZE]>,
+}
+
+/// Server half: holds the long-term PSI key
+pub struct PsiServer {
+    key: Secret<Scalar>,
+}
+
+/// Client half: holds the blinding scalar for one request
+pub struct PsiClient {
+    blind: Secret<Scalar>,
+    count: usize,
+}
+
+impl PsiServer {
+    /// Generate a new server key
+    #[cfg(feature = "std")]
+    pub fn generate() -> Self {
+        Self::generate_with_rng(&mut rand::thread_rng())
+    }
+
+    /// Generate a new server key using a caller-supplied RNG
+    pub fn generate_with_rng(rng: &mut impl CryptoRngCore) -> Self {
+        PsiServer {
+            key: Secret::new(random_scalar(rng)),
+        }
+    }
+
+    /// Restore a server key saved with `to_bytes`
+    ///
+    /// # Errors
+    /// `InvalidKey` if `bytes` is not a canonical non-zero scalar
+    pub fn from_bytes(bytes: &[u8; 32]) -> CryptoResult<Self> {
+        let key = Option::<Scalar>::from(Scalar::from_canonical_bytes(*bytes))
+            .filter(|k| *k != Scalar::ZERO)
+            .ok_or_else(|| CryptoError::InvalidKey("Invalid PSI server key".to_string()))?;
+        Ok(PsiServer {
+            key: Secret::new(key),
+        })
+    }
+
+    /// Export the server key; the published set is only valid under this key
+    pub fn to_bytes(&self) -> CryptoResult<Zeroizing<[u8; 32]>> {
+        self.key.with(|k| Ok(Zeroizing::new(k.to_bytes())))
+    }
+
+    /// Encode the registered identifiers as `b·H(y)`
+    ///
+    /// The result is sorted, so it reveals nothing about insertion order, and
+    /// can be cached and handed to every client until the key rotates.
+    pub fn encode_set<I: AsRef<[u8]>>(
+        &self,
+        identifiers: &[I],
+    ) -> CryptoResult<Vec<[u8; POINT_SIZE]>> {
+        self.key.with(|key| {
+            let mut set: Vec<[u8; POINT_SIZE]> = identifiers
+                .iter()
+                .map(|id| (key * hash_to_point(id.as_ref())).compress().to_bytes())
+                .collect();
+            set.sort_unstable();
+            set.dedup();
+            Ok(set)
+        })
+    }
+
+    /// Apply the server key to a client's blinded identifiers
+    ///
+    /// # Errors
+    /// `KeyAgreementFailed` if the request exceeds `MAX_PSI_BATCH` or holds
+    /// an invalid or identity point
+    pub fn evaluate(&self, request: &PsiRequest) -> CryptoResult<PsiResponse> {
+        if request.blinded.len() > MAX_PSI_BATCH {
+            return Err(CryptoError::KeyAgreementFailed(format!(
+                "PSI request exceeds {} identifiers",
+                MAX_PSI_BATCH
+            )));
+        }
+
+        self.key.with(|key| {
+            let evaluated = request
+                .blinded
+                .iter()
+                .map(|bytes| Ok((key * decode_point(bytes)?).compress().to_bytes()))
+                .collect::<CryptoResult<Vec<_>>>()?;
+            Ok(PsiResponse { evaluated })
+        })
+    }
+}
+
+impl PsiClient {
+    /// Blind the client's identifiers for a new request
+    ///
+    /// # Errors
+    /// `KeyAgreementFailed` if there are more than `MAX_PSI_BATCH` identifiers
+    #[cfg(feature = "std")]
+    pub fn new<I: AsRef<[u8]>>(identifiers: &[I]) -> CryptoResult<(Self, PsiRequest)> {
+        Self::new_with_rng(identifiers, &mut rand::thread_rng())
+    }
+
+    /// Blind the client's identifiers using a caller-supplied RNG
+    pub fn new_with_rng<I: AsRef<[u8]>>(
+        identifiers: &[I],
+        rng: &mut impl CryptoRngCore,
+    ) -> CryptoResult<(Self, PsiRequest)> {
+        if identifiers.len() > MAX_PSI_BATCH {
+            return Err(CryptoError::KeyAgreementFailed(format!(
+                "PSI request exceeds {} identifiers",
+                MAX_PSI_BATCH
+            )));
+        }
+
+        let blind = random_scalar(rng);
+        let blinded = identifiers
+            .iter()
+            .map(|id| (blind * hash_to_point(id.as_ref())).compress().to_bytes())
+            .collect();
+
+        let client = PsiClient {
+            blind: Secret::new(blind),
+            count: identifiers.len(),
+        };
+        Ok((client, PsiRequest { blinded }))
+    }
+
+    /// Unblind the server's response and intersect it with the published set
+    ///
+    /// # Arguments
+    /// * `response` - Server's answer to this client's request
+    /// * `server_set` - Set published by `PsiServer::encode_set`
+    ///
+    /// # Returns
+    /// Indices into the identifiers passed to `new`, in ascending order
+    ///
+    /// # Errors
+    /// `KeyAgreementFailed` if the response does not match the request or
+    /// holds an invalid point
+    pub fn intersect(
+        &self,
+        response: &PsiResponse,
+        server_set: &[[u8; POINT_SIZE]],
+    ) -> CryptoResult<Vec<usize>> {
+        if response.evaluated.len() != self.count {
+            return Err(CryptoError::KeyAgreementFailed(
+                "PSI response length mismatch".to_string(),
+            ));
+        }
+
+        let published: BTreeSet<&[u8; POINT_SIZE]> = server_set.iter().collect();
+        self.blind.with(|blind| {
+            let unblind = blind.invert();
+            let mut matches = Vec::new();
+            for (index, bytes) in response.evaluated.iter().enumerate() {
+                let point = (unblind * decode_point(bytes)?).compress().to_bytes();
+                if published.contains(&point) {
+                    matches.push(index);
+                }
+            }
+            Ok(matches)
+        })
+    }
+}
+
+/// Hash an identifier to a Ristretto point
+fn hash_to_point(identifier: &[u8]) -> RistrettoPoint {
+    let mut input = Vec::with_capacity(HASH_DOMAIN.len() + identifier.len());
+    input.extend_from_slice(HASH_DOMAIN);
+    input.extend_from_slice(identifier);
+    RistrettoPoint::from_uniform_bytes(&hash_sha512(&input))
+}
+
+/// Decode a point, rejecting invalid encodings and the identity
+fn decode_point(bytes: &[u8; POINT_SIZE]) -> CryptoResult<RistrettoPoint> {
+    CompressedRistretto(*bytes)
+        .decompress()
+        .filter(|p| *p != RistrettoPoint::identity())
+        .ok_or_else(|| CryptoError::KeyAgreementFailed("Invalid PSI point".to_string()))
+}
+
+/// Uniform non-zero scalar
+fn random_scalar(rng: &mut impl CryptoRngCore) -> Scalar {
+    loop {
+        let mut wide = [0u8; 64];
+        rng.fill_bytes(&mut wide);
+        let scalar = Scalar::from_bytes_mod_order_wide(&wide);
+        wide.zeroize();
+        if scalar != Scalar::ZERO {
+            return scalar;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// In-process contact directory standing in for the discovery service
+    struct TestDirectory {
+        server: PsiServer,
+        published: Vec<[u8; POINT_SIZE]>,
+    }
+
+    impl TestDirectory {
+        fn new(registered: &[&str]) -> Self {
+            let server = PsiServer::generate();
+            let published = server.encode_set(registered).unwrap();
+            TestDirectory { server, published }
+        }
+
+        fn discover(&self, contacts: &[&str]) -> Vec<usize> {
+            let (client, request) = PsiClient::new(contacts).unwrap();
+            let response = self.server.evaluate(&request).unwrap();
+            client.intersect(&response, &self.published).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_intersection() {
+        let directory = TestDirectory::new(&["+15550001", "+15550002", "+15550003"]);
+
+        let found = directory.discover(&["+15559999", "+15550003", "+15550001", "+15558888"]);
+        assert_eq!(found, vec![1, 2]);
+
+        assert!(directory.discover(&["+15557777"]).is_empty());
+        assert!(directory.discover(&[] as &[&str]).is_empty());
+    }
+
+    #[test]
+    fn test_request_hides_identifiers() {
+        let server = PsiServer::generate();
+        let published = server.encode_set(&["+15550001"]).unwrap();
+
+        let (_, first) = PsiClient::new(&["+15550001"]).unwrap();
+        let (_, second) = PsiClient::new(&["+15550001"]).unwrap();
+
+        // Fresh blinding each time, and never the unblinded hash
+        assert_ne!(first.blinded, second.blinded);
+        assert_ne!(
+            first.blinded[0],
+            hash_to_point(b"+15550001").compress().to_bytes()
+        );
+        assert_ne!(first.blinded[0], published[0]);
+    }
+
+    #[test]
+    fn test_server_key_round_trip() {
+        let server = PsiServer::generate();
+        let restored = PsiServer::from_bytes(&server.to_bytes().unwrap()).unwrap();
+        assert_eq!(
+            server.encode_set(&["a", "b"]).unwrap(),
+            restored.encode_set(&["a", "b"]).unwrap()
+        );
+
+        assert!(PsiServer::from_bytes(&[0u8; 32]).is_err());
+        assert!(PsiServer::from_bytes(&[0xffu8; 32]).is_err());
+    }
+
+    #[test]
+    fn test_messages_json_round_trip() {
+        let directory = TestDirectory::new(&["+15550001"]);
+        let (client, request) = PsiClient::new(&["+15550001"]).unwrap();
+
+        let json = serde_json::to_value(&request).unwrap();
+        assert!(json["blinded"][0].is_string(), "{json}");
+        let request: PsiRequest = serde_json::from_value(json).unwrap();
+
+        let response = directory.server.evaluate(&request).unwrap();
+        let response: PsiResponse =
+            serde_json::from_str(&serde_json::to_string(&response).unwrap()).unwrap();
+        assert_eq!(
+            client.intersect(&response, &directory.published).unwrap(),
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn test_wrong_server_key_finds_nothing() {
+        let registered = ["+15550001"];
+        let published = PsiServer::generate().encode_set(&registered).unwrap();
+
+        let (client, request) = PsiClient::new(&registered).unwrap();
+        let response = PsiServer::generate().evaluate(&request).unwrap();
+        assert!(client.intersect(&response, &published).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_malformed_messages_rejected() {
+        let server = PsiServer::generate();
+
+        let identity = PsiRequest {
+            blinded: vec![RistrettoPoint::identity().compress().to_bytes()],
+        };
+        assert!(matches!(
+            server.evaluate(&identity),
+            Err(CryptoError::KeyAgreementFailed(_))
+        ));
+
+        let invalid = PsiRequest {
+            blinded: vec![[0xffu8; POINT_SIZE]],
+        };
+        assert!(server.evaluate(&invalid).is_err());
+
+        let oversized = PsiRequest {
+            blinded: vec![[0u8; POINT_SIZE]; MAX_PSI_BATCH + 1],
+        };
+        assert!(server.evaluate(&oversized).is_err());
+
+        let (client, request) = PsiClient::new(&["a", "b"]).unwrap();
+        let mut response = server.evaluate(&request).unwrap();
+        response.evaluated.pop();
+        assert!(matches!(
+            client.intersect(&response, &[]),
+            Err(CryptoError::KeyAgreementFailed(_))
+        ));
+    }
+}
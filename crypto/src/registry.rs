@@ -0,0 +1,458 @@
+//! Secret Registry (Panic Mode)
+//!
+//! Key material lives in `Secret<T>` cells. With `std`, every cell registers
+//! a weak handle in a process-wide `SecretRegistry`; `panic_wipe()` zeroizes
+//! all live cells in place, so every object still holding one fails with
+//! `CryptoError::Wiped` from then on. Wipe hooks let storage backends destroy
+//! their data files in the same call.
+//!
+//! Without `std` there is no global registry and `Secret<T>` is a plain
+//! zeroize-on-drop wrapper.
+
+use crate::CryptoResult;
+use core::fmt;
+use zeroize::Zeroize;
+
+#[cfg(feature = "std")]
+use std::cell::Cell;
+#[cfg(feature = "std")]
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, Weak};
+
+/// Wipe hook (e.g. delete database files)
+///
+/// Shared so `wipe_all` can run the hooks without holding the hook list's lock.
+#[cfg(feature = "std")]
+pub type WipeHook = Arc<dyn Fn() + Send + Sync>;
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    /// Set while this thread runs wipe hooks, so a hook that wipes again does not recurse
+    static RUNNING_HOOKS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Object that can be zeroized through a shared handle
+#[cfg(feature = "std")]
+pub trait Wipe: Send + Sync {
+    /// Zeroize in place; later uses fail with `CryptoError::Wiped`
+    fn wipe(&self);
+}
+
+/// Smallest handle count at which `register` prunes dropped handles
+#[cfg(feature = "std")]
+const MIN_PRUNE_LEN: usize = 64;
+
+/// Registered handles; dropped ones are pruned once the list has doubled
+#[cfg(feature = "std")]
+struct Handles {
+    list: Vec<Weak<dyn Wipe>>,
+    prune_at: usize,
+}
+
+#[cfg(feature = "std")]
+impl Default for Handles {
+    fn default() -> Self {
+        Handles {
+            list: Vec::new(),
+            prune_at: MIN_PRUNE_LEN,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Handles {
+    /// Drop dead handles and move the next prune to twice the live count
+    fn prune(&mut self) {
+        self.list.retain(|weak| weak.strong_count() > 0);
+        self.prune_at = (self.list.len() * 2).max(MIN_PRUNE_LEN);
+    }
+}
+
+/// Process-wide list of live secrets and wipe hooks
+#[cfg(feature = "std")]
+#[derive(Default)]
+pub struct SecretRegistry {
+    secrets: Mutex<Handles>,
+    hooks: Mutex<Vec<WipeHook>>,
+}
+
+/// Result of a `panic_wipe()` call
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WipeReport {
+    /// Live secrets zeroized
+    pub secrets: usize,
+
+    /// Hooks run
+    pub hooks: usize,
+}
+
+/// Lock even if a panicking thread poisoned the mutex; panic mode must not fail
+#[cfg(feature = "std")]
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(feature = "std")]
+impl SecretRegistry {
+    /// The process-wide registry
+    pub fn global() -> &'static SecretRegistry {
+        static REGISTRY: OnceLock<SecretRegistry> = OnceLock::new();
+        REGISTRY.get_or_init(SecretRegistry::default)
+    }
+
+    /// Register a weak handle
+    ///
+    /// Dropped handles are pruned only when the list has doubled since the
+    /// last prune, so registration stays amortized O(1).
+    pub fn register(&self, handle: Weak<dyn Wipe>) {
+        let mut secrets = lock(&self.secrets);
+        if secrets.list.len() >= secrets.prune_at {
+            secrets.prune();
+        }
+        secrets.list.push(handle);
+    }
+
+    /// Add a hook run by every wipe
+    pub fn add_wipe_hook(&self, hook: WipeHook) {
+        lock(&self.hooks).push(hook);
+    }
+
+    /// Number of live registered secrets; prunes dropped handles
+    pub fn live_count(&self) -> usize {
+        let mut secrets = lock(&self.secrets);
+        secrets.prune();
+        secrets.list.len()
+    }
+
+    /// Zeroize every live secret, then run the hooks
+    ///
+    /// Hooks run outside the hook list's lock, so a hook may add hooks or
+    /// wipe again. A wipe started from inside a hook zeroizes secrets but
+    /// does not re-run the hooks.
+    pub fn wipe_all(&self) -> WipeReport {
+        // Upgrade under the lock, wipe outside it so a secret being dropped
+        // concurrently cannot deadlock against us
+        let live: Vec<Arc<dyn Wipe>> = {
+            let mut secrets = lock(&self.secrets);
+            let live = secrets.list.iter().filter_map(Weak::upgrade).collect();
+            *secrets = Handles::default();
+            live
+        };
+        for secret in &live {
+            secret.wipe();
+        }
+
+        if RUNNING_HOOKS.with(Cell::get) {
+            return WipeReport {
+                secrets: live.len(),
+                hooks: 0,
+            };
+        }
+
+        /// Clears `RUNNING_HOOKS` even if a hook panics
+        struct Running;
+        impl Drop for Running {
+            fn drop(&mut self) {
+                RUNNING_HOOKS.with(|running| running.set(false));
+            }
+        }
+
+        let hooks: Vec<WipeHook> = lock(&self.hooks).clone();
+        RUNNING_HOOKS.with(|running| running.set(true));
+        let _running = Running;
+        for hook in &hooks {
+            hook();
+        }
+
+        WipeReport {
+            secrets: live.len(),
+            hooks: hooks.len(),
+        }
+    }
+}
+
+/// Zeroize every registered secret and run the wipe hooks
+///
+/// Open sessions and key pairs stay allocated but return
+/// `CryptoError::Wiped` from every operation that needs their keys.
+#[cfg(feature = "std")]
+pub fn panic_wipe() -> WipeReport {
+    SecretRegistry::global().wipe_all()
+}
+
+/// Register a hook run by `panic_wipe()`
+#[cfg(feature = "std")]
+pub fn add_wipe_hook(hook: impl Fn() + Send + Sync + 'static) {
+    SecretRegistry::global().add_wipe_hook(Arc::new(hook));
+}
+
+#[cfg(feature = "std")]
+struct SecretCell<T: Zeroize> {
+    value: Mutex<Option<T>>,
+}
+
+#[cfg(feature = "std")]
+impl<T: Zeroize + Send> Wipe for SecretCell<T> {
+    fn wipe(&self) {
+        let mut value = lock(&self.value);
+        // Zeroize in place before dropping; `take()` would leave the bytes behind
+        if let Some(inner) = value.as_mut() {
+            inner.zeroize();
+        }
+        *value = None;
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Zeroize> Drop for SecretCell<T> {
+    fn drop(&mut self) {
+        let value = self
+            .value
+            .get_mut()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(inner) = value.as_mut() {
+            inner.zeroize();
+        }
+    }
+}
+
+/// Key material that `panic_wipe()` can destroy
+pub struct Secret<T: Zeroize + Send + 'static> {
+    #[cfg(feature = "std")]
+    cell: Arc<SecretCell<T>>,
+
+    #[cfg(not(feature = "std"))]
+    value: T,
+}
+
+impl<T: Zeroize + Send + 'static> Secret<T> {
+    /// Wrap and register a secret
+    pub fn new(value: T) -> Self {
+        #[cfg(feature = "std")]
+        {
+            Secret::from_option(Some(value))
+        }
+
+        #[cfg(not(feature = "std"))]
+        {
+            Secret { value }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn from_option(value: Option<T>) -> Self {
+        let cell = Arc::new(SecretCell {
+            value: Mutex::new(value),
+        });
+        let handle: Arc<dyn Wipe> = cell.clone();
+        SecretRegistry::global().register(Arc::downgrade(&handle));
+        Secret { cell }
+    }
+
+    /// Use the secret
+    ///
+    /// # Errors
+    /// `CryptoError::Wiped` after `panic_wipe()`, otherwise whatever `f` returns
+    pub fn with<R>(&self, f: impl FnOnce(&T) -> CryptoResult<R>) -> CryptoResult<R> {
+        #[cfg(feature = "std")]
+        {
+            let value = lock(&self.cell.value);
+            f(value.as_ref().ok_or(crate::CryptoError::Wiped)?)
+        }
+
+        #[cfg(not(feature = "std"))]
+        {
+            f(&self.value)
+        }
+    }
+
+    /// Whether the secret has been wiped
+    pub fn is_wiped(&self) -> bool {
+        #[cfg(feature = "std")]
+        {
+            lock(&self.cell.value).is_none()
+        }
+
+        #[cfg(not(feature = "std"))]
+        {
+            false
+        }
+    }
+
+    /// Zeroize this secret now
+    pub fn wipe(&self) {
+        #[cfg(feature = "std")]
+        {
+            Wipe::wipe(self.cell.as_ref());
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<T: Zeroize + Send + 'static> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+/// Copies into a new, separately registered cell; clones of a wiped secret are wiped
+impl<T: Zeroize + Clone + Send + 'static> Clone for Secret<T> {
+    fn clone(&self) -> Self {
+        #[cfg(feature = "std")]
+        {
+            let value = lock(&self.cell.value).clone();
+            Secret::from_option(value)
+        }
+
+        #[cfg(not(feature = "std"))]
+        {
+            Secret {
+                value: self.value.clone(),
+            }
+        }
+    }
+}
+
+impl<T: Zeroize + Send + 'static> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_wiped() {
+            f.write_str("Secret([WIPED])")
+        } else {
+            f.write_str("Secret([REDACTED])")
+        }
+    }
+}
+
+impl<T: Zeroize + serde::Serialize + Send + 'static> serde::Serialize for Secret<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use alloc::string::ToString;
+        use serde::ser::Error;
+
+        let mut result = None;
+        self.with(|value| {
+            result = Some(value.serialize(serializer));
+            Ok(())
+        })
+        .map_err(|e| S::Error::custom(e.to_string()))?;
+        result.expect("closure ran")
+    }
+}
+
+impl<'de, T: Zeroize + serde::Deserialize<'de> + Send + 'static> serde::Deserialize<'de>
+    for Secret<T>
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Secret::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CryptoError;
+    use alloc::vec::Vec;
+
+    // The registry is process-wide and tests run in parallel, so these tests
+    // wipe individual secrets or use a private registry instead of panic_wipe()
+
+    #[test]
+    fn test_secret_use_and_wipe() {
+        let secret = Secret::new([7u8; 32]);
+        assert_eq!(secret.with(|k| Ok(k[0])).unwrap(), 7);
+
+        secret.wipe();
+        assert!(secret.is_wiped());
+        assert!(matches!(secret.with(|k| Ok(k[0])), Err(CryptoError::Wiped)));
+        assert_eq!(format!("{:?}", secret), "Secret([WIPED])");
+    }
+
+    #[test]
+    fn test_clone_is_independent() {
+        let a = Secret::new(Vec::from([1u8, 2, 3]));
+        let b = a.clone();
+        a.wipe();
+        assert_eq!(b.with(|v| Ok(v.clone())).unwrap(), [1, 2, 3]);
+
+        let c = a.clone();
+        assert!(c.is_wiped());
+    }
+
+    #[test]
+    fn test_registry_wipes_live_secrets_and_runs_hooks() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let registry = SecretRegistry::default();
+        let keep = Arc::new(SecretCell {
+            value: Mutex::new(Some([1u8; 32])),
+        });
+        let dropped = Arc::new(SecretCell {
+            value: Mutex::new(Some([2u8; 32])),
+        });
+        let keep_handle: Arc<dyn Wipe> = keep.clone();
+        let dropped_handle: Arc<dyn Wipe> = dropped.clone();
+        registry.register(Arc::downgrade(&keep_handle));
+        registry.register(Arc::downgrade(&dropped_handle));
+        drop((dropped, dropped_handle, keep_handle));
+        assert_eq!(registry.live_count(), 1);
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let hook_calls = calls.clone();
+        registry.add_wipe_hook(Arc::new(move || {
+            hook_calls.fetch_add(1, Ordering::SeqCst);
+        }));
+
+        let report = registry.wipe_all();
+        assert_eq!(
+            report,
+            WipeReport {
+                secrets: 1,
+                hooks: 1
+            }
+        );
+        assert!(lock(&keep.value).is_none());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_hooks_may_reenter_the_registry() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let registry = Arc::new(SecretRegistry::default());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (hook_registry, hook_calls) = (registry.clone(), calls.clone());
+        registry.add_wipe_hook(Arc::new(move || {
+            hook_calls.fetch_add(1, Ordering::SeqCst);
+            hook_registry.add_wipe_hook(Arc::new(|| {}));
+            assert_eq!(hook_registry.wipe_all().hooks, 0);
+        }));
+
+        assert_eq!(registry.wipe_all().hooks, 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // The hook added during the wipe runs next time
+        assert_eq!(registry.wipe_all().hooks, 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_registry_prunes_dropped_handles() {
+        let registry = SecretRegistry::default();
+        let keep: Arc<dyn Wipe> = Arc::new(SecretCell {
+            value: Mutex::new(Some([1u8; 32])),
+        });
+        registry.register(Arc::downgrade(&keep));
+
+        for _ in 0..10 * MIN_PRUNE_LEN {
+            let dropped: Arc<dyn Wipe> = Arc::new(SecretCell {
+                value: Mutex::new(Some([2u8; 32])),
+            });
+            registry.register(Arc::downgrade(&dropped));
+        }
+        assert!(lock(&registry.secrets).list.len() <= MIN_PRUNE_LEN);
+
+        assert_eq!(registry.live_count(), 1);
+        assert_eq!(lock(&registry.secrets).list.len(), 1);
+    }
+}
@@ -0,0 +1,187 @@
+//! Streaming AEAD
+//!
+//! Chunked XChaCha20-Poly1305 using the STREAM construction (big-endian
+//! 32-bit counter + last-block flag). Every chunk is authenticated on its own,
+//! so large files never need to sit in one AEAD call, and truncation or
+//! reordering of chunks is detected.
+//!
+//! Wire format: `version (1) || nonce_prefix (19) || chunk_1 || ... || chunk_n`,
+//! where each chunk is at most `CHUNK_SIZE + TAG_SIZE` bytes and only the
+//! final chunk is sealed with the last-block flag.
+
+use crate::encryption::{KEY_SIZE, TAG_SIZE};
+use crate::{CryptoError, CryptoResult};
+use alloc::{string::ToString, vec::Vec};
+use chacha20poly1305::aead::stream::{DecryptorBE32, EncryptorBE32};
+use chacha20poly1305::aead::{KeyInit, Payload};
+use chacha20poly1305::XChaCha20Poly1305;
+use rand_core::CryptoRngCore;
+
+/// Stream format version
+pub const STREAM_VERSION: u8 = 1;
+
+/// Plaintext bytes per chunk (64 KiB)
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Nonce prefix size (24-byte XChaCha nonce minus 5 bytes of counter/flag)
+pub const NONCE_PREFIX_SIZE: usize = 19;
+
+/// Header size (version + nonce prefix)
+pub const HEADER_SIZE: usize = 1 + NONCE_PREFIX_SIZE;
+
+/// Size of a sealed stream for a given plaintext length
+pub fn sealed_len(plaintext_len: usize) -> usize {
+    let chunks = plaintext_len.div_ceil(CHUNK_SIZE).max(1);
+    HEADER_SIZE + plaintext_len + chunks * TAG_SIZE
+}
+
+/// Encrypt a whole buffer as a chunked stream
+///
+/// # Arguments
+/// * `key` - 256-bit stream key
+/// * `plaintext` - Data to encrypt (may be empty)
+/// * `rng` - Source for the nonce prefix
+///
+/// # Returns
+/// Header followed by the sealed chunks
+pub fn seal(
+    key: &[u8; KEY_SIZE],
+    plaintext: &[u8],
+    rng: &mut impl CryptoRngCore,
+) -> CryptoResult<Vec<u8>> {
+    let mut nonce_prefix = [0u8; NONCE_PREFIX_SIZE];
+    rng.fill_bytes(&mut nonce_prefix);
+
+    let mut header = [0u8; HEADER_SIZE];
+    header[0] = STREAM_VERSION;
+    header[1..].copy_from_slice(&nonce_prefix);
+
+    let cipher = XChaCha20Poly1305::new(key.into());
+    let mut encryptor = EncryptorBE32::from_aead(cipher, nonce_prefix.as_ref().into());
+
+    let mut out = Vec::with_capacity(sealed_len(plaintext.len()));
+    out.extend_from_slice(&header);
+
+    let mut chunks = plaintext.chunks(CHUNK_SIZE).peekable();
+    if chunks.peek().is_none() {
+        let sealed = encryptor
+            .encrypt_last(Payload {
+                msg: &[],
+                aad: &header,
+            })
+            .map_err(|_| CryptoError::EncryptionError("Stream chunk failed".to_string()))?;
+        out.extend_from_slice(&sealed);
+        return Ok(out);
+    }
+
+    while let Some(chunk) = chunks.next() {
+        let payload = Payload {
+            msg: chunk,
+            aad: &header,
+        };
+        if chunks.peek().is_some() {
+            let sealed = encryptor
+                .encrypt_next(payload)
+                .map_err(|_| CryptoError::EncryptionError("Stream chunk failed".to_string()))?;
+            out.extend_from_slice(&sealed);
+        } else {
+            let sealed = encryptor
+                .encrypt_last(payload)
+                .map_err(|_| CryptoError::EncryptionError("Stream chunk failed".to_string()))?;
+            out.extend_from_slice(&sealed);
+            break;
+        }
+    }
+
+    Ok(out)
+}
+
+/// Decrypt a chunked stream produced by [`seal`]
+///
+/// Fails if any chunk was modified, reordered, dropped or if the stream
+/// was truncated.
+pub fn open(key: &[u8; KEY_SIZE], sealed: &[u8]) -> CryptoResult<Vec<u8>> {
+    if sealed.len() < HEADER_SIZE + TAG_SIZE {
+        return Err(CryptoError::DecryptionError("Stream too short".to_string()));
+    }
+
+    let (header, body) = sealed.split_at(HEADER_SIZE);
+    if header[0] != STREAM_VERSION {
+        return Err(CryptoError::DecryptionError(
+            "Invalid stream version".to_string(),
+        ));
+    }
+
+    let cipher = XChaCha20Poly1305::new(key.into());
+    let mut decryptor = DecryptorBE32::from_aead(cipher, header[1..].into());
+
+    let mut out = Vec::with_capacity(body.len());
+    let mut chunks = body.chunks(CHUNK_SIZE + TAG_SIZE).peekable();
+
+    while let Some(chunk) = chunks.next() {
+        let payload = Payload {
+            msg: chunk,
+            aad: header,
+        };
+        if chunks.peek().is_some() {
+            let opened = decryptor
+                .decrypt_next(payload)
+                .map_err(|_| CryptoError::DecryptionError("Stream chunk failed".to_string()))?;
+            out.extend_from_slice(&opened);
+        } else {
+            let opened = decryptor
+                .decrypt_last(payload)
+                .map_err(|_| CryptoError::DecryptionError("Stream chunk failed".to_string()))?;
+            out.extend_from_slice(&opened);
+            break;
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_roundtrip_sizes() {
+        let key = [5u8; KEY_SIZE];
+        for len in [
+            0,
+            1,
+            CHUNK_SIZE - 1,
+            CHUNK_SIZE,
+            CHUNK_SIZE + 1,
+            3 * CHUNK_SIZE,
+        ] {
+            let plaintext = vec![0x5Au8; len];
+            let sealed = seal(&key, &plaintext, &mut rand::thread_rng()).unwrap();
+            assert_eq!(sealed.len(), sealed_len(len));
+            assert_eq!(open(&key, &sealed).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn test_stream_truncation_detected() {
+        let key = [6u8; KEY_SIZE];
+        let plaintext = vec![1u8; 2 * CHUNK_SIZE + 10];
+        let sealed = seal(&key, &plaintext, &mut rand::thread_rng()).unwrap();
+
+        // Drop the final chunk: the previous chunk is not flagged as last
+        let truncated = &sealed[..HEADER_SIZE + 2 * (CHUNK_SIZE + TAG_SIZE)];
+        assert!(open(&key, truncated).is_err());
+    }
+
+    #[test]
+    fn test_stream_tampering_detected() {
+        let key = [7u8; KEY_SIZE];
+        let mut sealed = seal(&key, b"attachment bytes", &mut rand::thread_rng()).unwrap();
+        let last = sealed.len() - 1;
+        sealed[last] ^= 0x01;
+        assert!(open(&key, &sealed).is_err());
+
+        let sealed = seal(&key, b"attachment bytes", &mut rand::thread_rng()).unwrap();
+        assert!(open(&[8u8; KEY_SIZE], &sealed).is_err());
+    }
+}
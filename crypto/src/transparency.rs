@@ -0,0 +1,622 @@
+//! Key Transparency Log
+//!
+//! Append-only Merkle tree (RFC 9162 hashing) of `(username, verifying_key, epoch)`
+//! entries. The directory publishes signed tree heads; clients check that
+//! every key they are shown is included in the tree and that every new tree
+//! head is consistent with the last one they saw. A directory that shows
+//! different keys to different users must then fork the log, which is
+//! detectable as soon as two clients compare tree heads.
+//!
+//! Hashing:
+//! - leaf: `SHA-256(0x00 || entry)`
+//! - node: `SHA-256(0x01 || left || right)`
+
+use crate::clock::Clock;
+use crate::key_exchange::{verify_signature, KeyPair, SIGNATURE_SIZE};
+use crate::{CryptoError, CryptoResult};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// SHA-256 node hash
+pub type Hash = [u8; 32];
+
+/// Domain separator for tree head signatures
+const TREE_HEAD_DOMAIN: &[u8] = b"chakchat_tree_head_v1";
+
+/// Key binding published in the log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Username (e.g., "alice@chakchat")
+    pub username: String,
+
+    /// Ed25519 identity key
+    #[serde(with = "crate::encoding::base64_array")]
+    pub verifying_key: [u8; 32],
+
+    /// Key epoch (incremented on every key change)
+    pub epoch: u64,
+}
+
+/// Signed commitment to the log at a given size
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedTreeHead {
+    /// Number of entries
+    pub tree_size: u64,
+
+    /// Merkle root over all entries
+    #[serde(with = "crate::encoding::base64_array")]
+    pub root_hash: Hash,
+
+    /// Signing time (Unix milliseconds)
+    pub timestamp: i64,
+
+    /// Log signature over size, timestamp and root
+    #[serde(with = "crate::encoding::base64_array")]
+    pub signature: [u8; SIGNATURE_SIZE],
+}
+
+/// Proof that an entry is in the tree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InclusionProof {
+    /// Position of the entry
+    pub leaf_index: u64,
+
+    /// Tree size the proof is for
+    pub tree_size: u64,
+
+    /// Sibling hashes from leaf to root
+    #[serde(with = "crate::encoding::base64_array_vec")]
+    pub path: Vec<Hash>,
+}
+
+impl LogEntry {
+    /// Canonical leaf encoding
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(8 + self.username.len() + 32 + 8);
+        out.extend_from_slice(&(self.username.len() as u64).to_be_bytes());
+        out.extend_from_slice(self.username.as_bytes());
+        out.extend_from_slice(&self.verifying_key);
+        out.extend_from_slice(&self.epoch.to_be_bytes());
+        out
+    }
+
+    /// Leaf hash of this entry
+    pub fn leaf_hash(&self) -> Hash {
+        leaf_hash(&self.to_bytes())
+    }
+}
+
+impl SignedTreeHead {
+    fn signed_bytes(tree_size: u64, timestamp: i64, root_hash: &Hash) -> Vec<u8> {
+        let mut out = Vec::with_capacity(TREE_HEAD_DOMAIN.len() + 8 + 8 + 32);
+        out.extend_from_slice(TREE_HEAD_DOMAIN);
+        out.extend_from_slice(&tree_size.to_be_bytes());
+        out.extend_from_slice(&timestamp.to_be_bytes());
+        out.extend_from_slice(root_hash);
+        out
+    }
+
+    /// Verify the log's signature
+    pub fn verify(&self, log_key: &[u8; 32]) -> CryptoResult<()> {
+        let data = Self::signed_bytes(self.tree_size, self.timestamp, &self.root_hash);
+        verify_signature(log_key, &data, &self.signature)
+    }
+}
+
+/// Hash a leaf
+pub fn leaf_hash(data: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([0x00]);
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+/// Hash two children
+pub fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([0x01]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Largest power of two strictly less than `n` (n >= 2)
+fn split_point(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+
+/// Root of a list of leaf hashes
+fn subtree_root(leaves: &[Hash]) -> Hash {
+    match leaves.len() {
+        0 => Sha256::digest([]).into(),
+        1 => leaves[0],
+        n => {
+            let k = split_point(n);
+            node_hash(&subtree_root(&leaves[..k]), &subtree_root(&leaves[k..]))
+        }
+    }
+}
+
+/// RFC 9162 PATH(m, D[n])
+fn inclusion_path(m: usize, leaves: &[Hash]) -> Vec<Hash> {
+    let n = leaves.len();
+    if n <= 1 {
+        return Vec::new();
+    }
+    let k = split_point(n);
+    if m < k {
+        let mut path = inclusion_path(m, &leaves[..k]);
+        path.push(subtree_root(&leaves[k..]));
+        path
+    } else {
+        let mut path = inclusion_path(m - k, &leaves[k..]);
+        path.push(subtree_root(&leaves[..k]));
+        path
+    }
+}
+
+/// RFC 9162 SUBPROOF(m, D[n], b)
+fn consistency_subproof(m: usize, leaves: &[Hash], complete: bool) -> Vec<Hash> {
+    let n = leaves.len();
+    if m == n {
+        return if complete {
+            Vec::new()
+        } else {
+            alloc::vec![subtree_root(leaves)]
+        };
+    }
+    let k = split_point(n);
+    if m <= k {
+        let mut proof = consistency_subproof(m, &leaves[..k], complete);
+        proof.push(subtree_root(&leaves[k..]));
+        proof
+    } else {
+        let mut proof = consistency_subproof(m - k, &leaves[k..], false);
+        proof.push(subtree_root(&leaves[..k]));
+        proof
+    }
+}
+
+/// Verify an inclusion proof (RFC 9162 section 2.1.3.2)
+pub fn verify_inclusion(
+    leaf_hash: &Hash,
+    leaf_index: u64,
+    tree_size: u64,
+    path: &[Hash],
+    root_hash: &Hash,
+) -> CryptoResult<()> {
+    if leaf_index >= tree_size {
+        return Err(CryptoError::InvalidProof(
+            "Leaf index out of range".to_string(),
+        ));
+    }
+
+    let mut fn_ = leaf_index;
+    let mut sn = tree_size - 1;
+    let mut r = *leaf_hash;
+
+    for p in path {
+        if sn == 0 {
+            return Err(CryptoError::InvalidProof(
+                "Inclusion path too long".to_string(),
+            ));
+        }
+        if fn_ & 1 == 1 || fn_ == sn {
+            r = node_hash(p, &r);
+            if fn_ & 1 == 0 {
+                while fn_ & 1 == 0 && fn_ != 0 {
+                    fn_ >>= 1;
+                    sn >>= 1;
+                }
+            }
+        } else {
+            r = node_hash(&r, p);
+        }
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+
+    if sn != 0 || r != *root_hash {
+        return Err(CryptoError::InvalidProof(
+            "Inclusion proof mismatch".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Verify a consistency proof (RFC 9162 section 2.1.4.2)
+pub fn verify_consistency(
+    old_size: u64,
+    new_size: u64,
+    old_root: &Hash,
+    new_root: &Hash,
+    proof: &[Hash],
+) -> CryptoResult<()> {
+    if old_size > new_size {
+        return Err(CryptoError::InvalidProof("Tree shrank".to_string()));
+    }
+    if old_size == new_size {
+        return if proof.is_empty() && old_root == new_root {
+            Ok(())
+        } else {
+            Err(CryptoError::InvalidProof(
+                "Same-size roots differ".to_string(),
+            ))
+        };
+    }
+    if old_size == 0 {
+        return if proof.is_empty() {
+            Ok(())
+        } else {
+            Err(CryptoError::InvalidProof(
+                "Unexpected proof for empty tree".to_string(),
+            ))
+        };
+    }
+
+    let mut proof = proof.iter();
+    let mut fn_ = old_size - 1;
+    let mut sn = new_size - 1;
+
+    let (mut fr, mut sr) = if old_size.is_power_of_two() {
+        (*old_root, *old_root)
+    } else {
+        let first = proof
+            .next()
+            .ok_or_else(|| CryptoError::InvalidProof("Consistency proof too short".to_string()))?;
+        (*first, *first)
+    };
+    if !old_size.is_power_of_two() || fn_ & 1 == 1 {
+        while fn_ & 1 == 1 {
+            fn_ >>= 1;
+            sn >>= 1;
+        }
+    }
+
+    for c in proof {
+        if sn == 0 {
+            return Err(CryptoError::InvalidProof(
+                "Consistency proof too long".to_string(),
+            ));
+        }
+        if fn_ & 1 == 1 || fn_ == sn {
+            fr = node_hash(c, &fr);
+            sr = node_hash(c, &sr);
+            if fn_ & 1 == 0 {
+                while fn_ & 1 == 0 && fn_ != 0 {
+                    fn_ >>= 1;
+                    sn >>= 1;
+                }
+            }
+        } else {
+            sr = node_hash(&sr, c);
+        }
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+
+    if sn != 0 || fr != *old_root || sr != *new_root {
+        return Err(CryptoError::InvalidProof(
+            "Consistency proof mismatch".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// In-process log server
+///
+/// Stand-in for the directory-side log (DHT or identity-service).
+pub struct TransparencyLog {
+    signing_key: KeyPair,
+    entries: Vec<LogEntry>,
+    leaves: Vec<Hash>,
+}
+
+impl TransparencyLog {
+    /// Create empty log signed by `signing_key`
+    pub fn new(signing_key: KeyPair) -> Self {
+        TransparencyLog {
+            signing_key,
+            entries: Vec::new(),
+            leaves: Vec::new(),
+        }
+    }
+
+    /// Log's public verifying key
+    pub fn verifying_key(&self) -> &[u8; 32] {
+        self.signing_key.get_verifying_key()
+    }
+
+    /// Append an entry and return its index
+    pub fn append(&mut self, entry: LogEntry) -> u64 {
+        self.leaves.push(entry.leaf_hash());
+        self.entries.push(entry);
+        (self.leaves.len() - 1) as u64
+    }
+
+    /// Current number of entries
+    pub fn size(&self) -> u64 {
+        self.leaves.len() as u64
+    }
+
+    /// Entry at `index`
+    pub fn entry(&self, index: u64) -> Option<&LogEntry> {
+        self.entries.get(index as usize)
+    }
+
+    /// Latest entry for `username` with its index
+    pub fn lookup(&self, username: &str) -> Option<(u64, &LogEntry)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, e)| e.username == username)
+            .map(|(i, e)| (i as u64, e))
+    }
+
+    /// Merkle root of the first `tree_size` entries
+    pub fn root_hash(&self, tree_size: u64) -> CryptoResult<Hash> {
+        Ok(subtree_root(self.prefix(tree_size)?))
+    }
+
+    /// Sign the current tree head
+    pub fn signed_tree_head(&self, clock: &dyn Clock) -> CryptoResult<SignedTreeHead> {
+        let tree_size = self.size();
+        let root_hash = self.root_hash(tree_size)?;
+        let timestamp = clock.now_millis();
+        let signature = self.signing_key.sign(&SignedTreeHead::signed_bytes(
+            tree_size, timestamp, &root_hash,
+        ))?;
+
+        Ok(SignedTreeHead {
+            tree_size,
+            root_hash,
+            timestamp,
+            signature,
+        })
+    }
+
+    /// Inclusion proof for entry `leaf_index` in the tree of size `tree_size`
+    pub fn inclusion_proof(&self, leaf_index: u64, tree_size: u64) -> CryptoResult<InclusionProof> {
+        let leaves = self.prefix(tree_size)?;
+        if leaf_index >= tree_size {
+            return Err(CryptoError::InvalidProof(
+                "Leaf index out of range".to_string(),
+            ));
+        }
+
+        Ok(InclusionProof {
+            leaf_index,
+            tree_size,
+            path: inclusion_path(leaf_index as usize, leaves),
+        })
+    }
+
+    /// Consistency proof between two tree sizes
+    pub fn consistency_proof(&self, old_size: u64, new_size: u64) -> CryptoResult<Vec<Hash>> {
+        let leaves = self.prefix(new_size)?;
+        if old_size > new_size {
+            return Err(CryptoError::InvalidProof("Tree shrank".to_string()));
+        }
+        if old_size == 0 || old_size == new_size {
+            return Ok(Vec::new());
+        }
+        Ok(consistency_subproof(old_size as usize, leaves, true))
+    }
+
+    fn prefix(&self, tree_size: u64) -> CryptoResult<&[Hash]> {
+        self.leaves
+            .get(..tree_size as usize)
+            .ok_or_else(|| CryptoError::InvalidProof("Tree size beyond log".to_string()))
+    }
+}
+
+/// Client-side verifier that remembers the last tree head it accepted
+#[derive(Debug, Clone)]
+pub struct TransparencyVerifier {
+    log_key: [u8; 32],
+    last_head: Option<SignedTreeHead>,
+}
+
+impl TransparencyVerifier {
+    /// Create verifier trusting `log_key`
+    pub fn new(log_key: [u8; 32]) -> Self {
+        TransparencyVerifier {
+            log_key,
+            last_head: None,
+        }
+    }
+
+    /// Last accepted tree head
+    pub fn last_head(&self) -> Option<&SignedTreeHead> {
+        self.last_head.as_ref()
+    }
+
+    /// Accept a new tree head if it is signed and consistent with the last one
+    ///
+    /// # Arguments
+    /// * `head` - Tree head from the directory
+    /// * `consistency_proof` - Proof from the last accepted size to `head.tree_size`
+    pub fn update(&mut self, head: SignedTreeHead, consistency_proof: &[Hash]) -> CryptoResult<()> {
+        head.verify(&self.log_key)?;
+
+        if let Some(last) = &self.last_head {
+            if head.tree_size < last.tree_size {
+                return Err(CryptoError::InvalidProof(
+                    "Tree head rolled back".to_string(),
+                ));
+            }
+            verify_consistency(
+                last.tree_size,
+                head.tree_size,
+                &last.root_hash,
+                &head.root_hash,
+                consistency_proof,
+            )?;
+        }
+
+        self.last_head = Some(head);
+        Ok(())
+    }
+
+    /// Check that `entry` is in the tree committed to by the last accepted head
+    pub fn verify_entry(&self, entry: &LogEntry, proof: &InclusionProof) -> CryptoResult<()> {
+        let head = self
+            .last_head
+            .as_ref()
+            .ok_or_else(|| CryptoError::InvalidProof("No tree head accepted yet".to_string()))?;
+
+        if proof.tree_size != head.tree_size {
+            return Err(CryptoError::InvalidProof(
+                "Proof is for a different tree size".to_string(),
+            ));
+        }
+
+        verify_inclusion(
+            &entry.leaf_hash(),
+            proof.leaf_index,
+            proof.tree_size,
+            &proof.path,
+            &head.root_hash,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+
+    fn entry(name: &str, key: u8, epoch: u64) -> LogEntry {
+        LogEntry {
+            username: name.to_string(),
+            verifying_key: [key; 32],
+            epoch,
+        }
+    }
+
+    fn log_with(n: usize) -> TransparencyLog {
+        let mut log = TransparencyLog::new(KeyPair::generate().unwrap());
+        for i in 0..n {
+            log.append(entry(&alloc::format!("user{}", i), i as u8, 0));
+        }
+        log
+    }
+
+    #[test]
+    fn test_inclusion_proofs_all_sizes() {
+        let log = log_with(17);
+        for size in 1..=17u64 {
+            let root = log.root_hash(size).unwrap();
+            for index in 0..size {
+                let proof = log.inclusion_proof(index, size).unwrap();
+                let leaf = log.entry(index).unwrap().leaf_hash();
+                verify_inclusion(&leaf, index, size, &proof.path, &root).unwrap();
+
+                let wrong = entry("mallory", 0xEE, 0).leaf_hash();
+                assert!(verify_inclusion(&wrong, index, size, &proof.path, &root).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_consistency_proofs_all_sizes() {
+        let log = log_with(17);
+        for new_size in 1..=17u64 {
+            let new_root = log.root_hash(new_size).unwrap();
+            for old_size in 1..=new_size {
+                let old_root = log.root_hash(old_size).unwrap();
+                let proof = log.consistency_proof(old_size, new_size).unwrap();
+                verify_consistency(old_size, new_size, &old_root, &new_root, &proof).unwrap();
+
+                if old_size < new_size {
+                    let bogus = [0xAB; 32];
+                    assert!(
+                        verify_consistency(old_size, new_size, &bogus, &new_root, &proof).is_err()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rfc_leaf_and_empty_hashes() {
+        // RFC 6962: MTH({}) = SHA-256(""), MTH({d}) = SHA-256(0x00 || d)
+        assert_eq!(
+            hex::encode(subtree_root(&[])),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex::encode(leaf_hash(b"")),
+            "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d"
+        );
+    }
+
+    #[test]
+    fn test_verifier_tracks_tree_heads() {
+        let mut log = log_with(3);
+        let clock = FixedClock(1_000);
+        let mut verifier = TransparencyVerifier::new(*log.verifying_key());
+
+        let head1 = log.signed_tree_head(&clock).unwrap();
+        verifier.update(head1.clone(), &[]).unwrap();
+
+        let index = log.append(entry("alice@chakchat", 0xA1, 1));
+        let head2 = log.signed_tree_head(&clock).unwrap();
+        let proof = log
+            .consistency_proof(head1.tree_size, head2.tree_size)
+            .unwrap();
+        verifier.update(head2.clone(), &proof).unwrap();
+
+        let (found, alice) = log.lookup("alice@chakchat").unwrap();
+        assert_eq!(found, index);
+        let inclusion = log.inclusion_proof(index, head2.tree_size).unwrap();
+        verifier.verify_entry(alice, &inclusion).unwrap();
+
+        // Rolling back to an older head is rejected
+        assert!(verifier.update(head1, &[]).is_err());
+    }
+
+    #[test]
+    fn test_split_view_detected() {
+        let log_key = KeyPair::generate().unwrap();
+        let key_bytes = *log_key.get_verifying_key();
+        let clock = FixedClock(1_000);
+
+        // Directory keeps two forks and shows each to a different user
+        let mut honest = TransparencyLog::new(log_key.clone());
+        let mut forked = TransparencyLog::new(log_key);
+        for log in [&mut honest, &mut forked] {
+            log.append(entry("bob@chakchat", 0xB0, 0));
+        }
+        honest.append(entry("alice@chakchat", 0xA1, 0));
+        forked.append(entry("alice@chakchat", 0xEE, 0));
+
+        let mut verifier = TransparencyVerifier::new(key_bytes);
+        verifier
+            .update(honest.signed_tree_head(&clock).unwrap(), &[])
+            .unwrap();
+
+        forked.append(entry("carol@chakchat", 0xC0, 0));
+        let forked_head = forked.signed_tree_head(&clock).unwrap();
+        let proof = forked.consistency_proof(2, 3).unwrap();
+        assert!(verifier.update(forked_head, &proof).is_err());
+    }
+
+    #[test]
+    fn test_forged_tree_head_rejected() {
+        let log = log_with(2);
+        let mut head = log.signed_tree_head(&FixedClock(0)).unwrap();
+        head.root_hash[0] ^= 1;
+
+        let mut verifier = TransparencyVerifier::new(*log.verifying_key());
+        assert!(matches!(
+            verifier.update(head, &[]),
+            Err(CryptoError::SignatureVerificationFailed)
+        ));
+    }
+}
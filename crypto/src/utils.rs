@@ -3,9 +3,18 @@
 //! Helper functions for hashing, key derivation, and secure operations.
 
 use crate::CryptoError;
+use alloc::format;
+#[cfg(feature = "std")]
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "std")]
+use core::sync::atomic::{compiler_fence, Ordering};
+use hmac::Mac;
+#[cfg(feature = "std")]
 use rand::RngCore;
 use scrypt::{scrypt, Params};
 use sha2::{Digest, Sha256, Sha512};
+use subtle::{Choice, ConstantTimeEq};
+#[cfg(feature = "std")]
 use zeroize::Zeroize;
 
 /// Hash data with SHA-256
@@ -35,31 +44,27 @@ pub fn hash_sha512(data: &[u8]) -> [u8; 64] {
 /// - r=8 (memory cost)
 /// - p=1 (parallelization)
 /// - 32-byte output
-pub fn derive_key_from_password(
-    password: &[u8],
-    salt: &[u8; 32],
-) -> Result<[u8; 32], CryptoError> {
-    let params = Params::new(14, 8, 1, 32).map_err(|e| {
-        CryptoError::KeyDerivationError(format!("Invalid scrypt params: {}", e))
-    })?;
+pub fn derive_key_from_password(password: &[u8], salt: &[u8; 32]) -> Result<[u8; 32], CryptoError> {
+    let params = Params::new(14, 8, 1)
+        .map_err(|e| CryptoError::KeyDerivationError(format!("Invalid scrypt params: {}", e)))?;
 
     let mut key = [0u8; 32];
-    scrypt(password, salt, &params, &mut key).map_err(|e| {
-        CryptoError::KeyDerivationError(format!("Scrypt failed: {}", e))
-    })?;
+    scrypt(password, salt, &params, &mut key)
+        .map_err(|e| CryptoError::KeyDerivationError(format!("Scrypt failed: {}", e)))?;
 
     Ok(key)
 }
 
 /// Generate random bytes
+#[cfg(feature = "std")]
 pub fn random_bytes(size: usize) -> Result<Vec<u8>, CryptoError> {
     let mut bytes = vec![0u8; size];
-    rand::thread_rng()
-        .fill_bytes(&mut bytes);
+    rand::thread_rng().fill_bytes(&mut bytes);
     Ok(bytes)
 }
 
 /// Generate random array
+#[cfg(feature = "std")]
 pub fn random_array<const N: usize>() -> Result<[u8; N], CryptoError> {
     let mut array = [0u8; N];
     rand::thread_rng().fill_bytes(&mut array);
@@ -67,40 +72,38 @@ pub fn random_array<const N: usize>() -> Result<[u8; N], CryptoError> {
 }
 
 /// Secure memory comparison (constant-time)
+///
+/// Runs in time that depends only on `a.len()`, so pass the untrusted value
+/// as `a` and the secret or expected value as `b`. A length mismatch is folded
+/// into the result instead of returning early.
 pub fn constant_time_compare(a: &[u8], b: &[u8]) -> bool {
-    if a.len() != b.len() {
-        return false;
-    }
+    let same_len = (a.len() as u64).ct_eq(&(b.len() as u64));
 
-    let mut result = 0u8;
-    for i in 0..a.len() {
-        result |= a[i] ^ b[i];
+    let mut equal = Choice::from(1);
+    for (i, x) in a.iter().enumerate() {
+        // Compare against zero once `b` runs out; `same_len` already failed
+        let y = b.get(i).copied().unwrap_or(0);
+        equal &= x.ct_eq(&y);
     }
 
-    result == 0
+    (same_len & equal).into()
 }
 
 /// Compute HMAC-SHA256
 pub fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
-    use sha2::Mac;
-    use sha2::digest::Mac as DigestMac;
-
     type HmacSha256 = hmac::Hmac<Sha256>;
-    use hmac::Mac as HmacMac;
 
     let mut mac = HmacSha256::new_from_slice(key).expect("key length valid");
     mac.update(data);
     let result = mac.finalize();
 
     let mut hash = [0u8; 32];
-    hash.copy_from_slice(result.as_ref());
+    hash.copy_from_slice(&result.into_bytes());
     hash
 }
 
 /// Compute HMAC-SHA512
 pub fn hmac_sha512(key: &[u8], data: &[u8]) -> [u8; 64] {
-    use hmac::Mac as HmacMac;
-
     type HmacSha512 = hmac::Hmac<Sha512>;
 
     let mut mac = HmacSha512::new_from_slice(key).expect("key length valid");
@@ -108,33 +111,39 @@ pub fn hmac_sha512(key: &[u8], data: &[u8]) -> [u8; 64] {
     let result = mac.finalize();
 
     let mut hash = [0u8; 64];
-    hash.copy_from_slice(result.as_ref());
+    hash.copy_from_slice(&result.into_bytes());
     hash
 }
 
 /// Gutmann 7-pass secure memory overwrite
 ///
 /// Overwrites memory with random and deterministic patterns
-/// to prevent forensic recovery.
+/// to prevent forensic recovery. Every pass is fenced so the compiler cannot
+/// drop it as a dead store, and the final pass writes zeros with volatile
+/// stores (`zeroize`), so the buffer always ends up all-zero.
+#[cfg(feature = "std")]
 pub fn secure_wipe(buffer: &mut [u8]) {
     // Pass 1-3: Deterministic patterns
-    for byte in buffer.iter_mut() {
-        *byte = 0x00;
-    }
-    for byte in buffer.iter_mut() {
-        *byte = 0xFF;
-    }
-    for byte in buffer.iter_mut() {
-        *byte = 0xAA;
+    for pattern in [0x00, 0xFF, 0xAA] {
+        buffer.fill(pattern);
+        fence(buffer);
     }
 
     // Pass 4-6: Random patterns
     for _ in 0..3 {
         rand::thread_rng().fill_bytes(buffer);
+        fence(buffer);
     }
 
-    // Pass 7: Final random
-    rand::thread_rng().fill_bytes(buffer);
+    // Pass 7: Volatile zero
+    buffer.zeroize();
+}
+
+/// Make the preceding writes observable and keep them in program order
+#[cfg(feature = "std")]
+fn fence(buffer: &mut [u8]) {
+    core::hint::black_box(&mut *buffer);
+    compiler_fence(Ordering::SeqCst);
 }
 
 #[cfg(test)]
@@ -201,6 +210,9 @@ mod tests {
         assert!(constant_time_compare(a, b));
         assert!(!constant_time_compare(a, c));
         assert!(!constant_time_compare(a, b"short"));
+        assert!(!constant_time_compare(b"secretlonger", a));
+        assert!(!constant_time_compare(b"secret\0\0", b"secret"));
+        assert!(constant_time_compare(b"", b""));
     }
 
     #[test]
@@ -230,7 +242,7 @@ mod tests {
         // Buffer should be modified
         assert_ne!(buffer, original);
 
-        // After wipe, should mostly be zeros (last pass)
-        // But not guaranteed due to random nature
+        // Last pass is all zeros
+        assert!(buffer.iter().all(|&b| b == 0));
     }
 }
@@ -0,0 +1,51 @@
+//! Shared Test Helpers
+//!
+//! Vector parsing and a replaying RNG for the integration tests that check
+//! against committed JSON vectors. Each test binary uses a subset.
+
+#![allow(dead_code)]
+
+use chakchat_crypto::KeyPair;
+use rand_core::{CryptoRng, RngCore};
+use serde_json::Value;
+
+/// Hex-decoded bytes of a vector field
+pub fn bytes(v: &Value, field: &str) -> Vec<u8> {
+    hex::decode(v[field].as_str().expect("hex string")).expect("valid hex")
+}
+
+/// Hex-decoded 32-byte vector field
+pub fn array32(v: &Value, field: &str) -> [u8; 32] {
+    bytes(v, field).try_into().expect("32 bytes")
+}
+
+/// Key pair from a vector's X25519 private key; the signing half is unused
+pub fn key_pair(v: &Value, field: &str) -> KeyPair {
+    KeyPair::from_bytes(&array32(v, field), &[0u8; 32]).unwrap()
+}
+
+/// RNG that hands out fixed bytes
+pub struct Replay(pub Vec<u8>);
+
+impl RngCore for Replay {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        assert!(dest.len() <= self.0.len(), "replay RNG exhausted");
+        dest.copy_from_slice(&self.0[..dest.len()]);
+        self.0.drain(..dest.len());
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for Replay {}
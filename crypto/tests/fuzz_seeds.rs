@@ -0,0 +1,293 @@
+//! Fuzz Seed Replay
+//!
+//! Runs every fuzz target over its committed seeds in `fuzz/seeds/<target>`,
+//! plus every truncation and single-bit flip of each seed, so the targets are
+//! exercised on plain `cargo test` without cargo-fuzz or nightly.
+//!
+//! Regenerate the seeds after a format change with:
+//!
+//! ```bash
+//! cargo test --test fuzz_seeds -- --ignored regenerate_seeds
+//! ```
+
+#[path = "../fuzz/src/lib.rs"]
+mod targets;
+
+use chakchat_crypto::attachment::{
+    decrypt_attachment, encrypt_attachment_with_rng, AttachmentPointer,
+};
+use chakchat_crypto::device::{DeviceCapabilities, DeviceCertificate, RevocationList};
+use chakchat_crypto::padding::{pad, PaddingPolicy};
+use chakchat_crypto::stream;
+use chakchat_crypto::transparency::{LogEntry, TransparencyLog};
+use chakchat_crypto::{KeyPair, SignedEnvelope};
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+use std::fs;
+use std::path::PathBuf;
+use targets::fixtures::{self, CONTEXT, NOW, RECIPIENT, SENDER, STREAM_KEY};
+
+/// Bit flips per seed are capped so large seeds stay fast
+const MAX_FLIPPED_BITS: usize = 4096;
+
+fn seed_dir(target: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("fuzz/seeds")
+        .join(target)
+}
+
+fn seeds(target: &str) -> Vec<Vec<u8>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(seed_dir(target))
+        .unwrap_or_else(|e| panic!("missing seeds for {}: {}", target, e))
+        .map(|entry| entry.expect("readable seed directory").path())
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no seeds for {}", target);
+    paths
+        .iter()
+        .map(|path| fs::read(path).expect("readable seed"))
+        .collect()
+}
+
+/// Run a target on every seed, each truncation and each single-bit flip
+fn replay(target: &str, run: fn(&[u8])) {
+    for seed in seeds(target) {
+        run(&seed);
+        for len in 0..seed.len() {
+            run(&seed[..len]);
+        }
+        for bit in 0..(seed.len() * 8).min(MAX_FLIPPED_BITS) {
+            let mut mutated = seed.clone();
+            mutated[bit / 8] ^= 1 << (bit % 8);
+            run(&mutated);
+        }
+    }
+}
+
+#[test]
+fn test_encrypted_message_seeds() {
+    replay("encrypted_message", targets::encrypted_message);
+
+    // Seeds must still match the fixed session, or they only cover the error paths
+    let message = bincode::deserialize(&seeds("encrypted_message")[0]).unwrap();
+    assert!(fixtures::session()
+        .decrypt_with(&message, &fixtures::clock())
+        .is_ok());
+}
+
+#[test]
+fn test_key_pair_seeds() {
+    replay("key_pair", targets::key_pair);
+}
+
+#[test]
+fn test_signed_envelope_seeds() {
+    replay("signed_envelope", targets::signed_envelope);
+
+    let envelope: SignedEnvelope = bincode::deserialize(&seeds("signed_envelope")[0]).unwrap();
+    let opened = envelope.open_with(
+        &fixtures::identity().verifying_key,
+        RECIPIENT,
+        CONTEXT,
+        &mut fixtures::session(),
+        &fixtures::clock(),
+    );
+    assert!(opened.is_ok());
+}
+
+#[test]
+fn test_stream_seeds() {
+    replay("stream", targets::stream);
+    assert!(stream::open(&STREAM_KEY, &seeds("stream")[0]).is_ok());
+}
+
+#[test]
+fn test_padding_seeds() {
+    replay("padding", targets::padding);
+}
+
+#[test]
+fn test_attachment_seeds() {
+    replay("attachment", targets::attachment);
+
+    let seed = &seeds("attachment")[0];
+    let mut ciphertext = seed.as_slice();
+    let pointer: AttachmentPointer = bincode::deserialize_from(&mut ciphertext).unwrap();
+    assert!(decrypt_attachment(ciphertext, &pointer).is_ok());
+}
+
+#[test]
+fn test_expiring_seeds() {
+    replay("expiring", targets::expiring);
+
+    let (mut store, _) = fixtures::expiring_store();
+    let blob = &seeds("expiring")[0];
+    assert!(store
+        .open(fixtures::EXPIRING_ID, blob, &fixtures::clock())
+        .is_ok());
+}
+
+#[test]
+fn test_transparency_seeds() {
+    replay("transparency", targets::transparency);
+}
+
+#[test]
+fn test_device_seeds() {
+    replay("device", targets::device);
+}
+
+#[cfg(feature = "pq")]
+#[test]
+fn test_post_quantum_seeds() {
+    replay("post_quantum", targets::post_quantum);
+}
+
+fn write_seeds(target: &str, seeds: &[Vec<u8>]) {
+    let dir = seed_dir(target);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (i, seed) in seeds.iter().enumerate() {
+        fs::write(dir.join(format!("seed-{}", i)), seed).unwrap();
+    }
+}
+
+#[test]
+#[ignore]
+fn regenerate_seeds() {
+    let mut rng = ChaCha20Rng::seed_from_u64(38);
+    let clock = fixtures::clock();
+    let identity = fixtures::identity();
+
+    let mut session = fixtures::session();
+    write_seeds(
+        "encrypted_message",
+        &[
+            bincode::serialize(&session.encrypt_with(b"hello", &mut rng, &clock).unwrap()).unwrap(),
+            bincode::serialize(
+                &session
+                    .encrypt_expiring_with(b"disappearing", NOW + 60_000, &mut rng, &clock)
+                    .unwrap(),
+            )
+            .unwrap(),
+        ],
+    );
+
+    write_seeds(
+        "key_pair",
+        &[
+            bincode::serialize(&identity).unwrap(),
+            bincode::serialize(&KeyPair::generate_with_rng(&mut rng).unwrap()).unwrap(),
+        ],
+    );
+
+    let envelope = SignedEnvelope::seal_with(
+        &identity,
+        SENDER,
+        RECIPIENT,
+        CONTEXT,
+        b"signed hello",
+        &mut session,
+        &mut rng,
+        &clock,
+    )
+    .unwrap();
+    write_seeds("signed_envelope", &[bincode::serialize(&envelope).unwrap()]);
+
+    write_seeds(
+        "stream",
+        &[
+            stream::seal(&STREAM_KEY, b"streamed attachment body", &mut rng).unwrap(),
+            stream::seal(&STREAM_KEY, b"", &mut rng).unwrap(),
+        ],
+    );
+
+    write_seeds(
+        "padding",
+        &[
+            pad(b"hello", PaddingPolicy::Padme, &mut rng).unwrap(),
+            pad(b"", PaddingPolicy::PowerOfTwo { min_bucket: 64 }, &mut rng).unwrap(),
+        ],
+    );
+
+    let attachment = encrypt_attachment_with_rng(
+        b"file contents",
+        "image/png",
+        Some(vec![0x89; 16]),
+        &mut rng,
+    )
+    .unwrap();
+    let mut attachment_seed = bincode::serialize(&attachment.pointer).unwrap();
+    attachment_seed.extend_from_slice(&attachment.ciphertext);
+    write_seeds("attachment", &[attachment_seed]);
+
+    write_seeds("expiring", &[fixtures::expiring_store().1]);
+
+    let mut log = TransparencyLog::new(fixtures::identity());
+    let entry = |i: u8| LogEntry {
+        username: format!("user{}", i),
+        verifying_key: [i; 32],
+        epoch: 1,
+    };
+    for i in 0..3 {
+        log.append(entry(i));
+    }
+    let old_head = log.signed_tree_head(&clock).unwrap();
+    for i in 3..5 {
+        log.append(entry(i));
+    }
+    let new_head = log.signed_tree_head(&clock).unwrap();
+    let consistency = log.consistency_proof(3, 5).unwrap();
+    let inclusion = log.inclusion_proof(2, 5).unwrap();
+    write_seeds(
+        "transparency",
+        &[
+            bincode::serialize(&(
+                3u64,
+                5u64,
+                old_head.root_hash,
+                new_head.root_hash,
+                consistency.clone(),
+            ))
+            .unwrap(),
+            bincode::serialize(&(entry(2), inclusion, new_head.root_hash)).unwrap(),
+            bincode::serialize(&(old_head, Vec::<[u8; 32]>::new(), new_head, consistency)).unwrap(),
+        ],
+    );
+
+    let laptop = KeyPair::generate_with_rng(&mut rng).unwrap();
+    let cert = DeviceCertificate::issue(
+        &identity,
+        SENDER,
+        "laptop",
+        &laptop,
+        DeviceCapabilities::MESSAGING,
+        NOW + 86_400_000,
+        &clock,
+    )
+    .unwrap();
+    let crl =
+        RevocationList::issue(&identity, SENDER, 1, vec!["phone".to_string()], &clock).unwrap();
+    write_seeds(
+        "device",
+        &[
+            bincode::serialize(&(vec![cert.clone()], Some(crl))).unwrap(),
+            bincode::serialize(&(vec![cert], None::<RevocationList>)).unwrap(),
+        ],
+    );
+
+    #[cfg(feature = "pq")]
+    {
+        use chakchat_crypto::post_quantum::PostQuantumKeyPair;
+        let keys = PostQuantumKeyPair::generate().unwrap();
+        let (_, ciphertext) = PostQuantumKeyPair::encapsulate(keys.public_key_bytes()).unwrap();
+        write_seeds(
+            "post_quantum",
+            &[
+                bincode::serialize(&keys).unwrap(),
+                keys.public_key_bytes().to_vec(),
+                ciphertext,
+            ],
+        );
+    }
+}
@@ -0,0 +1,163 @@
+//! HPKE Test Vectors
+//!
+//! Replays the RFC 9180 vectors in `tests/vectors/hpke.json` for
+//! DHKEM(X25519, HKDF-SHA256), HKDF-SHA256 with AES-256-GCM,
+//! ChaCha20Poly1305 and export-only, in base and auth modes. The sender's
+//! RNG replays `ikmE`, so the ephemeral key and `enc` are the vector's.
+
+mod common;
+
+use chakchat_crypto::hpke::{Hpke, HpkeAead};
+use chakchat_crypto::{CryptoError, KeyPair};
+use common::{array32, bytes, key_pair, Replay};
+use serde_json::Value;
+
+const VECTORS: &str = include_str!("vectors/hpke.json");
+
+fn vectors() -> Vec<Value> {
+    let vectors: Value = serde_json::from_str(VECTORS).expect("hpke.json is valid JSON");
+    vectors["vectors"]
+        .as_array()
+        .expect("vectors is an array")
+        .clone()
+}
+
+fn suite(v: &Value) -> Hpke {
+    let aead = match v["aead_id"].as_u64().unwrap() {
+        0x0002 => HpkeAead::Aes256Gcm,
+        0x0003 => HpkeAead::ChaCha20Poly1305,
+        0xFFFF => HpkeAead::ExportOnly,
+        id => panic!("unexpected AEAD {:#06x}", id),
+    };
+    assert_eq!(v["kem_id"].as_u64().unwrap(), 0x0020);
+    assert_eq!(v["kdf_id"].as_u64().unwrap(), 0x0001);
+    Hpke::new(aead)
+}
+
+#[test]
+fn test_rfc9180_vectors() {
+    let cases = vectors();
+    assert_eq!(cases.len(), 6);
+
+    for case in cases {
+        let hpke = suite(&case);
+        let info = bytes(&case, "info");
+        let recipient = key_pair(&case, "skRm");
+        assert_eq!(recipient.public_key, array32(&case, "pkRm"));
+        let mut rng = Replay(bytes(&case, "ikmE"));
+
+        let ((enc, mut sender), mut receiver) = match case["mode"].as_u64().unwrap() {
+            0 => {
+                let sender = hpke
+                    .setup_base_sender_with(&recipient.public_key, &info, &mut rng)
+                    .unwrap();
+                let receiver = hpke
+                    .setup_base_recipient(&sender.0, &recipient, &info)
+                    .unwrap();
+                (sender, receiver)
+            }
+            2 => {
+                let sender_keys = key_pair(&case, "skSm");
+                assert_eq!(sender_keys.public_key, array32(&case, "pkSm"));
+                let sender = hpke
+                    .setup_auth_sender_with(&recipient.public_key, &info, &sender_keys, &mut rng)
+                    .unwrap();
+                let receiver = hpke
+                    .setup_auth_recipient(&sender.0, &recipient, &info, &sender_keys.public_key)
+                    .unwrap();
+                (sender, receiver)
+            }
+            mode => panic!("unexpected mode {}", mode),
+        };
+        assert_eq!(enc.to_vec(), bytes(&case, "enc"));
+
+        let mut seq = 0;
+        for encryption in case["encryptions"].as_array().unwrap() {
+            // Advance both contexts past the sequence numbers left out of the file
+            while seq < encryption["seq"].as_u64().unwrap() {
+                let filler = sender.seal(b"", b"").unwrap();
+                receiver.open(b"", &filler).unwrap();
+                seq += 1;
+            }
+
+            let (aad, plaintext) = (bytes(encryption, "aad"), bytes(encryption, "pt"));
+            let ciphertext = sender.seal(&aad, &plaintext).unwrap();
+            assert_eq!(ciphertext, bytes(encryption, "ct"), "seq {}", seq);
+            assert_eq!(receiver.open(&aad, &ciphertext).unwrap(), plaintext);
+            seq += 1;
+        }
+
+        for export in case["exports"].as_array().unwrap() {
+            let context = bytes(export, "exporter_context");
+            let length = export["L"].as_u64().unwrap() as usize;
+            let expected = bytes(export, "exported_value");
+            assert_eq!(*sender.export(&context, length).unwrap(), expected);
+            assert_eq!(*receiver.export(&context, length).unwrap(), expected);
+        }
+
+        if hpke.aead() == HpkeAead::ExportOnly {
+            assert!(sender.seal(b"", b"data").is_err());
+        }
+    }
+}
+
+#[test]
+fn test_single_shot_round_trip() {
+    let recipient = KeyPair::generate().unwrap();
+    let sender = KeyPair::generate().unwrap();
+
+    for hpke in [
+        Hpke::new(HpkeAead::ChaCha20Poly1305),
+        Hpke::new(HpkeAead::Aes256Gcm),
+    ] {
+        let sealed = hpke
+            .seal_base(&recipient.public_key, b"contact request", b"aad", b"hello")
+            .unwrap();
+        assert_eq!(
+            hpke.open_base(&sealed, &recipient, b"contact request", b"aad")
+                .unwrap(),
+            b"hello"
+        );
+        assert!(hpke
+            .open_base(&sealed, &recipient, b"other info", b"aad")
+            .is_err());
+        assert!(hpke
+            .open_base(&sealed, &recipient, b"contact request", b"other aad")
+            .is_err());
+        assert!(hpke
+            .open_base(&sealed, &sender, b"contact request", b"aad")
+            .is_err());
+
+        let sealed = hpke
+            .seal_auth(&recipient.public_key, b"prekeys", b"", b"bundle", &sender)
+            .unwrap();
+        assert_eq!(
+            hpke.open_auth(&sealed, &recipient, b"prekeys", b"", &sender.public_key)
+                .unwrap(),
+            b"bundle"
+        );
+
+        // Someone else's key does not authenticate the message
+        let impostor = KeyPair::generate().unwrap();
+        assert!(hpke
+            .open_auth(&sealed, &recipient, b"prekeys", b"", &impostor.public_key)
+            .is_err());
+    }
+}
+
+#[test]
+fn test_small_order_keys_rejected() {
+    let recipient = KeyPair::generate().unwrap();
+    let hpke = Hpke::default();
+
+    // The identity point makes every X25519 output zero
+    let zero = [0u8; 32];
+    assert!(matches!(
+        hpke.seal_base(&zero, b"", b"", b"data"),
+        Err(CryptoError::KeyAgreementFailed(_))
+    ));
+    assert!(matches!(
+        hpke.setup_base_recipient(&zero, &recipient, b""),
+        Err(CryptoError::KeyAgreementFailed(_))
+    ));
+}
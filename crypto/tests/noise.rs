@@ -0,0 +1,116 @@
+//! Noise Test Vectors
+//!
+//! Replays the cacophony vectors in `tests/vectors/noise.json` for
+//! Noise_XX and Noise_IK over 25519, ChaChaPoly and SHA256. Each side's RNG
+//! replays its vector ephemeral key, so every handshake and transport
+//! message must match byte for byte.
+
+mod common;
+
+use chakchat_crypto::noise::{HandshakePattern, HandshakeState};
+use common::{array32, bytes, key_pair, Replay};
+use serde_json::Value;
+
+const VECTORS: &str = include_str!("vectors/noise.json");
+
+fn vectors() -> Vec<Value> {
+    let vectors: Value = serde_json::from_str(VECTORS).expect("noise.json is valid JSON");
+    vectors["vectors"]
+        .as_array()
+        .expect("vectors is an array")
+        .clone()
+}
+
+fn pattern(v: &Value) -> HandshakePattern {
+    match v["protocol_name"].as_str().unwrap() {
+        "Noise_XX_25519_ChaChaPoly_SHA256" => HandshakePattern::XX,
+        "Noise_IK_25519_ChaChaPoly_SHA256" => HandshakePattern::IK,
+        name => panic!("unexpected protocol {}", name),
+    }
+}
+
+#[test]
+fn test_cacophony_vectors() {
+    let cases = vectors();
+    assert_eq!(cases.len(), 2);
+
+    for case in cases {
+        let pattern = pattern(&case);
+        assert_eq!(pattern.protocol_name(), case["protocol_name"]);
+        let init_static = key_pair(&case, "init_static");
+        let resp_static = key_pair(&case, "resp_static");
+        let remote_static = case
+            .get("init_remote_static")
+            .map(|_| array32(&case, "init_remote_static"));
+        if let Some(remote_static) = remote_static {
+            assert_eq!(remote_static, resp_static.public_key);
+        }
+
+        let mut initiator = HandshakeState::initiator_with(
+            pattern,
+            &bytes(&case, "init_prologue"),
+            &init_static,
+            remote_static.as_ref(),
+            &mut Replay(bytes(&case, "init_ephemeral")),
+        )
+        .unwrap();
+        let mut responder = HandshakeState::responder_with(
+            pattern,
+            &bytes(&case, "resp_prologue"),
+            &resp_static,
+            &mut Replay(bytes(&case, "resp_ephemeral")),
+        )
+        .unwrap();
+
+        let messages = case["messages"].as_array().unwrap();
+        let mut index = 0;
+        while !initiator.is_finished() {
+            let message = &messages[index];
+            let (writer, reader) = if index % 2 == 0 {
+                (&mut initiator, &mut responder)
+            } else {
+                (&mut responder, &mut initiator)
+            };
+            let payload = bytes(message, "payload");
+            let ciphertext = writer.write_message(&payload).unwrap();
+            assert_eq!(
+                hex::encode(&ciphertext),
+                message["ciphertext"],
+                "{} message {}",
+                pattern.protocol_name(),
+                index
+            );
+            assert_eq!(reader.read_message(&ciphertext).unwrap(), payload);
+            index += 1;
+        }
+        assert!(responder.is_finished());
+
+        let mut initiator = initiator.into_transport().unwrap();
+        let mut responder = responder.into_transport().unwrap();
+        assert_eq!(
+            initiator.handshake_hash().as_slice(),
+            bytes(&case, "handshake_hash")
+        );
+        assert_eq!(responder.handshake_hash(), initiator.handshake_hash());
+        assert_eq!(initiator.remote_static(), &resp_static.public_key);
+        assert_eq!(responder.remote_static(), &init_static.public_key);
+
+        for (index, message) in messages.iter().enumerate().skip(index) {
+            let (writer, reader) = if index % 2 == 0 {
+                (&mut initiator, &mut responder)
+            } else {
+                (&mut responder, &mut initiator)
+            };
+            let payload = bytes(message, "payload");
+            let ciphertext = writer.encrypt(&payload).unwrap();
+            assert_eq!(
+                hex::encode(&ciphertext),
+                message["ciphertext"],
+                "{} message {}",
+                pattern.protocol_name(),
+                index
+            );
+            assert_eq!(reader.decrypt(&ciphertext).unwrap(), payload);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Panic Wipe
+//!
+//! Runs in its own test binary: `panic_wipe()` is process-wide and would
+//! break unrelated tests running in parallel.
+
+use chakchat_crypto::clock::FixedClock;
+use chakchat_crypto::expiring::ExpiringKeyStore;
+use chakchat_crypto::registry::{self, SecretRegistry};
+use chakchat_crypto::{CryptoError, EphemeralDH, KeyPair, TripleLayerEncryption};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[test]
+fn test_panic_wipe_invalidates_all_secrets() {
+    let identity = KeyPair::generate().unwrap();
+    let ephemeral = EphemeralDH::generate().unwrap();
+    let mut session = TripleLayerEncryption::new(&[9u8; 32]).unwrap();
+    let encrypted = session.encrypt(b"before the wipe").unwrap();
+    let clock = FixedClock(1_000);
+    let mut expiring = ExpiringKeyStore::new();
+    let sealed = expiring
+        .seal(1, b"disappearing", 2_000, &mut rand::thread_rng(), &clock)
+        .unwrap();
+    #[cfg(feature = "pq")]
+    let (pq_keypair, pq_ciphertext) = {
+        let keypair = chakchat_crypto::post_quantum::PostQuantumKeyPair::generate().unwrap();
+        let (_, ciphertext) =
+            chakchat_crypto::post_quantum::PostQuantumKeyPair::encapsulate(&keypair.public_key)
+                .unwrap();
+        (keypair, ciphertext)
+    };
+    assert!(SecretRegistry::global().live_count() >= 5);
+
+    let hook_calls = Arc::new(AtomicUsize::new(0));
+    let counter = hook_calls.clone();
+    registry::add_wipe_hook(move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+    });
+
+    let report = registry::panic_wipe();
+    assert!(report.secrets >= 5);
+    assert_eq!(report.hooks, 1);
+    assert_eq!(hook_calls.load(Ordering::SeqCst), 1);
+
+    assert!(matches!(session.encrypt(b"after"), Err(CryptoError::Wiped)));
+    assert!(matches!(
+        session.decrypt(&encrypted),
+        Err(CryptoError::Wiped)
+    ));
+    assert!(matches!(identity.sign(b"data"), Err(CryptoError::Wiped)));
+    assert!(matches!(
+        identity.compute_shared_secret(&[1u8; 32]),
+        Err(CryptoError::Wiped)
+    ));
+    assert!(matches!(
+        ephemeral.compute_shared_secret(ephemeral.public_key()),
+        Err(CryptoError::Wiped)
+    ));
+    assert!(serde_json::to_string(&identity).is_err());
+    assert!(matches!(
+        expiring.open(1, &sealed, &clock),
+        Err(CryptoError::Wiped)
+    ));
+    #[cfg(feature = "pq")]
+    assert!(matches!(
+        pq_keypair.decapsulate(&pq_ciphertext),
+        Err(CryptoError::Wiped)
+    ));
+
+    // Clones of wiped objects stay wiped; new keys work normally
+    assert!(matches!(
+        session.clone().encrypt(b"x"),
+        Err(CryptoError::Wiped)
+    ));
+    let fresh = KeyPair::generate().unwrap();
+    assert!(fresh.sign(b"data").is_ok());
+}
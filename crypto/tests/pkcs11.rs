@@ -0,0 +1,226 @@
+//! PKCS#11 Key Store against SoftHSM
+//!
+//! Initializes a throwaway SoftHSM v2 token in a temporary directory and runs
+//! the `KeyStore` operations on it. Needs `softhsm2` installed; without it the
+//! tests print a note and pass.
+//!
+//! ```bash
+//! sudo apt-get install softhsm2
+//! cargo test --features pkcs11 --test pkcs11
+//! ```
+//!
+//! Set `PKCS11_MODULE` if `libsofthsm2.so` is not in a standard location.
+
+#![cfg(feature = "pkcs11")]
+
+use chakchat_crypto::clock::FixedClock;
+use chakchat_crypto::key_exchange::verify_signature;
+use chakchat_crypto::pkcs11::Pkcs11KeyStore;
+use chakchat_crypto::{CryptoError, KeyPair, KeyStore, SignedEnvelope, TripleLayerEncryption};
+use cryptoki::context::Pkcs11;
+use cryptoki::object::{Attribute, AttributeType, ObjectClass};
+use cryptoki::session::{Session, UserType};
+use cryptoki::types::AuthPin;
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::OnceLock;
+use std::{env, fs};
+
+const TOKEN: &str = "chakchat-test";
+const SO_PIN: &str = "87654321";
+const PIN: &str = "12345678";
+
+const MODULE_PATHS: &[&str] = &[
+    "/usr/lib/softhsm/libsofthsm2.so",
+    "/usr/lib/x86_64-linux-gnu/softhsm/libsofthsm2.so",
+    "/usr/local/lib/softhsm/libsofthsm2.so",
+    "/opt/homebrew/lib/softhsm/libsofthsm2.so",
+];
+
+/// Module path after initializing the test token, or `None` without SoftHSM
+fn softhsm() -> Option<&'static PathBuf> {
+    static MODULE: OnceLock<Option<PathBuf>> = OnceLock::new();
+    MODULE
+        .get_or_init(|| {
+            let module = env::var_os("PKCS11_MODULE")
+                .map(PathBuf::from)
+                .or_else(|| {
+                    MODULE_PATHS
+                        .iter()
+                        .map(PathBuf::from)
+                        .find(|path| path.exists())
+                })
+                .filter(|path| path.exists());
+            let Some(module) = module else {
+                eprintln!("SoftHSM not found; skipping PKCS#11 tests");
+                return None;
+            };
+
+            let dir = env::temp_dir().join(format!("chakchat-softhsm-{}", std::process::id()));
+            let tokens = dir.join("tokens");
+            fs::create_dir_all(&tokens).unwrap();
+            let conf = dir.join("softhsm2.conf");
+            fs::write(
+                &conf,
+                format!("directories.tokendir = {}\n", tokens.display()),
+            )
+            .unwrap();
+            // Read by the module when it is loaded, so it must be set first
+            env::set_var("SOFTHSM2_CONF", &conf);
+
+            let status = Command::new("softhsm2-util")
+                .args([
+                    "--init-token",
+                    "--free",
+                    "--label",
+                    TOKEN,
+                    "--so-pin",
+                    SO_PIN,
+                    "--pin",
+                    PIN,
+                ])
+                .status()
+                .expect("softhsm2-util runs");
+            assert!(status.success(), "token initialization failed");
+            Some(module)
+        })
+        .as_ref()
+}
+
+fn open(label: &str) -> Pkcs11KeyStore {
+    Pkcs11KeyStore::open(softhsm().unwrap(), TOKEN, PIN, label).unwrap()
+}
+
+/// Separate logged-in session for inspecting token objects
+fn token_session() -> Session {
+    let pkcs11 = Pkcs11::new(softhsm().unwrap()).unwrap();
+    let slot = pkcs11
+        .get_slots_with_initialized_token()
+        .unwrap()
+        .into_iter()
+        .find(|slot| pkcs11.get_token_info(*slot).unwrap().label() == TOKEN)
+        .unwrap();
+    let session = pkcs11.open_ro_session(slot).unwrap();
+    let _ = session.login(UserType::User, Some(&AuthPin::new(PIN.into())));
+    session
+}
+
+#[test]
+fn test_pkcs11_sign_and_agree() {
+    if softhsm().is_none() {
+        return;
+    }
+    let mut rng = ChaCha20Rng::seed_from_u64(40);
+    let mut store = open("sign-and-agree");
+    let keys = store.generate(&mut rng).unwrap();
+    assert_eq!(store.public_keys().unwrap(), keys);
+
+    let signature = store.sign(b"hello").unwrap();
+    assert!(verify_signature(&keys.verifying_key, b"hello", &signature).is_ok());
+
+    let peer = KeyPair::generate().unwrap();
+    assert_eq!(
+        store.compute_shared_secret(&peer.public_key).unwrap(),
+        peer.compute_shared_secret(&keys.public_key).unwrap()
+    );
+
+    // Token objects outlive the session
+    drop(store);
+    assert_eq!(open("sign-and-agree").public_keys().unwrap(), keys);
+}
+
+#[test]
+fn test_pkcs11_signs_envelopes() {
+    if softhsm().is_none() {
+        return;
+    }
+    let mut rng = ChaCha20Rng::seed_from_u64(41);
+    let clock = FixedClock(1_700_000_000_000);
+    let mut store = open("envelope");
+    let keys = store.generate(&mut rng).unwrap();
+
+    let mut alice = TripleLayerEncryption::new(&[5u8; 32]).unwrap();
+    let mut bob = TripleLayerEncryption::new(&[5u8; 32]).unwrap();
+    let envelope = SignedEnvelope::seal_with(
+        &store, "alice", "bob", "chat", b"hi", &mut alice, &mut rng, &clock,
+    )
+    .unwrap();
+    let opened = envelope
+        .open_with(&keys.verifying_key, "bob", "chat", &mut bob, &clock)
+        .unwrap();
+    assert_eq!(opened, b"hi");
+}
+
+#[test]
+fn test_pkcs11_private_keys_stay_on_token() {
+    if softhsm().is_none() {
+        return;
+    }
+    let mut store = open("sensitive");
+    store.generate(&mut ChaCha20Rng::seed_from_u64(42)).unwrap();
+
+    let session = token_session();
+    let private_keys = session
+        .find_objects(&[
+            Attribute::Class(ObjectClass::PRIVATE_KEY),
+            Attribute::Label(b"sensitive".to_vec()),
+        ])
+        .unwrap();
+    assert_eq!(private_keys.len(), 2);
+    for key in private_keys {
+        let attributes = session
+            .get_attributes(key, &[AttributeType::Sensitive, AttributeType::Extractable])
+            .unwrap();
+        assert!(attributes.contains(&Attribute::Sensitive(true)));
+        assert!(attributes.contains(&Attribute::Extractable(false)));
+    }
+}
+
+#[test]
+fn test_pkcs11_generate_replaces_key() {
+    if softhsm().is_none() {
+        return;
+    }
+    let mut rng = ChaCha20Rng::seed_from_u64(43);
+    let mut store = open("rotate");
+    let first = store.generate(&mut rng).unwrap();
+    let second = store.generate(&mut rng).unwrap();
+
+    assert_ne!(first, second);
+    // Exactly one key per purpose remains, or lookups would be ambiguous
+    assert_eq!(store.public_keys().unwrap(), second);
+    assert!(store.sign(b"data").is_ok());
+
+    // The temporary label used during generation is gone
+    let session = token_session();
+    for handle in session.find_objects(&[]).unwrap() {
+        let label = match session
+            .get_attributes(handle, &[AttributeType::Label])
+            .unwrap()
+            .pop()
+        {
+            Some(Attribute::Label(label)) => label,
+            _ => continue,
+        };
+        assert!(!label.starts_with(b"rotate.pending"));
+    }
+}
+
+#[test]
+fn test_pkcs11_errors() {
+    if softhsm().is_none() {
+        return;
+    }
+    let module = softhsm().unwrap();
+
+    let missing = open("never-generated");
+    assert!(matches!(
+        missing.sign(b"data"),
+        Err(CryptoError::KeyStoreError(_))
+    ));
+
+    assert!(Pkcs11KeyStore::open(module, "no-such-token", PIN, "key").is_err());
+    assert!(Pkcs11KeyStore::open("/nonexistent/libpkcs11.so", TOKEN, PIN, "key").is_err());
+}
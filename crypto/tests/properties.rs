@@ -0,0 +1,182 @@
+//! Round-Trip Properties
+//!
+//! proptest checks for every encrypt/decrypt pair: decrypting what was
+//! encrypted returns the plaintext, and flipping any single authenticated bit
+//! makes decryption fail.
+
+use chakchat_crypto::attachment::{decrypt_attachment, encrypt_attachment_with_rng};
+use chakchat_crypto::clock::FixedClock;
+use chakchat_crypto::expiring::ExpiringKeyStore;
+use chakchat_crypto::padding::{pad, unpad, PaddingPolicy};
+use chakchat_crypto::stream;
+use chakchat_crypto::{EncryptedMessage, KeyPair, SignedEnvelope, TripleLayerEncryption};
+use proptest::prelude::*;
+use rand_chacha::ChaCha20Rng;
+use rand_core::SeedableRng;
+
+const NOW: i64 = 1_700_000_000_000;
+
+fn policies() -> impl Strategy<Value = PaddingPolicy> {
+    prop_oneof![
+        Just(PaddingPolicy::None),
+        Just(PaddingPolicy::Padme),
+        (1usize..4096).prop_map(|min_bucket| PaddingPolicy::PowerOfTwo { min_bucket }),
+        (0usize..512).prop_map(|max_extra| PaddingPolicy::RandomRange { max_extra }),
+    ]
+}
+
+fn flip(bytes: &mut [u8], bit: usize) {
+    let bit = bit % (bytes.len() * 8);
+    bytes[bit / 8] ^= 1 << (bit % 8);
+}
+
+/// Flip one bit in a field that decryption authenticates or checks
+///
+/// `counter`, `message_id` and `timestamp` are transport metadata outside the
+/// AEAD and are deliberately not covered.
+fn flip_authenticated(message: &mut EncryptedMessage, field: usize, bit: usize) {
+    match field % 7 {
+        0 => flip(&mut message.ciphertext, bit),
+        1 => flip(&mut message.layer1_nonce, bit),
+        2 => flip(&mut message.layer2_nonce, bit),
+        3 => flip(&mut message.layer3_nonce, bit),
+        4 => message.version ^= 1 << (bit % 8),
+        5 => flip(&mut message.key_commitment, bit),
+        _ => {
+            let expires_at = message.expires_at.get_or_insert(i64::MAX);
+            *expires_at ^= 1 << (bit % 64);
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn prop_triple_layer_round_trip(
+        secret in any::<[u8; 32]>(),
+        plaintext in prop::collection::vec(any::<u8>(), 1..2048),
+        policy in policies(),
+        expires in prop::option::of(1i64..1_000_000),
+        seed in any::<u64>(),
+    ) {
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        let clock = FixedClock(NOW);
+        let mut sender = TripleLayerEncryption::new(&secret).unwrap().with_padding(policy);
+        let mut receiver = TripleLayerEncryption::new(&secret).unwrap();
+
+        let message = match expires {
+            Some(ttl) => sender.encrypt_expiring_with(&plaintext, NOW + ttl, &mut rng, &clock),
+            None => sender.encrypt_with(&plaintext, &mut rng, &clock),
+        }
+        .unwrap();
+
+        prop_assert_eq!(receiver.decrypt_with(&message, &clock).unwrap(), plaintext);
+    }
+
+    #[test]
+    fn prop_triple_layer_bit_flip_fails(
+        plaintext in prop::collection::vec(any::<u8>(), 1..512),
+        expires in prop::option::of(1i64..1_000_000),
+        field in any::<usize>(),
+        bit in any::<usize>(),
+        seed in any::<u64>(),
+    ) {
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        let clock = FixedClock(NOW);
+        let mut session = TripleLayerEncryption::new(&[7u8; 32]).unwrap();
+
+        let mut message = match expires {
+            Some(ttl) => session.encrypt_expiring_with(&plaintext, NOW + ttl, &mut rng, &clock),
+            None => session.encrypt_with(&plaintext, &mut rng, &clock),
+        }
+        .unwrap();
+        flip_authenticated(&mut message, field, bit);
+
+        prop_assert!(session.decrypt_with(&message, &clock).is_err());
+    }
+
+    #[test]
+    fn prop_signed_envelope_round_trip(
+        plaintext in prop::collection::vec(any::<u8>(), 0..1024),
+        context in "[a-z:0-9]{1,16}",
+        seed in any::<u64>(),
+    ) {
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        let clock = FixedClock(NOW);
+        let sender = KeyPair::generate_with_rng(&mut rng).unwrap();
+        let mut session = TripleLayerEncryption::new(&[9u8; 32]).unwrap();
+
+        let envelope = SignedEnvelope::seal_with(
+            &sender, "alice", "bob", &context, &plaintext, &mut session, &mut rng, &clock,
+        )
+        .unwrap();
+        let opened = envelope
+            .open_with(&sender.verifying_key, "bob", &context, &mut session, &clock)
+            .unwrap();
+
+        prop_assert_eq!(opened, plaintext);
+    }
+
+    #[test]
+    fn prop_stream_round_trip_and_bit_flip(
+        key in any::<[u8; 32]>(),
+        plaintext in prop::collection::vec(any::<u8>(), 0..(3 * stream::CHUNK_SIZE)),
+        bit in any::<usize>(),
+        seed in any::<u64>(),
+    ) {
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        let mut sealed = stream::seal(&key, &plaintext, &mut rng).unwrap();
+        prop_assert_eq!(sealed.len(), stream::sealed_len(plaintext.len()));
+        prop_assert_eq!(stream::open(&key, &sealed).unwrap(), plaintext);
+
+        flip(&mut sealed, bit);
+        prop_assert!(stream::open(&key, &sealed).is_err());
+    }
+
+    #[test]
+    fn prop_padding_round_trip(
+        plaintext in prop::collection::vec(any::<u8>(), 0..4096),
+        policy in policies(),
+        seed in any::<u64>(),
+    ) {
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        let padded = pad(&plaintext, policy, &mut rng).unwrap();
+        prop_assert!(padded.len() > plaintext.len());
+        prop_assert_eq!(unpad(padded).unwrap(), plaintext);
+    }
+
+    #[test]
+    fn prop_attachment_round_trip_and_bit_flip(
+        plaintext in prop::collection::vec(any::<u8>(), 0..4096),
+        bit in any::<usize>(),
+        seed in any::<u64>(),
+    ) {
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        let attachment = encrypt_attachment_with_rng(&plaintext, "application/octet-stream", None, &mut rng)
+            .unwrap();
+        prop_assert_eq!(decrypt_attachment(&attachment.ciphertext, &attachment.pointer).unwrap(), plaintext);
+
+        let mut ciphertext = attachment.ciphertext.clone();
+        flip(&mut ciphertext, bit);
+        prop_assert!(decrypt_attachment(&ciphertext, &attachment.pointer).is_err());
+    }
+
+    #[test]
+    fn prop_expiring_round_trip_and_bit_flip(
+        message_id in any::<u64>(),
+        plaintext in prop::collection::vec(any::<u8>(), 0..1024),
+        bit in any::<usize>(),
+        seed in any::<u64>(),
+    ) {
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        let clock = FixedClock(NOW);
+        let mut store = ExpiringKeyStore::new();
+
+        let mut blob = store.seal(message_id, &plaintext, NOW + 60_000, &mut rng, &clock).unwrap();
+        prop_assert_eq!(store.open(message_id, &blob, &clock).unwrap(), plaintext);
+
+        flip(&mut blob, bit);
+        prop_assert!(store.open(message_id, &blob, &clock).is_err());
+    }
+}
@@ -3,25 +3,52 @@
 //! Direct peer-to-peer communication via WebRTC DataChannels
 //! Decentralized username discovery via DHT (Distributed Hash Table)
 //! Zero central servers - completely decentralized!
-
+//! Peer channels are authenticated with a Noise_IK handshake
+//! against the static key each peer publishes in the DHT; the initiator
+//! also signs the handshake with its identity key, so a static key copied
+//! into someone else's record cannot be attributed to them
+
+use chakchat_crypto::key_exchange::verify_signature;
+use chakchat_crypto::noise::{HandshakePattern, HandshakeState, TransportState};
+use chakchat_crypto::KeyStore;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{Mutex, RwLock};
+
+/// P2P wire protocol version, bound into the handshake prologue
+pub const P2P_PROTOCOL_VERSION: u8 = 1;
+
+/// Domain separator for `PeerInfo` signatures
+const PEER_INFO_DOMAIN: &[u8] = b"chakchat_peer_info_v1";
+
+/// Domain separator for the initiator's handshake signature
+const PEER_AUTH_DOMAIN: &[u8] = b"chakchat_peer_auth_v1";
 
 /// Peer information stored in DHT
-#[derive(Clone, Debug)]
+///
+/// Keys and signatures serialize as base64url in JSON and as raw bytes in
+/// binary formats.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PeerInfo {
     /// Username (e.g., "alice@chakchat")
     pub username: String,
 
-    /// Public key (for verification)
+    /// X25519 static key (authenticated by the Noise handshake)
+    #[serde(with = "chakchat_crypto::encoding::base64_bytes")]
     pub public_key: Vec<u8>,
 
+    /// Ed25519 identity key the record is signed with
+    #[serde(with = "chakchat_crypto::encoding::base64_array")]
+    pub verifying_key: [u8; 32],
+
     /// Network endpoints (IP:Port, supports multiple)
     pub endpoints: Vec<SocketAddr>,
 
-    /// Signature (proves ownership of private key)
+    /// Identity signature over every other field (see `PeerInfo::signed_bytes`)
+    #[serde(with = "chakchat_crypto::encoding::base64_bytes")]
     pub signature: Vec<u8>,
 
     /// Timestamp (for expiration)
@@ -36,12 +63,14 @@ impl PeerInfo {
     pub fn new(
         username: String,
         public_key: Vec<u8>,
+        verifying_key: [u8; 32],
         endpoints: Vec<SocketAddr>,
         signature: Vec<u8>,
     ) -> Self {
         PeerInfo {
             username,
             public_key,
+            verifying_key,
             endpoints,
             signature,
             timestamp: chrono::Local::now().timestamp(),
@@ -49,6 +78,59 @@ impl PeerInfo {
         }
     }
 
+    /// Create peer info for `identity` and sign it
+    pub fn signed(
+        username: String,
+        identity: &dyn KeyStore,
+        endpoints: Vec<SocketAddr>,
+    ) -> Result<Self, String> {
+        let keys = identity.public_keys().map_err(|e| e.to_string())?;
+        let mut peer_info = PeerInfo::new(
+            username,
+            keys.public_key.to_vec(),
+            keys.verifying_key,
+            endpoints,
+            Vec::new(),
+        );
+        peer_info.signature = identity
+            .sign(&peer_info.signed_bytes())
+            .map_err(|e| e.to_string())?
+            .to_vec();
+        Ok(peer_info)
+    }
+
+    /// Check the signature against `verifying_key`
+    pub fn verify(&self) -> Result<(), String> {
+        let signature: [u8; 64] = self
+            .signature
+            .as_slice()
+            .try_into()
+            .map_err(|_| "Invalid peer info signature".to_string())?;
+        verify_signature(&self.verifying_key, &self.signed_bytes(), &signature)
+            .map_err(|_| format!("Invalid signature on peer info for: {}", self.username))
+    }
+
+    /// Length-prefixed transcript covered by the signature
+    fn signed_bytes(&self) -> Vec<u8> {
+        let endpoints: Vec<String> = self.endpoints.iter().map(|e| e.to_string()).collect();
+        let mut fields: Vec<&[u8]> = vec![
+            self.username.as_bytes(),
+            &self.public_key,
+            &self.verifying_key,
+        ];
+        fields.extend(endpoints.iter().map(|e| e.as_bytes()));
+
+        let mut transcript = PEER_INFO_DOMAIN.to_vec();
+        transcript.extend_from_slice(&(fields.len() as u64).to_be_bytes());
+        for field in fields {
+            transcript.extend_from_slice(&(field.len() as u64).to_be_bytes());
+            transcript.extend_from_slice(field);
+        }
+        transcript.extend_from_slice(&self.timestamp.to_be_bytes());
+        transcript.extend_from_slice(&self.ttl.to_be_bytes());
+        transcript
+    }
+
     /// Check if peer info has expired
     pub fn is_expired(&self) -> bool {
         let now = chrono::Local::now().timestamp();
@@ -66,7 +148,11 @@ pub struct DHTNode {
     /// HashMap storing: username -> PeerInfo
     entries: Arc<RwLock<HashMap<String, PeerInfo>>>,
 
-    /// Routing table for DHT lookups
+    /// Identity key each username was first published with; kept past expiry
+    owners: Arc<RwLock<HashMap<String, [u8; 32]>>>,
+
+    /// Routing table for DHT lookups (TODO: iterative lookups)
+    #[allow(dead_code)]
     routing_table: Arc<RwLock<RoutingTable>>,
 }
 
@@ -98,15 +184,37 @@ impl DHTNode {
         DHTNode {
             peer_id,
             entries: Arc::new(RwLock::new(HashMap::new())),
+            owners: Arc::new(RwLock::new(HashMap::new())),
             routing_table: Arc::new(RwLock::new(RoutingTable::new(20))),
         }
     }
 
     /// Publish peer info to DHT
+    ///
+    /// The record must be signed by its `verifying_key`, and a username stays
+    /// bound to the identity key it was first published with, so a record
+    /// can only be replaced by a newer one from the same identity.
     pub async fn publish(&self, peer_info: PeerInfo) -> Result<(), String> {
-        // Verify signature before storing (TODO: implement signature verification)
-        
+        peer_info.verify()?;
+
+        let mut owners = self.owners.write().await;
+        if let Some(owner) = owners.get(&peer_info.username) {
+            if *owner != peer_info.verifying_key {
+                return Err(format!(
+                    "Username already registered to another key: {}",
+                    peer_info.username
+                ));
+            }
+        }
+
         let mut entries = self.entries.write().await;
+        if let Some(existing) = entries.get(&peer_info.username) {
+            if existing.timestamp > peer_info.timestamp {
+                return Err(format!("Stale peer info for: {}", peer_info.username));
+            }
+        }
+
+        owners.insert(peer_info.username.clone(), peer_info.verifying_key);
         entries.insert(peer_info.username.clone(), peer_info);
         Ok(())
     }
@@ -114,7 +222,7 @@ impl DHTNode {
     /// Lookup peer by username
     pub async fn lookup(&self, username: &str) -> Result<Option<PeerInfo>, String> {
         let entries = self.entries.read().await;
-        
+
         if let Some(peer_info) = entries.get(username) {
             // Check if expired
             if peer_info.is_expired() {
@@ -159,14 +267,14 @@ impl RoutingTable {
     pub fn add_node(&mut self, node: DHTNodeInfo) {
         // Calculate bucket index based on XOR distance
         let bucket_index = Self::calculate_bucket_index(&node.node_id);
-        
+
         if let Some(bucket) = self.buckets.get_mut(bucket_index) {
             // Remove if already exists
             bucket.retain(|n| n.node_id != node.node_id);
-            
+
             // Add to front
             bucket.insert(0, node);
-            
+
             // Keep only K nodes per bucket
             if bucket.len() > self.k {
                 bucket.pop();
@@ -175,9 +283,9 @@ impl RoutingTable {
     }
 
     /// Get nearby nodes from routing table
-    pub fn get_nearby_nodes(&self, target_id: &[u8], count: usize) -> Vec<DHTNodeInfo> {
+    pub fn get_nearby_nodes(&self, _target_id: &[u8], count: usize) -> Vec<DHTNodeInfo> {
         let mut nearby = Vec::new();
-        
+
         for bucket in &self.buckets {
             for node in bucket {
                 nearby.push(node.clone());
@@ -186,7 +294,7 @@ impl RoutingTable {
                 }
             }
         }
-        
+
         nearby
     }
 
@@ -195,7 +303,7 @@ impl RoutingTable {
         if node_id.is_empty() {
             return 0;
         }
-        
+
         // Simple calculation: use first byte modulo bucket count
         (node_id[0] as usize) % 160
     }
@@ -230,8 +338,8 @@ pub struct PeerConnection {
     /// Last activity timestamp
     pub last_activity: i64,
 
-    /// Shared secret (for encryption)
-    pub shared_secret: Option<Vec<u8>>,
+    /// Noise transport ciphers once authenticated; wiped by `registry::panic_wipe()`
+    pub session: Option<Arc<Mutex<TransportState>>>,
 }
 
 /// Connection status
@@ -273,52 +381,142 @@ impl P2PNetwork {
     }
 
     /// Connect to peer by username
-    pub async fn connect_to_peer(&self, username: &str) -> Result<(), String> {
+    ///
+    /// Runs the Noise_IK handshake as initiator over `transport` (a WebRTC
+    /// DataChannel, QUIC stream or TCP socket), so only the holder of the
+    /// key published in the DHT can complete it. `local_username` is the
+    /// name `identity` is published under; the responder checks it.
+    pub async fn connect_to_peer<T>(
+        &self,
+        username: &str,
+        local_username: &str,
+        identity: &(dyn KeyStore + Sync),
+        transport: &mut T,
+    ) -> Result<(), String>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
         // 1. Lookup peer in DHT
         let peer_info = self
             .discover_peer(username)
             .await?
             .ok_or_else(|| format!("Peer not found: {}", username))?;
+        let remote_static: [u8; 32] = peer_info
+            .public_key
+            .as_slice()
+            .try_into()
+            .map_err(|_| format!("Invalid public key for: {}", username))?;
 
         // 2. Create connection record
+        self.set_connection(username, ConnectionStatus::Connecting, None)
+            .await;
+
+        // 3. Noise_IK handshake (ephemeral-static and static-static DH)
+        match initiate_handshake(transport, identity, local_username, &remote_static).await {
+            Ok(session) => {
+                // 4. Mark as Authenticated
+                self.set_connection(username, ConnectionStatus::Authenticated, Some(session))
+                    .await;
+                Ok(())
+            }
+            Err(e) => {
+                self.set_connection(username, ConnectionStatus::Error(e.clone()), None)
+                    .await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Accept an incoming connection
+    ///
+    /// Runs the Noise_IK handshake as responder and checks the initiator's
+    /// claimed username, static key and identity signature against the DHT;
+    /// failures are rejected before the final handshake message is sent.
+    ///
+    /// # Returns
+    /// The authenticated peer's username
+    pub async fn accept_peer<T>(
+        &self,
+        identity: &(dyn KeyStore + Sync),
+        transport: &mut T,
+    ) -> Result<String, String>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        let (peer_info, session) = accept_handshake(transport, identity, &self.dht).await?;
+
+        self.set_connection(
+            &peer_info.username,
+            ConnectionStatus::Authenticated,
+            Some(session),
+        )
+        .await;
+        Ok(peer_info.username)
+    }
+
+    /// Get connection state for a peer
+    pub async fn connection(&self, username: &str) -> Option<PeerConnection> {
+        self.peers.read().await.get(username).cloned()
+    }
+
+    /// Store or replace the connection record for a peer
+    async fn set_connection(
+        &self,
+        username: &str,
+        status: ConnectionStatus,
+        session: Option<TransportState>,
+    ) {
         let connection = PeerConnection {
             username: username.to_string(),
-            status: ConnectionStatus::Connecting,
+            status,
             last_activity: chrono::Local::now().timestamp(),
-            shared_secret: None,
+            session: session.map(|session| Arc::new(Mutex::new(session))),
         };
 
-        // 3. Store connection
         let mut peers = self.peers.write().await;
         peers.insert(username.to_string(), connection);
-
-        // TODO: 4. Establish WebRTC DataChannel
-        // TODO: 5. Perform ECDH key exchange
-        // TODO: 6. Mark as Authenticated
-
-        Ok(())
     }
 
     /// Send message to peer (encrypted)
-    pub async fn send_message(
+    ///
+    /// Encrypts `message` with the peer's Noise session and writes it to
+    /// `transport` as one frame. The session stays locked until the frame is
+    /// written, so concurrent sends reach the peer in nonce order.
+    pub async fn send_message<T: AsyncWrite + Unpin>(
         &self,
         username: &str,
         message: &[u8],
+        transport: &mut T,
     ) -> Result<(), String> {
-        let peers = self.peers.read().await;
+        let session = self.session(username).await?;
+        let mut session = session.lock().await;
+        let ciphertext = session.encrypt(message).map_err(|e| e.to_string())?;
+        write_frame(transport, &ciphertext).await
+    }
 
+    /// Receive one message from peer (decrypted)
+    pub async fn receive_message<T: AsyncRead + Unpin>(
+        &self,
+        username: &str,
+        transport: &mut T,
+    ) -> Result<Vec<u8>, String> {
+        let session = self.session(username).await?;
+        let mut session = session.lock().await;
+        let ciphertext = read_frame(transport).await?;
+        session.decrypt(&ciphertext).map_err(|e| e.to_string())
+    }
+
+    /// Noise session of an authenticated peer
+    async fn session(&self, username: &str) -> Result<Arc<Mutex<TransportState>>, String> {
+        let peers = self.peers.read().await;
         let connection = peers
             .get(username)
             .ok_or_else(|| format!("No connection to: {}", username))?;
 
-        if connection.status != ConnectionStatus::Authenticated {
-            return Err("Not authenticated with peer".to_string());
+        match (&connection.status, &connection.session) {
+            (ConnectionStatus::Authenticated, Some(session)) => Ok(session.clone()),
+            _ => Err("Not authenticated with peer".to_string()),
         }
-
-        // TODO: Encrypt message with shared secret
-        // TODO: Send via WebRTC DataChannel
-
-        Ok(())
     }
 
     /// Get DHT statistics
@@ -332,21 +530,186 @@ impl P2PNetwork {
     }
 }
 
+/// Handshake prologue: both sides must run the same protocol versions
+fn handshake_prologue() -> Vec<u8> {
+    let mut prologue = b"chakchat-p2p".to_vec();
+    prologue.push(P2P_PROTOCOL_VERSION);
+    prologue.push(chakchat_crypto::PROTOCOL_VERSION);
+    prologue
+}
+
+/// Transcript the initiator signs: the handshake hash before the first
+/// message (prologue and responder static key) and the initiator static key
+fn peer_auth_transcript(handshake_hash: &[u8], initiator_static: &[u8; 32]) -> Vec<u8> {
+    let mut transcript = PEER_AUTH_DOMAIN.to_vec();
+    transcript.extend_from_slice(handshake_hash);
+    transcript.extend_from_slice(initiator_static);
+    transcript
+}
+
+/// Run a Noise_IK handshake as initiator
+///
+/// The first message carries `username` and an identity signature binding
+/// this handshake's static keys to it: `len (2) || username || signature (64)`.
+pub async fn initiate_handshake<T>(
+    transport: &mut T,
+    identity: &(dyn KeyStore + Sync),
+    username: &str,
+    remote_static: &[u8; 32],
+) -> Result<TransportState, String>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let prologue = handshake_prologue();
+    let mut handshake = HandshakeState::initiator(
+        HandshakePattern::IK,
+        &prologue,
+        identity,
+        Some(remote_static),
+    )
+    .map_err(|e| e.to_string())?;
+
+    let local_static = identity
+        .public_keys()
+        .map_err(|e| e.to_string())?
+        .public_key;
+    let transcript = peer_auth_transcript(handshake.handshake_hash(), &local_static);
+    let signature = identity.sign(&transcript).map_err(|e| e.to_string())?;
+    let name_len = u16::try_from(username.len()).map_err(|_| "Username too long".to_string())?;
+    let mut payload = name_len.to_be_bytes().to_vec();
+    payload.extend_from_slice(username.as_bytes());
+    payload.extend_from_slice(&signature);
+
+    let message = handshake
+        .write_message(&payload)
+        .map_err(|e| e.to_string())?;
+    write_frame(transport, &message).await?;
+    let reply = read_frame(transport).await?;
+    handshake.read_message(&reply).map_err(|e| e.to_string())?;
+
+    handshake.into_transport().map_err(|e| e.to_string())
+}
+
+/// Run a Noise_IK handshake as responder
+///
+/// After the first message the initiator's claimed username is looked up in
+/// `dht`; the record must publish the initiator's static key and its
+/// `verifying_key` must have signed the handshake. Otherwise the initiator
+/// gets no reply and the transport is shut down, so it never completes the
+/// handshake.
+pub async fn accept_handshake<T>(
+    transport: &mut T,
+    identity: &(dyn KeyStore + Sync),
+    dht: &DHTNode,
+) -> Result<(PeerInfo, TransportState), String>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let prologue = handshake_prologue();
+    let mut handshake = HandshakeState::responder(HandshakePattern::IK, &prologue, identity)
+        .map_err(|e| e.to_string())?;
+
+    let transcript_hash = *handshake.handshake_hash();
+    let message = read_frame(transport).await?;
+    let payload = handshake
+        .read_message(&message)
+        .map_err(|e| e.to_string())?;
+    let remote_static = *handshake
+        .remote_static()
+        .ok_or_else(|| "Missing initiator static key".to_string())?;
+
+    let peer_info =
+        match authenticate_initiator(&payload, &transcript_hash, &remote_static, dht).await {
+            Ok(peer_info) => peer_info,
+            Err(e) => {
+                let _ = transport.shutdown().await;
+                return Err(e);
+            }
+        };
+
+    let reply = handshake.write_message(&[]).map_err(|e| e.to_string())?;
+    write_frame(transport, &reply).await?;
+
+    let session = handshake.into_transport().map_err(|e| e.to_string())?;
+    Ok((peer_info, session))
+}
+
+/// Check the initiator's first-message payload against its DHT record
+async fn authenticate_initiator(
+    payload: &[u8],
+    handshake_hash: &[u8],
+    remote_static: &[u8; 32],
+    dht: &DHTNode,
+) -> Result<PeerInfo, String> {
+    let invalid = || "Invalid peer authentication".to_string();
+    let name_len = payload.get(..2).ok_or_else(invalid)?;
+    let name_len = u16::from_be_bytes([name_len[0], name_len[1]]) as usize;
+    if payload.len() != 2 + name_len + 64 {
+        return Err(invalid());
+    }
+    let username = std::str::from_utf8(&payload[2..2 + name_len]).map_err(|_| invalid())?;
+    let signature: [u8; 64] = payload[2 + name_len..].try_into().map_err(|_| invalid())?;
+
+    let peer_info = dht
+        .lookup(username)
+        .await?
+        .ok_or_else(|| "Unknown peer".to_string())?;
+    if peer_info.public_key != remote_static {
+        return Err("Unknown peer key".to_string());
+    }
+    verify_signature(
+        &peer_info.verifying_key,
+        &peer_auth_transcript(handshake_hash, remote_static),
+        &signature,
+    )
+    .map_err(|_| invalid())?;
+    Ok(peer_info)
+}
+
+/// Write one Noise message with a 2-byte big-endian length prefix
+pub async fn write_frame<T: AsyncWrite + Unpin>(
+    transport: &mut T,
+    message: &[u8],
+) -> Result<(), String> {
+    let len = u16::try_from(message.len()).map_err(|_| "Frame too long".to_string())?;
+    transport
+        .write_all(&len.to_be_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+    transport
+        .write_all(message)
+        .await
+        .map_err(|e| e.to_string())?;
+    transport.flush().await.map_err(|e| e.to_string())
+}
+
+/// Read one length-prefixed Noise message
+pub async fn read_frame<T: AsyncRead + Unpin>(transport: &mut T) -> Result<Vec<u8>, String> {
+    let len = transport.read_u16().await.map_err(|e| e.to_string())? as usize;
+    let mut message = vec![0u8; len];
+    transport
+        .read_exact(&mut message)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(message)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chakchat_crypto::KeyPair;
     use std::str::FromStr;
 
     #[tokio::test]
     async fn test_dht_publish_and_lookup() {
         let dht = DHTNode::new(b"peer1".to_vec());
 
-        let peer_info = PeerInfo::new(
+        let peer_info = PeerInfo::signed(
             "alice@chakchat".to_string(),
-            b"pubkey123".to_vec(),
+            &KeyPair::generate().unwrap(),
             vec![SocketAddr::from_str("192.168.1.1:8080").unwrap()],
-            b"signature".to_vec(),
-        );
+        )
+        .unwrap();
 
         // Publish
         dht.publish(peer_info.clone()).await.unwrap();
@@ -365,16 +728,38 @@ mod tests {
         assert!(found.is_none());
     }
 
+    #[test]
+    fn test_peer_info_json() {
+        let peer_info = PeerInfo::new(
+            "alice@chakchat".to_string(),
+            vec![0xfb; 32],
+            [0xfb; 32],
+            vec![SocketAddr::from_str("192.168.1.1:8080").unwrap()],
+            vec![0xff; 64],
+        );
+
+        let json: serde_json::Value = serde_json::to_value(&peer_info).unwrap();
+        assert_eq!(json["public_key"], "-_v7".repeat(10) + "-_s");
+        assert_eq!(json["verifying_key"], json["public_key"]);
+        assert!(json["signature"].is_string());
+        assert_eq!(json["endpoints"][0], "192.168.1.1:8080");
+
+        let restored: PeerInfo = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.public_key, peer_info.public_key);
+        assert_eq!(restored.signature, peer_info.signature);
+        assert_eq!(restored.endpoints, peer_info.endpoints);
+    }
+
     #[tokio::test]
     async fn test_p2p_network_discovery() {
         let network = P2PNetwork::new(b"peer1".to_vec());
 
-        let peer_info = PeerInfo::new(
+        let peer_info = PeerInfo::signed(
             "bob@chakchat".to_string(),
-            b"pubkey456".to_vec(),
+            &KeyPair::generate().unwrap(),
             vec![SocketAddr::from_str("192.168.1.2:8081").unwrap()],
-            b"signature2".to_vec(),
-        );
+        )
+        .unwrap();
 
         network.publish_self(peer_info).await.unwrap();
 
@@ -386,19 +771,10 @@ mod tests {
     async fn test_dht_stats() {
         let dht = DHTNode::new(b"peer1".to_vec());
 
-        let peer1 = PeerInfo::new(
-            "alice".to_string(),
-            b"key1".to_vec(),
-            vec![],
-            b"sig1".to_vec(),
-        );
-
-        let peer2 = PeerInfo::new(
-            "bob".to_string(),
-            b"key2".to_vec(),
-            vec![],
-            b"sig2".to_vec(),
-        );
+        let peer1 =
+            PeerInfo::signed("alice".to_string(), &KeyPair::generate().unwrap(), vec![]).unwrap();
+        let peer2 =
+            PeerInfo::signed("bob".to_string(), &KeyPair::generate().unwrap(), vec![]).unwrap();
 
         dht.publish(peer1).await.unwrap();
         dht.publish(peer2).await.unwrap();
@@ -408,6 +784,35 @@ mod tests {
         assert_eq!(stats.active_peers, 2);
     }
 
+    #[tokio::test]
+    async fn test_forged_peer_info_rejected() {
+        let dht = DHTNode::new(b"peer1".to_vec());
+        let bob_keys = KeyPair::generate().unwrap();
+        let mallory_keys = KeyPair::generate().unwrap();
+        let bob_info = PeerInfo::signed("bob".to_string(), &bob_keys, vec![]).unwrap();
+
+        // Unsigned record, and bob's record re-pointed at mallory's static key
+        let unsigned = PeerInfo::new(
+            "bob".to_string(),
+            mallory_keys.public_key.to_vec(),
+            bob_keys.verifying_key,
+            vec![],
+            vec![0u8; 64],
+        );
+        assert!(dht.publish(unsigned).await.is_err());
+        let mut repointed = bob_info.clone();
+        repointed.public_key = mallory_keys.public_key.to_vec();
+        assert!(dht.publish(repointed).await.is_err());
+
+        // Validly self-signed, but "bob" already belongs to bob's identity key
+        dht.publish(bob_info).await.unwrap();
+        let squatted = PeerInfo::signed("bob".to_string(), &mallory_keys, vec![]).unwrap();
+        assert!(dht.publish(squatted).await.is_err());
+
+        let found = dht.lookup("bob").await.unwrap().unwrap();
+        assert_eq!(found.public_key, bob_keys.public_key.to_vec());
+    }
+
     #[test]
     fn test_routing_table_add_node() {
         let mut rt = RoutingTable::new(20);
@@ -423,4 +828,152 @@ mod tests {
         let nearby = rt.get_nearby_nodes(b"target", 5);
         assert_eq!(nearby.len(), 1);
     }
+
+    /// Two networks that know each other's published keys
+    async fn pair() -> (P2PNetwork, KeyPair, P2PNetwork, KeyPair) {
+        let alice_keys = KeyPair::generate().unwrap();
+        let bob_keys = KeyPair::generate().unwrap();
+        let alice = P2PNetwork::new(b"alice".to_vec());
+        let bob = P2PNetwork::new(b"bob".to_vec());
+
+        for network in [&alice, &bob] {
+            for (username, keys) in [("alice", &alice_keys), ("bob", &bob_keys)] {
+                let peer_info = PeerInfo::signed(username.to_string(), keys, vec![]).unwrap();
+                network.publish_self(peer_info).await.unwrap();
+            }
+        }
+        (alice, alice_keys, bob, bob_keys)
+    }
+
+    #[tokio::test]
+    async fn test_connect_and_accept_peer() {
+        let (alice, alice_keys, bob, bob_keys) = pair().await;
+        let (mut alice_end, mut bob_end) = tokio::io::duplex(1024);
+
+        let (connected, accepted) = tokio::join!(
+            alice.connect_to_peer("bob", "alice", &alice_keys, &mut alice_end),
+            bob.accept_peer(&bob_keys, &mut bob_end),
+        );
+        connected.unwrap();
+        assert_eq!(accepted.unwrap(), "alice");
+
+        let to_bob = alice.connection("bob").await.unwrap();
+        let to_alice = bob.connection("alice").await.unwrap();
+        assert_eq!(to_bob.status, ConnectionStatus::Authenticated);
+        assert_eq!(to_alice.status, ConnectionStatus::Authenticated);
+
+        alice
+            .send_message("bob", b"hi bob", &mut alice_end)
+            .await
+            .unwrap();
+        assert_eq!(
+            bob.receive_message("alice", &mut bob_end).await.unwrap(),
+            b"hi bob"
+        );
+        bob.send_message("alice", b"hi alice", &mut bob_end)
+            .await
+            .unwrap();
+        assert_eq!(
+            alice.receive_message("bob", &mut alice_end).await.unwrap(),
+            b"hi alice"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_requires_authenticated_session() {
+        let (alice, _, _, _) = pair().await;
+        let (mut alice_end, _bob_end) = tokio::io::duplex(1024);
+
+        assert!(alice
+            .send_message("bob", b"hi", &mut alice_end)
+            .await
+            .is_err());
+        alice
+            .set_connection("bob", ConnectionStatus::Connecting, None)
+            .await;
+        assert_eq!(
+            alice
+                .send_message("bob", b"hi", &mut alice_end)
+                .await
+                .unwrap_err(),
+            "Not authenticated with peer"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_to_impostor_fails() {
+        let (alice, alice_keys, _, _) = pair().await;
+        let mallory_keys = KeyPair::generate().unwrap();
+        let mallory = P2PNetwork::new(b"mallory".to_vec());
+        let alice_info = PeerInfo::signed("alice".to_string(), &alice_keys, vec![]).unwrap();
+        mallory.publish_self(alice_info).await.unwrap();
+        let (mut alice_end, mut mallory_end) = tokio::io::duplex(1024);
+
+        // Mallory answers for bob without bob's key, then hangs up
+        let (connected, accepted) = tokio::join!(
+            alice.connect_to_peer("bob", "alice", &alice_keys, &mut alice_end),
+            async move { mallory.accept_peer(&mallory_keys, &mut mallory_end).await },
+        );
+        assert!(accepted.is_err());
+        assert!(connected.is_err());
+        assert!(matches!(
+            alice.connection("bob").await.unwrap().status,
+            ConnectionStatus::Error(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_accept_unknown_peer_fails() {
+        let (_, _, bob, bob_keys) = pair().await;
+        let stranger_keys = KeyPair::generate().unwrap();
+        let (mut stranger_end, mut bob_end) = tokio::io::duplex(1024);
+
+        let (connected, accepted) = tokio::join!(
+            initiate_handshake(
+                &mut stranger_end,
+                &stranger_keys,
+                "alice",
+                &bob_keys.public_key
+            ),
+            bob.accept_peer(&bob_keys, &mut bob_end),
+        );
+        // Bob never sends the final handshake message
+        assert!(connected.is_err());
+        assert_eq!(accepted.unwrap_err(), "Unknown peer key");
+    }
+
+    #[tokio::test]
+    async fn test_copied_static_key_not_attributed() {
+        let (alice, alice_keys, bob, bob_keys) = pair().await;
+
+        // Mallory publishes a validly signed record carrying alice's static key
+        let mallory_keys = KeyPair::generate().unwrap();
+        let mut copied = PeerInfo::new(
+            "mallory".to_string(),
+            alice_keys.public_key.to_vec(),
+            mallory_keys.verifying_key,
+            vec![],
+            Vec::new(),
+        );
+        copied.signature = mallory_keys.sign(&copied.signed_bytes()).unwrap().to_vec();
+        bob.publish_self(copied).await.unwrap();
+
+        // Alice's connections are still attributed to alice...
+        let (mut alice_end, mut bob_end) = tokio::io::duplex(1024);
+        let (connected, accepted) = tokio::join!(
+            alice.connect_to_peer("bob", "alice", &alice_keys, &mut alice_end),
+            bob.accept_peer(&bob_keys, &mut bob_end),
+        );
+        connected.unwrap();
+        assert_eq!(accepted.unwrap(), "alice");
+
+        // ...and claiming the copied record fails: alice cannot sign as mallory
+        let (mut alice_end, mut bob_end) = tokio::io::duplex(1024);
+        let (connected, accepted) = tokio::join!(
+            initiate_handshake(&mut alice_end, &alice_keys, "mallory", &bob_keys.public_key),
+            bob.accept_peer(&bob_keys, &mut bob_end),
+        );
+        assert!(connected.is_err());
+        assert_eq!(accepted.unwrap_err(), "Invalid peer authentication");
+    }
 }
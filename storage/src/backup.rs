@@ -0,0 +1,841 @@
+//! Encrypted Backups
+//!
+//! Single-file export of the identity key, contacts, ratchet state and message
+//! history. Records are serialized, compressed with DEFLATE and encrypted as a
+//! chunked XChaCha20-Poly1305 STREAM, so neither export nor import needs the
+//! whole archive in memory.
+//!
+//! File format:
+//! `magic (8) || version (1) || salt (32) || key_check (32) || nonce_prefix (19) || chunks`
+//!
+//! The full header is bound as associated data to every chunk. The stream key
+//! is derived from a random 256-bit `RecoveryKey` that is shown to the user
+//! once and never stored. Inside the stream: a `Manifest`, then the records,
+//! then an end marker carrying the record counts.
+
+use crate::models::{Contact, Message, RatchetState};
+use crate::store::{
+    for_each_row, insert_sealed, upsert_sealed, Store, CONTACTS, MESSAGES, SESSIONS,
+};
+use crate::{StorageError, StorageResult};
+use chacha20poly1305::aead::stream::{DecryptorBE32, EncryptorBE32};
+use chacha20poly1305::aead::{KeyInit, Payload};
+use chacha20poly1305::XChaCha20Poly1305;
+use chakchat_crypto::clock::{Clock, SystemClock};
+use chakchat_crypto::encryption::TAG_SIZE;
+use chakchat_crypto::stream::{CHUNK_SIZE, NONCE_PREFIX_SIZE};
+use chakchat_crypto::utils::constant_time_compare;
+use chakchat_crypto::KeyPair;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use hkdf::Hkdf;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fmt;
+use std::io::{self, Read, Write};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+/// File magic
+pub const BACKUP_MAGIC: &[u8; 8] = b"CHAKBAK\0";
+
+/// Backup format version
+pub const BACKUP_VERSION: u8 = 1;
+
+const SALT_SIZE: usize = 32;
+const KEY_CHECK_SIZE: usize = 32;
+
+/// Header size (magic, version, salt, key check, nonce prefix)
+pub const HEADER_SIZE: usize = 8 + 1 + SALT_SIZE + KEY_CHECK_SIZE + NONCE_PREFIX_SIZE;
+
+const STREAM_KEY_INFO: &[u8] = b"chakchat_backup_stream_v1";
+const KEY_CHECK_INFO: &[u8] = b"chakchat_backup_key_check_v1";
+
+/// Largest accepted record (messages carry their content inline)
+const MAX_RECORD_SIZE: usize = 64 * 1024 * 1024;
+
+/// Largest accepted decompressed stream; DEFLATE expands up to ~1000x
+const MAX_DECOMPRESSED_SIZE: u64 = 16 * 1024 * 1024 * 1024;
+
+/// Sealed chunk size on disk
+const SEALED_CHUNK_SIZE: usize = CHUNK_SIZE + TAG_SIZE;
+
+/// 256-bit key that unlocks a backup
+#[derive(Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop)]
+pub struct RecoveryKey([u8; 32]);
+
+impl fmt::Debug for RecoveryKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RecoveryKey([REDACTED])")
+    }
+}
+
+impl RecoveryKey {
+    /// Generate a fresh recovery key
+    pub fn generate() -> Self {
+        let mut key = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut key);
+        RecoveryKey(key)
+    }
+
+    /// Display form: 64 hex digits in groups of four
+    pub fn to_display_string(&self) -> String {
+        let hex = Zeroizing::new(hex::encode(self.0));
+        hex.as_bytes()
+            .chunks(4)
+            .map(|group| std::str::from_utf8(group).expect("hex is ASCII"))
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+
+    /// Parse the display form; dashes and whitespace are ignored
+    pub fn parse(input: &str) -> StorageResult<Self> {
+        let digits: Zeroizing<String> = Zeroizing::new(
+            input
+                .chars()
+                .filter(|c| !c.is_whitespace() && *c != '-')
+                .collect(),
+        );
+
+        let mut key = [0u8; 32];
+        hex::decode_to_slice(digits.as_bytes(), &mut key)
+            .map_err(|_| StorageError::InvalidBackup("Malformed recovery key".to_string()))?;
+        Ok(RecoveryKey(key))
+    }
+
+    fn derive(
+        &self,
+        salt: &[u8; SALT_SIZE],
+    ) -> StorageResult<(Zeroizing<[u8; 32]>, [u8; KEY_CHECK_SIZE])> {
+        let hk = Hkdf::<Sha256>::new(Some(salt), &self.0);
+        let mut stream_key = Zeroizing::new([0u8; 32]);
+        let mut key_check = [0u8; KEY_CHECK_SIZE];
+        hk.expand(STREAM_KEY_INFO, stream_key.as_mut())
+            .and_then(|_| hk.expand(KEY_CHECK_INFO, &mut key_check))
+            .map_err(|e| StorageError::InvalidBackup(e.to_string()))?;
+        Ok((stream_key, key_check))
+    }
+}
+
+/// Versioned description of the backup contents
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Backup format version
+    pub format_version: u8,
+
+    /// Store schema version at export time
+    pub schema_version: u32,
+
+    /// Export time (Unix milliseconds)
+    pub created_at: i64,
+}
+
+/// Record counts written after the last record
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupSummary {
+    /// Contacts in the backup
+    pub contacts: u64,
+
+    /// Ratchet states in the backup
+    pub sessions: u64,
+
+    /// Messages in the backup
+    pub messages: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Record {
+    Identity(KeyPair),
+    Contact(Contact),
+    Session(RatchetState),
+    Message(Message),
+    End(BackupSummary),
+}
+
+impl Store {
+    /// Export everything to an encrypted backup
+    ///
+    /// # Arguments
+    /// * `identity` - User's identity key pair
+    /// * `out` - Destination (e.g. a file)
+    ///
+    /// # Returns
+    /// The recovery key; show it to the user once, it is not stored anywhere
+    pub fn export_backup<W: Write>(
+        &self,
+        identity: &KeyPair,
+        out: W,
+    ) -> StorageResult<RecoveryKey> {
+        let recovery_key = RecoveryKey::generate();
+        self.export_backup_with(identity, out, &recovery_key, &SystemClock)?;
+        Ok(recovery_key)
+    }
+
+    /// Export under a caller-supplied recovery key and clock
+    pub fn export_backup_with<W: Write>(
+        &self,
+        identity: &KeyPair,
+        mut out: W,
+        recovery_key: &RecoveryKey,
+        clock: &dyn Clock,
+    ) -> StorageResult<BackupSummary> {
+        let mut salt = [0u8; SALT_SIZE];
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_SIZE];
+        rand::thread_rng().fill_bytes(&mut salt);
+        rand::thread_rng().fill_bytes(&mut nonce_prefix);
+
+        let (stream_key, key_check) = recovery_key.derive(&salt)?;
+
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        header.extend_from_slice(BACKUP_MAGIC);
+        header.push(BACKUP_VERSION);
+        header.extend_from_slice(&salt);
+        header.extend_from_slice(&key_check);
+        header.extend_from_slice(&nonce_prefix);
+        out.write_all(&header)?;
+
+        let writer = EncryptWriter::new(out, &stream_key, &nonce_prefix, header);
+        let mut encoder = DeflateEncoder::new(writer, Compression::default());
+
+        let manifest = Manifest {
+            format_version: BACKUP_VERSION,
+            schema_version: self.schema_version()?,
+            created_at: clock.now_millis(),
+        };
+        write_record(&mut encoder, &manifest)?;
+        write_record(&mut encoder, &Record::Identity(identity.clone()))?;
+
+        let mut summary = BackupSummary::default();
+        for_each_row(&self.conn, &self.cipher, CONTACTS, |contact| {
+            summary.contacts += 1;
+            write_record(&mut encoder, &Record::Contact(contact))
+        })?;
+        for_each_row(&self.conn, &self.cipher, SESSIONS, |state| {
+            summary.sessions += 1;
+            write_record(&mut encoder, &Record::Session(state))
+        })?;
+        for_each_row(&self.conn, &self.cipher, MESSAGES, |message| {
+            summary.messages += 1;
+            write_record(&mut encoder, &Record::Message(message))
+        })?;
+        write_record(&mut encoder, &Record::End(summary))?;
+
+        let mut out = encoder.finish()?.finish()?;
+        out.flush()?;
+        Ok(summary)
+    }
+
+    /// Restore a backup into this (empty) store
+    ///
+    /// The import runs in a single transaction and is rolled back on any error.
+    ///
+    /// # Returns
+    /// The identity key pair, manifest and record counts
+    ///
+    /// # Errors
+    /// * `WrongRecoveryKey` - the key does not unlock this backup
+    /// * `BackupTruncated` - the file ends early or its final chunk is damaged
+    /// * `InvalidBackup` - not a backup, unsupported version, or corrupted chunk
+    /// * `Corrupted` - the records decompress to more than `MAX_DECOMPRESSED_SIZE`
+    /// * `StoreNotEmpty` - the store already holds data
+    pub fn import_backup<R: Read>(
+        &mut self,
+        input: R,
+        recovery_key: &RecoveryKey,
+    ) -> StorageResult<(KeyPair, Manifest, BackupSummary)> {
+        self.import_backup_limited(input, recovery_key, MAX_DECOMPRESSED_SIZE)
+    }
+
+    /// `import_backup` with the decompressed size capped at `limit` bytes
+    fn import_backup_limited<R: Read>(
+        &mut self,
+        mut input: R,
+        recovery_key: &RecoveryKey,
+        limit: u64,
+    ) -> StorageResult<(KeyPair, Manifest, BackupSummary)> {
+        let mut header = vec![0u8; HEADER_SIZE];
+        input.read_exact(&mut header).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => StorageError::BackupTruncated,
+            _ => StorageError::Io(e),
+        })?;
+
+        if &header[..8] != BACKUP_MAGIC {
+            return Err(StorageError::InvalidBackup(
+                "Not a ChakChat backup".to_string(),
+            ));
+        }
+        if header[8] != BACKUP_VERSION {
+            return Err(StorageError::InvalidBackup(format!(
+                "Unsupported backup version {}",
+                header[8]
+            )));
+        }
+
+        let salt: [u8; SALT_SIZE] = header[9..9 + SALT_SIZE].try_into().expect("fixed slice");
+        let stored_check = &header[9 + SALT_SIZE..9 + SALT_SIZE + KEY_CHECK_SIZE];
+        let nonce_prefix: [u8; NONCE_PREFIX_SIZE] = header[HEADER_SIZE - NONCE_PREFIX_SIZE..]
+            .try_into()
+            .expect("fixed slice");
+
+        let (stream_key, key_check) = recovery_key.derive(&salt)?;
+        if !constant_time_compare(&key_check, stored_check) {
+            return Err(StorageError::WrongRecoveryKey);
+        }
+        if !self.is_empty()? {
+            return Err(StorageError::StoreNotEmpty);
+        }
+
+        let reader = DecryptReader::new(input, &stream_key, &nonce_prefix, header)?;
+        let mut records = Records {
+            decoder: DeflateDecoder::new(reader),
+            read: 0,
+            limit,
+        };
+
+        let manifest: Manifest = read_record(&mut records)?;
+        if manifest.format_version != BACKUP_VERSION {
+            return Err(StorageError::InvalidBackup(
+                "Manifest version mismatch".to_string(),
+            ));
+        }
+
+        let identity = match read_record(&mut records)? {
+            Record::Identity(identity) => identity,
+            _ => return Err(StorageError::InvalidBackup("Missing identity".to_string())),
+        };
+
+        let tx = self.conn.transaction()?;
+        let mut summary = BackupSummary::default();
+        loop {
+            match read_record(&mut records)? {
+                Record::Contact(contact) => {
+                    let tag = self
+                        .cipher
+                        .index_tag(CONTACTS, contact.username.as_bytes())?;
+                    upsert_sealed(&tx, &self.cipher, CONTACTS, "username_tag", &tag, &contact)?;
+                    summary.contacts += 1;
+                }
+                Record::Session(state) => {
+                    let tag = self.cipher.index_tag(SESSIONS, &state.contact_public_key)?;
+                    upsert_sealed(&tx, &self.cipher, SESSIONS, "contact_tag", &tag, &state)?;
+                    summary.sessions += 1;
+                }
+                Record::Message(message) => {
+                    let tag = self
+                        .cipher
+                        .index_tag(MESSAGES, message.conversation_id.as_bytes())?;
+                    insert_sealed(
+                        &tx,
+                        &self.cipher,
+                        MESSAGES,
+                        "conversation_tag",
+                        &tag,
+                        &message,
+                    )?;
+                    summary.messages += 1;
+                }
+                Record::End(expected) => {
+                    if expected != summary {
+                        return Err(StorageError::InvalidBackup(
+                            "Record count mismatch".to_string(),
+                        ));
+                    }
+                    break;
+                }
+                Record::Identity(_) => {
+                    return Err(StorageError::InvalidBackup(
+                        "Duplicate identity".to_string(),
+                    ));
+                }
+            }
+        }
+
+        // The end marker must coincide with the authenticated end of the stream
+        let mut trailing = [0u8; 1];
+        if read_fully(&mut records, &mut trailing)? != 0 {
+            return Err(StorageError::InvalidBackup("Trailing data".to_string()));
+        }
+
+        tx.commit()?;
+        Ok((identity, manifest, summary))
+    }
+}
+
+fn write_record<W: Write, T: Serialize>(out: &mut W, record: &T) -> StorageResult<()> {
+    let bytes = Zeroizing::new(
+        bincode::serialize(record).map_err(|e| StorageError::InvalidBackup(e.to_string()))?,
+    );
+    out.write_all(&(bytes.len() as u32).to_be_bytes())?;
+    out.write_all(&bytes)?;
+    Ok(())
+}
+
+/// Decompressed record stream with a running size cap
+struct Records<R: Read> {
+    decoder: DeflateDecoder<DecryptReader<R>>,
+    read: u64,
+    limit: u64,
+}
+
+fn read_record<R: Read, T: serde::de::DeserializeOwned>(
+    records: &mut Records<R>,
+) -> StorageResult<T> {
+    let mut len = [0u8; 4];
+    read_exact(records, &mut len)?;
+
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_RECORD_SIZE {
+        return Err(StorageError::InvalidBackup("Record too large".to_string()));
+    }
+
+    let mut bytes = Zeroizing::new(vec![0u8; len]);
+    read_exact(records, &mut bytes)?;
+
+    bincode::deserialize(&bytes).map_err(|e| StorageError::InvalidBackup(e.to_string()))
+}
+
+fn read_exact<R: Read>(records: &mut Records<R>, buf: &mut [u8]) -> StorageResult<()> {
+    if read_fully(records, buf)? != buf.len() {
+        return Err(StorageError::InvalidBackup(
+            "Unexpected end of records".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Read until `buf` is full or the stream ends, mapping stream failures
+///
+/// Fails with `Corrupted` once the stream has produced more than its limit.
+fn read_fully<R: Read>(records: &mut Records<R>, buf: &mut [u8]) -> StorageResult<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match records.decoder.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => {
+                filled += n;
+                records.read += n as u64;
+                if records.read > records.limit {
+                    return Err(StorageError::Corrupted(format!(
+                        "Backup decompresses to more than {} bytes",
+                        records.limit
+                    )));
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => {
+                return Err(match records.decoder.get_ref().failure {
+                    Some(StreamFailure::Truncated) => StorageError::BackupTruncated,
+                    Some(StreamFailure::Corrupted) => {
+                        StorageError::InvalidBackup("Chunk failed authentication".to_string())
+                    }
+                    None => StorageError::InvalidBackup(e.to_string()),
+                });
+            }
+        }
+    }
+    Ok(filled)
+}
+
+/// Buffers plaintext and seals it in `CHUNK_SIZE` chunks
+struct EncryptWriter<W: Write> {
+    inner: W,
+    encryptor: Option<EncryptorBE32<XChaCha20Poly1305>>,
+    aad: Vec<u8>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> EncryptWriter<W> {
+    fn new(inner: W, key: &[u8; 32], nonce_prefix: &[u8; NONCE_PREFIX_SIZE], aad: Vec<u8>) -> Self {
+        let cipher = XChaCha20Poly1305::new(key.into());
+        EncryptWriter {
+            inner,
+            encryptor: Some(EncryptorBE32::from_aead(
+                cipher,
+                nonce_prefix.as_ref().into(),
+            )),
+            aad,
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+        }
+    }
+
+    fn seal_chunk(&mut self) -> io::Result<()> {
+        let encryptor = self.encryptor.as_mut().expect("writer not finished");
+        let sealed = encryptor
+            .encrypt_next(Payload {
+                msg: &self.buffer,
+                aad: &self.aad,
+            })
+            .map_err(|_| io::Error::other("Stream chunk failed"))?;
+        self.buffer.zeroize();
+        self.inner.write_all(&sealed)
+    }
+
+    /// Seal the final chunk and return the inner writer
+    fn finish(mut self) -> io::Result<W> {
+        let encryptor = self.encryptor.take().expect("writer not finished");
+        let sealed = encryptor
+            .encrypt_last(Payload {
+                msg: &self.buffer,
+                aad: &self.aad,
+            })
+            .map_err(|_| io::Error::other("Stream chunk failed"))?;
+        self.buffer.zeroize();
+        self.inner.write_all(&sealed)?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for EncryptWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let mut rest = data;
+        while !rest.is_empty() {
+            // A full buffer is only sealed once more data arrives, so the
+            // last chunk is always sealed by `finish`
+            if self.buffer.len() == CHUNK_SIZE {
+                self.seal_chunk()?;
+            }
+            let take = rest.len().min(CHUNK_SIZE - self.buffer.len());
+            self.buffer.extend_from_slice(&rest[..take]);
+            rest = &rest[take..];
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StreamFailure {
+    Truncated,
+    Corrupted,
+}
+
+/// Opens sealed chunks one at a time, looking one chunk ahead to find the last
+struct DecryptReader<R: Read> {
+    inner: R,
+    decryptor: Option<DecryptorBE32<XChaCha20Poly1305>>,
+    aad: Vec<u8>,
+    next: Vec<u8>,
+    plain: Zeroizing<Vec<u8>>,
+    pos: usize,
+    failure: Option<StreamFailure>,
+}
+
+impl<R: Read> DecryptReader<R> {
+    fn new(
+        mut inner: R,
+        key: &[u8; 32],
+        nonce_prefix: &[u8; NONCE_PREFIX_SIZE],
+        aad: Vec<u8>,
+    ) -> StorageResult<Self> {
+        let next = read_chunk(&mut inner)?;
+        if next.is_empty() {
+            return Err(StorageError::BackupTruncated);
+        }
+
+        let cipher = XChaCha20Poly1305::new(key.into());
+        Ok(DecryptReader {
+            inner,
+            decryptor: Some(DecryptorBE32::from_aead(
+                cipher,
+                nonce_prefix.as_ref().into(),
+            )),
+            aad,
+            next,
+            plain: Zeroizing::new(Vec::new()),
+            pos: 0,
+            failure: None,
+        })
+    }
+
+    fn fail(&mut self, failure: StreamFailure) -> io::Error {
+        self.failure = Some(failure);
+        self.decryptor = None;
+        io::Error::new(io::ErrorKind::InvalidData, "Backup stream failed")
+    }
+
+    fn open_next_chunk(&mut self) -> io::Result<()> {
+        let current = std::mem::take(&mut self.next);
+        if current.len() == SEALED_CHUNK_SIZE {
+            self.next = read_chunk(&mut self.inner)?;
+        }
+
+        let payload = Payload {
+            msg: &current,
+            aad: &self.aad,
+        };
+        let opened = if self.next.is_empty() {
+            let decryptor = self.decryptor.take().expect("stream not finished");
+            decryptor
+                .decrypt_last(payload)
+                .map_err(|_| self.fail(StreamFailure::Truncated))?
+        } else {
+            let decryptor = self.decryptor.as_mut().expect("stream not finished");
+            decryptor
+                .decrypt_next(payload)
+                .map_err(|_| self.fail(StreamFailure::Corrupted))?
+        };
+
+        self.plain = Zeroizing::new(opened);
+        self.pos = 0;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.failure.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Backup stream failed",
+            ));
+        }
+        while self.pos == self.plain.len() {
+            if self.decryptor.is_none() {
+                return Ok(0);
+            }
+            self.open_next_chunk()?;
+        }
+
+        let n = buf.len().min(self.plain.len() - self.pos);
+        buf[..n].copy_from_slice(&self.plain[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Read one sealed chunk (shorter only at end of input)
+fn read_chunk<R: Read>(inner: &mut R) -> io::Result<Vec<u8>> {
+    let mut chunk = Vec::with_capacity(SEALED_CHUNK_SIZE);
+    inner
+        .take(SEALED_CHUNK_SIZE as u64)
+        .read_to_end(&mut chunk)?;
+    Ok(chunk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chakchat_crypto::clock::FixedClock;
+    use tempfile::TempDir;
+
+    fn populated_store() -> (TempDir, Store) {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = Store::open(dir.path().join("source.db"), b"pw").unwrap();
+
+        store
+            .save_contact(&Contact {
+                username: "bob".to_string(),
+                public_key: [2u8; 32],
+                identity_signature: vec![3u8; 64],
+                last_seen: 10,
+                verified: true,
+            })
+            .unwrap();
+        store
+            .save_ratchet_state(&RatchetState {
+                contact_public_key: [2u8; 32],
+                chain_key_state: vec![4u8; 96],
+                message_count: 12,
+                created_at: 1,
+                updated_at: 2,
+            })
+            .unwrap();
+
+        // Enough pseudo-random content to span several stream chunks
+        let mut rng_bytes = vec![0u8; 3 * CHUNK_SIZE];
+        rand::thread_rng().fill_bytes(&mut rng_bytes);
+        for (i, content) in rng_bytes.chunks(4096).enumerate() {
+            store
+                .insert_message(&Message {
+                    conversation_id: "alice-bob".to_string(),
+                    sender_public_key: [2u8; 32],
+                    content: content.to_vec(),
+                    timestamp: i as i64,
+                    is_read: i % 2 == 0,
+                })
+                .unwrap();
+        }
+        (dir, store)
+    }
+
+    fn fresh_store(dir: &TempDir, name: &str) -> Store {
+        Store::open(dir.path().join(name), b"new device").unwrap()
+    }
+
+    #[test]
+    fn test_export_import_roundtrip() {
+        let (dir, source) = populated_store();
+        let identity = KeyPair::generate().unwrap();
+
+        let mut archive = Vec::new();
+        let recovery_key = source.export_backup(&identity, &mut archive).unwrap();
+        let display = recovery_key.to_display_string();
+
+        let mut target = fresh_store(&dir, "target.db");
+        let (restored, manifest, summary) = target
+            .import_backup(archive.as_slice(), &RecoveryKey::parse(&display).unwrap())
+            .unwrap();
+
+        assert_eq!(restored.verifying_key, identity.verifying_key);
+        assert_eq!(
+            restored.get_private_key().unwrap(),
+            identity.get_private_key().unwrap()
+        );
+        assert_eq!(manifest.format_version, BACKUP_VERSION);
+        assert_eq!(
+            summary,
+            BackupSummary {
+                contacts: 1,
+                sessions: 1,
+                messages: 48
+            }
+        );
+
+        assert_eq!(target.contacts().unwrap(), source.contacts().unwrap());
+        assert_eq!(
+            target.ratchet_state(&[2u8; 32]).unwrap(),
+            source.ratchet_state(&[2u8; 32]).unwrap()
+        );
+        let restored_messages: Vec<Message> = target
+            .conversation("alice-bob")
+            .unwrap()
+            .into_iter()
+            .map(|(_, m)| m)
+            .collect();
+        let source_messages: Vec<Message> = source
+            .conversation("alice-bob")
+            .unwrap()
+            .into_iter()
+            .map(|(_, m)| m)
+            .collect();
+        assert_eq!(restored_messages, source_messages);
+    }
+
+    #[test]
+    fn test_wrong_key_rejected() {
+        let (dir, source) = populated_store();
+        let mut archive = Vec::new();
+        source
+            .export_backup(&KeyPair::generate().unwrap(), &mut archive)
+            .unwrap();
+
+        let mut target = fresh_store(&dir, "target.db");
+        assert!(matches!(
+            target.import_backup(archive.as_slice(), &RecoveryKey::generate()),
+            Err(StorageError::WrongRecoveryKey)
+        ));
+    }
+
+    #[test]
+    fn test_truncation_detected_and_rolled_back() {
+        let (dir, source) = populated_store();
+        let key = RecoveryKey::generate();
+        let mut archive = Vec::new();
+        source
+            .export_backup_with(
+                &KeyPair::generate().unwrap(),
+                &mut archive,
+                &key,
+                &FixedClock(0),
+            )
+            .unwrap();
+        assert!(archive.len() > HEADER_SIZE + 2 * SEALED_CHUNK_SIZE);
+
+        let mut target = fresh_store(&dir, "target.db");
+        for cut in [
+            10,
+            HEADER_SIZE,
+            HEADER_SIZE + 100,
+            HEADER_SIZE + SEALED_CHUNK_SIZE,
+            HEADER_SIZE + 2 * SEALED_CHUNK_SIZE,
+            archive.len() - 1,
+        ] {
+            assert!(
+                matches!(
+                    target.import_backup(&archive[..cut], &key),
+                    Err(StorageError::BackupTruncated)
+                ),
+                "cut at {}",
+                cut
+            );
+            assert!(target.is_empty().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_decompressed_size_capped() {
+        let (dir, source) = populated_store();
+        let key = RecoveryKey::generate();
+        let mut archive = Vec::new();
+        source
+            .export_backup_with(
+                &KeyPair::generate().unwrap(),
+                &mut archive,
+                &key,
+                &FixedClock(0),
+            )
+            .unwrap();
+
+        // Every message is a 4 KiB record, so the stream is well past this
+        let mut target = fresh_store(&dir, "target.db");
+        assert!(matches!(
+            target.import_backup_limited(archive.as_slice(), &key, 3 * CHUNK_SIZE as u64 / 2),
+            Err(StorageError::Corrupted(_))
+        ));
+        assert!(target.is_empty().unwrap());
+
+        target.import_backup(archive.as_slice(), &key).unwrap();
+    }
+
+    #[test]
+    fn test_tampered_chunk_detected() {
+        let (dir, source) = populated_store();
+        let key = RecoveryKey::generate();
+        let mut archive = Vec::new();
+        source
+            .export_backup_with(
+                &KeyPair::generate().unwrap(),
+                &mut archive,
+                &key,
+                &FixedClock(0),
+            )
+            .unwrap();
+
+        archive[HEADER_SIZE + 10] ^= 1;
+        let mut target = fresh_store(&dir, "target.db");
+        assert!(matches!(
+            target.import_backup(archive.as_slice(), &key),
+            Err(StorageError::InvalidBackup(_))
+        ));
+        assert!(target.is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_import_requires_fresh_store() {
+        let (_dir, mut source) = populated_store();
+        let mut archive = Vec::new();
+        let key = source
+            .export_backup(&KeyPair::generate().unwrap(), &mut archive)
+            .unwrap();
+
+        assert!(matches!(
+            source.import_backup(archive.as_slice(), &key),
+            Err(StorageError::StoreNotEmpty)
+        ));
+    }
+
+    #[test]
+    fn test_recovery_key_display_roundtrip() {
+        let key = RecoveryKey::generate();
+        let display = key.to_display_string();
+        assert_eq!(display.len(), 64 + 15);
+        assert_eq!(RecoveryKey::parse(&display).unwrap(), key);
+        assert_eq!(RecoveryKey::parse(&display.replace('-', " ")).unwrap(), key);
+        assert!(RecoveryKey::parse("not-a-key").is_err());
+        assert_eq!(format!("{:?}", key), "RecoveryKey([REDACTED])");
+    }
+}
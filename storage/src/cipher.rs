@@ -0,0 +1,170 @@
+//! Row Encryption
+//!
+//! The password-derived master key is split with HKDF into a row encryption
+//! key and an index key. Rows are stored as `nonce (24) || ciphertext || tag`
+//! with `table || row_id` as associated data.
+
+use crate::{StorageError, StorageResult};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use chakchat_crypto::registry::Secret;
+use chakchat_crypto::utils::derive_key_from_password;
+use chakchat_crypto::CryptoError;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use std::fmt;
+use zeroize::Zeroize;
+
+/// XChaCha20 nonce size
+pub const NONCE_SIZE: usize = 24;
+
+/// Salt size for password derivation
+pub const SALT_SIZE: usize = 32;
+
+const ROW_KEY_INFO: &[u8] = b"chakchat_storage_rows_v1";
+const INDEX_KEY_INFO: &[u8] = b"chakchat_storage_index_v1";
+
+#[derive(Clone, Zeroize)]
+struct RowKeys {
+    row_key: [u8; 32],
+    index_key: [u8; 32],
+}
+
+/// Keys for sealing rows and computing lookup tags
+///
+/// The keys are registered with `chakchat_crypto::registry`, so
+/// `panic_wipe()` makes every open store unusable.
+pub struct RowCipher {
+    keys: Secret<RowKeys>,
+}
+
+impl fmt::Debug for RowCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RowCipher").finish_non_exhaustive()
+    }
+}
+
+impl RowCipher {
+    /// Derive row and index keys from a password
+    pub fn from_password(password: &[u8], salt: &[u8; SALT_SIZE]) -> StorageResult<Self> {
+        let mut master = derive_key_from_password(password, salt)?;
+        let hk = Hkdf::<Sha256>::new(None, &master);
+        master.zeroize();
+
+        let mut keys = RowKeys {
+            row_key: [0u8; 32],
+            index_key: [0u8; 32],
+        };
+        let expanded = hk
+            .expand(ROW_KEY_INFO, &mut keys.row_key)
+            .and_then(|_| hk.expand(INDEX_KEY_INFO, &mut keys.index_key));
+        let cipher = RowCipher {
+            keys: Secret::new(keys),
+        };
+        expanded.map_err(|e| StorageError::Corrupted(e.to_string()))?;
+
+        Ok(cipher)
+    }
+
+    /// Encrypt a row
+    pub fn seal(&self, table: &str, row_id: i64, plaintext: &[u8]) -> StorageResult<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_SIZE];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let aad = Self::associated_data(table, row_id);
+        let ciphertext = self.keys.with(|keys| {
+            XChaCha20Poly1305::new(keys.row_key.as_ref().into())
+                .encrypt(
+                    XNonce::from_slice(&nonce),
+                    Payload {
+                        msg: plaintext,
+                        aad: &aad,
+                    },
+                )
+                .map_err(|e| CryptoError::EncryptionError(e.to_string()))
+        })?;
+
+        let mut blob = Vec::with_capacity(NONCE_SIZE + ciphertext.len());
+        blob.extend_from_slice(&nonce);
+        blob.extend_from_slice(&ciphertext);
+        Ok(blob)
+    }
+
+    /// Decrypt a row, failing if it was moved from another row or table
+    pub fn open(&self, table: &str, row_id: i64, blob: &[u8]) -> StorageResult<Vec<u8>> {
+        if blob.len() < NONCE_SIZE {
+            return Err(StorageError::Corrupted(format!(
+                "{} row {} too short",
+                table, row_id
+            )));
+        }
+        let (nonce, ciphertext) = blob.split_at(NONCE_SIZE);
+
+        let aad = Self::associated_data(table, row_id);
+        let opened = self.keys.with(|keys| {
+            Ok(
+                XChaCha20Poly1305::new(keys.row_key.as_ref().into()).decrypt(
+                    XNonce::from_slice(nonce),
+                    Payload {
+                        msg: ciphertext,
+                        aad: &aad,
+                    },
+                ),
+            )
+        })?;
+        opened.map_err(|_| {
+            StorageError::Corrupted(format!("{} row {} failed authentication", table, row_id))
+        })
+    }
+
+    /// Keyed lookup tag for an indexed value
+    pub fn index_tag(&self, table: &str, value: &[u8]) -> StorageResult<[u8; 32]> {
+        Ok(self.keys.with(|keys| {
+            let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&keys.index_key)
+                .map_err(|e| CryptoError::KeyDerivationError(e.to_string()))?;
+            mac.update(&(table.len() as u64).to_be_bytes());
+            mac.update(table.as_bytes());
+            mac.update(value);
+            Ok(mac.finalize().into_bytes().into())
+        })?)
+    }
+
+    fn associated_data(table: &str, row_id: i64) -> Vec<u8> {
+        let mut aad = Vec::with_capacity(table.len() + 9);
+        aad.extend_from_slice(table.as_bytes());
+        aad.push(0);
+        aad.extend_from_slice(&row_id.to_be_bytes());
+        aad
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_row_bound_to_table_and_id() {
+        let cipher = RowCipher::from_password(b"password", &[1u8; SALT_SIZE]).unwrap();
+        let blob = cipher.seal("messages", 7, b"hello").unwrap();
+
+        assert_eq!(
+            cipher.open("messages", 7, &blob).unwrap(),
+            b"hello".to_vec()
+        );
+        assert!(cipher.open("messages", 8, &blob).is_err());
+        assert!(cipher.open("contacts", 7, &blob).is_err());
+    }
+
+    #[test]
+    fn test_index_tags_keyed() {
+        let a = RowCipher::from_password(b"password", &[1u8; SALT_SIZE]).unwrap();
+        let b = RowCipher::from_password(b"password", &[2u8; SALT_SIZE]).unwrap();
+
+        let tag = a.index_tag("contacts", b"bob").unwrap();
+        assert_eq!(tag, a.index_tag("contacts", b"bob").unwrap());
+        assert_ne!(tag, b.index_tag("contacts", b"bob").unwrap());
+        assert_ne!(tag, a.index_tag("messages", b"bob").unwrap());
+    }
+}
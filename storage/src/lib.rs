@@ -0,0 +1,71 @@
+//! ChakChat Local Storage
+//!
+//! Encrypted on-device store for messages, contacts and ratchet state.
+//!
+//! Every row is sealed with XChaCha20-Poly1305 under a key derived from the
+//! user's password (`chakchat_crypto::utils::derive_key_from_password`). The
+//! table name and row ID are bound as associated data, so ciphertexts cannot
+//! be swapped between rows or tables without detection. Lookup columns hold
+//! keyed HMAC tags instead of usernames or conversation IDs.
+
+#![forbid(unsafe_code)]
+#![warn(missing_docs)]
+
+pub mod backup;
+pub mod cipher;
+pub mod migrations;
+pub mod models;
+pub mod store;
+
+pub use backup::RecoveryKey;
+pub use models::{Contact, Message, RatchetState};
+pub use store::Store;
+
+use chakchat_crypto::CryptoError;
+
+/// Storage errors
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    /// SQLite error
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+
+    /// Key derivation or other crypto failure
+    #[error("Crypto error: {0}")]
+    Crypto(#[from] CryptoError),
+
+    /// Password does not match the store
+    #[error("Wrong password")]
+    WrongPassword,
+
+    /// Row failed authentication or could not be decoded
+    #[error("Corrupted row: {0}")]
+    Corrupted(String),
+
+    /// Store was written by a newer schema version
+    #[error("Unsupported schema version: {0}")]
+    UnsupportedSchema(u32),
+
+    /// I/O error while reading or writing a backup
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// Recovery key does not unlock this backup
+    #[error("Wrong recovery key")]
+    WrongRecoveryKey,
+
+    /// Backup ends early or its final chunk is damaged
+    #[error("Backup truncated")]
+    BackupTruncated,
+
+    /// Not a backup, unsupported version or corrupted contents
+    #[error("Invalid backup: {0}")]
+    InvalidBackup(String),
+
+    /// Backups can only be restored into an empty store
+    #[error("Store is not empty")]
+    StoreNotEmpty,
+}
+
+/// Result type for storage operations
+pub type StorageResult<T> = Result<T, StorageError>;
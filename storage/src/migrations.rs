@@ -0,0 +1,115 @@
+//! Schema Migrations
+//!
+//! Migrations run in order inside one transaction; the applied version is
+//! kept in SQLite's `user_version`. Only ciphertext, row IDs and keyed lookup
+//! tags are ever stored in plain columns.
+
+use crate::{StorageError, StorageResult};
+use rusqlite::Connection;
+
+/// Migration scripts; `MIGRATIONS[n]` upgrades from version `n` to `n + 1`
+pub const MIGRATIONS: &[&str] = &[
+    // v1: initial schema
+    "CREATE TABLE meta (
+        name  TEXT PRIMARY KEY,
+        value BLOB NOT NULL
+    );
+    CREATE TABLE messages (
+        id               INTEGER PRIMARY KEY AUTOINCREMENT,
+        conversation_tag BLOB NOT NULL,
+        payload          BLOB NOT NULL
+    );
+    CREATE TABLE contacts (
+        id           INTEGER PRIMARY KEY AUTOINCREMENT,
+        username_tag BLOB NOT NULL UNIQUE,
+        payload      BLOB NOT NULL
+    );
+    CREATE TABLE session_keys (
+        id          INTEGER PRIMARY KEY AUTOINCREMENT,
+        contact_tag BLOB NOT NULL UNIQUE,
+        payload     BLOB NOT NULL
+    );",
+    // v2: conversation lookups
+    "CREATE INDEX idx_messages_conversation ON messages (conversation_tag, id);",
+];
+
+/// Latest schema version
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Current schema version of a database
+pub fn schema_version(conn: &Connection) -> StorageResult<u32> {
+    Ok(conn.pragma_query_value(None, "user_version", |row| row.get(0))?)
+}
+
+/// Apply pending migrations up to `target`
+///
+/// Migrations only run forward; a `target` at or below the current version
+/// leaves the database untouched.
+///
+/// # Returns
+/// Version the database was at before migrating
+pub fn migrate_to(conn: &mut Connection, target: u32) -> StorageResult<u32> {
+    let current = schema_version(conn)?;
+    if current > SCHEMA_VERSION {
+        return Err(StorageError::UnsupportedSchema(current));
+    }
+
+    let target = target.min(SCHEMA_VERSION);
+    if target <= current {
+        return Ok(current);
+    }
+
+    let tx = conn.transaction()?;
+    for script in &MIGRATIONS[current as usize..target as usize] {
+        tx.execute_batch(script)?;
+    }
+    tx.pragma_update(None, "user_version", target)?;
+    tx.commit()?;
+
+    Ok(current)
+}
+
+/// Apply all pending migrations
+pub fn migrate(conn: &mut Connection) -> StorageResult<u32> {
+    migrate_to(conn, SCHEMA_VERSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrates_fresh_and_partial() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert_eq!(migrate_to(&mut conn, 1).unwrap(), 0);
+        assert_eq!(schema_version(&conn).unwrap(), 1);
+
+        assert_eq!(migrate(&mut conn).unwrap(), 1);
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+
+        // Idempotent once up to date
+        assert_eq!(migrate(&mut conn).unwrap(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_older_target_is_a_no_op() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+
+        assert_eq!(migrate_to(&mut conn, 1).unwrap(), SCHEMA_VERSION);
+        assert_eq!(migrate_to(&mut conn, 0).unwrap(), SCHEMA_VERSION);
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_rejects_newer_schema() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .unwrap();
+
+        assert!(matches!(
+            migrate(&mut conn),
+            Err(StorageError::UnsupportedSchema(v)) if v == SCHEMA_VERSION + 1
+        ));
+    }
+}
@@ -0,0 +1,76 @@
+//! Stored Records
+//!
+//! Plaintext forms of the rows; serialized with bincode before sealing.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Chat message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Message {
+    /// Conversation ID (hash of both participants)
+    pub conversation_id: String,
+
+    /// Sender's public key, for verification
+    pub sender_public_key: [u8; 32],
+
+    /// Decrypted message content
+    pub content: Vec<u8>,
+
+    /// Send time (Unix milliseconds)
+    pub timestamp: i64,
+
+    /// Whether the user has read it
+    pub is_read: bool,
+}
+
+/// Contact entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Contact {
+    /// Username (unique)
+    pub username: String,
+
+    /// Contact's X25519 public key
+    pub public_key: [u8; 32],
+
+    /// Identity signature, for verification
+    pub identity_signature: Vec<u8>,
+
+    /// Last seen (Unix milliseconds)
+    pub last_seen: i64,
+
+    /// Manually verified (QR code)
+    pub verified: bool,
+}
+
+/// Double Ratchet state for one contact
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct RatchetState {
+    /// Contact's public key
+    pub contact_public_key: [u8; 32],
+
+    /// Serialized chain key state
+    pub chain_key_state: Vec<u8>,
+
+    /// Messages sent on this session
+    pub message_count: u64,
+
+    /// Creation time (Unix milliseconds)
+    pub created_at: i64,
+
+    /// Last update (Unix milliseconds)
+    pub updated_at: i64,
+}
+
+impl fmt::Debug for RatchetState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RatchetState")
+            .field("contact_public_key", &self.contact_public_key)
+            .field("chain_key_state", &"[REDACTED]")
+            .field("message_count", &self.message_count)
+            .field("created_at", &self.created_at)
+            .field("updated_at", &self.updated_at)
+            .finish()
+    }
+}
@@ -0,0 +1,731 @@
+//! Encrypted Store
+//!
+//! SQLite-backed store for messages, contacts and ratchet state. The salt and
+//! a key-check value live in the `meta` table; everything else is sealed per
+//! row by `RowCipher`.
+//!
+//! Connections run with `secure_delete` on, so deleted rows and ciphertexts
+//! replaced by `rotate_key` are zeroed rather than left in free pages.
+
+use crate::cipher::{RowCipher, SALT_SIZE};
+use crate::migrations;
+use crate::models::{Contact, Message, RatchetState};
+use crate::{StorageError, StorageResult};
+use chakchat_crypto::registry;
+use chakchat_crypto::utils::constant_time_compare;
+use rand::RngCore;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+pub(crate) const MESSAGES: &str = "messages";
+pub(crate) const CONTACTS: &str = "contacts";
+pub(crate) const SESSIONS: &str = "session_keys";
+const META: &str = "meta";
+
+/// Known plaintext sealed in `meta` to detect a wrong password
+const KEY_CHECK: &[u8] = b"chakchat_storage_key_check_v1";
+
+/// Encrypted on-device store
+pub struct Store {
+    pub(crate) conn: Connection,
+    pub(crate) cipher: RowCipher,
+}
+
+impl fmt::Debug for Store {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Store")
+            .field("path", &self.conn.path())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Store {
+    /// Open or create a store
+    ///
+    /// # Arguments
+    /// * `path` - SQLite database file
+    /// * `password` - User password; the storage key is derived from it
+    ///
+    /// # Errors
+    /// * `WrongPassword` - store exists and was created with another password
+    /// * `UnsupportedSchema` - store was written by a newer version
+    pub fn open(path: impl AsRef<Path>, password: &[u8]) -> StorageResult<Self> {
+        let mut conn = Connection::open(path)?;
+        conn.pragma_update(None, "secure_delete", true)?;
+        migrations::migrate(&mut conn)?;
+
+        let cipher = match meta_get(&conn, "salt")? {
+            Some(salt) => {
+                let salt: [u8; SALT_SIZE] = salt
+                    .try_into()
+                    .map_err(|_| StorageError::Corrupted("Invalid salt".to_string()))?;
+                let cipher = RowCipher::from_password(password, &salt)?;
+
+                let check = meta_get(&conn, "key_check")?
+                    .ok_or_else(|| StorageError::Corrupted("Missing key check".to_string()))?;
+                match cipher.open(META, 0, &check) {
+                    Ok(value) if constant_time_compare(&value, KEY_CHECK) => cipher,
+                    _ => return Err(StorageError::WrongPassword),
+                }
+            }
+            None => {
+                let tx = conn.transaction()?;
+                let cipher = write_key_material(&tx, password)?;
+                tx.commit()?;
+                cipher
+            }
+        };
+
+        Ok(Store { conn, cipher })
+    }
+
+    /// Destroy the database files when `chakchat_crypto::registry::panic_wipe()` runs
+    ///
+    /// The hook overwrites the database and its journal, WAL and shared-memory
+    /// files with zeros, then deletes them. The row keys are wiped by the same
+    /// call, so this store fails with `CryptoError::Wiped` afterwards.
+    pub fn destroy_on_panic_wipe(&self) {
+        let path = match self.conn.path() {
+            Some(path) if !path.is_empty() => PathBuf::from(path),
+            _ => return,
+        };
+        registry::add_wipe_hook(move || destroy_files(&path));
+    }
+
+    /// Current schema version
+    pub fn schema_version(&self) -> StorageResult<u32> {
+        migrations::schema_version(&self.conn)
+    }
+
+    /// Whether the store holds no messages, contacts or ratchet state
+    pub fn is_empty(&self) -> StorageResult<bool> {
+        let rows: i64 = self.conn.query_row(
+            "SELECT (SELECT COUNT(*) FROM messages)
+                  + (SELECT COUNT(*) FROM contacts)
+                  + (SELECT COUNT(*) FROM session_keys)",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(rows == 0)
+    }
+
+    /// Store a message
+    ///
+    /// # Returns
+    /// Row ID of the new message
+    pub fn insert_message(&mut self, message: &Message) -> StorageResult<i64> {
+        let tag = self
+            .cipher
+            .index_tag(MESSAGES, message.conversation_id.as_bytes())?;
+        let tx = self.conn.transaction()?;
+        let id = insert_sealed(
+            &tx,
+            &self.cipher,
+            MESSAGES,
+            "conversation_tag",
+            &tag,
+            message,
+        )?;
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// Load one message
+    pub fn message(&self, id: i64) -> StorageResult<Option<Message>> {
+        load_by_id(&self.conn, &self.cipher, MESSAGES, id)
+    }
+
+    /// All messages in a conversation, oldest first
+    pub fn conversation(&self, conversation_id: &str) -> StorageResult<Vec<(i64, Message)>> {
+        let tag = self
+            .cipher
+            .index_tag(MESSAGES, conversation_id.as_bytes())?;
+        load_by_tag(&self.conn, &self.cipher, MESSAGES, "conversation_tag", &tag)
+    }
+
+    /// Mark a message as read
+    pub fn mark_read(&mut self, id: i64) -> StorageResult<bool> {
+        let Some(mut message) = self.message(id)? else {
+            return Ok(false);
+        };
+        message.is_read = true;
+        update_sealed(&self.conn, &self.cipher, MESSAGES, id, &message)?;
+        Ok(true)
+    }
+
+    /// Delete a message
+    pub fn delete_message(&mut self, id: i64) -> StorageResult<bool> {
+        Ok(self
+            .conn
+            .execute("DELETE FROM messages WHERE id = ?1", [id])?
+            > 0)
+    }
+
+    /// Insert or replace a contact (keyed by username)
+    pub fn save_contact(&mut self, contact: &Contact) -> StorageResult<i64> {
+        let tag = self
+            .cipher
+            .index_tag(CONTACTS, contact.username.as_bytes())?;
+        let tx = self.conn.transaction()?;
+        let id = upsert_sealed(&tx, &self.cipher, CONTACTS, "username_tag", &tag, contact)?;
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// Look up a contact by username
+    pub fn contact(&self, username: &str) -> StorageResult<Option<Contact>> {
+        let tag = self.cipher.index_tag(CONTACTS, username.as_bytes())?;
+        Ok(
+            load_by_tag(&self.conn, &self.cipher, CONTACTS, "username_tag", &tag)?
+                .pop()
+                .map(|(_, contact)| contact),
+        )
+    }
+
+    /// All contacts
+    pub fn contacts(&self) -> StorageResult<Vec<Contact>> {
+        Ok(load_all(&self.conn, &self.cipher, CONTACTS)?
+            .into_iter()
+            .map(|(_, contact)| contact)
+            .collect())
+    }
+
+    /// Delete a contact
+    pub fn delete_contact(&mut self, username: &str) -> StorageResult<bool> {
+        let tag = self.cipher.index_tag(CONTACTS, username.as_bytes())?;
+        Ok(self
+            .conn
+            .execute("DELETE FROM contacts WHERE username_tag = ?1", [&tag[..]])?
+            > 0)
+    }
+
+    /// Insert or replace the ratchet state for a contact
+    pub fn save_ratchet_state(&mut self, state: &RatchetState) -> StorageResult<i64> {
+        let tag = self.cipher.index_tag(SESSIONS, &state.contact_public_key)?;
+        let tx = self.conn.transaction()?;
+        let id = upsert_sealed(&tx, &self.cipher, SESSIONS, "contact_tag", &tag, state)?;
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// Load the ratchet state for a contact
+    pub fn ratchet_state(
+        &self,
+        contact_public_key: &[u8; 32],
+    ) -> StorageResult<Option<RatchetState>> {
+        let tag = self.cipher.index_tag(SESSIONS, contact_public_key)?;
+        Ok(
+            load_by_tag(&self.conn, &self.cipher, SESSIONS, "contact_tag", &tag)?
+                .pop()
+                .map(|(_, state)| state),
+        )
+    }
+
+    /// Delete the ratchet state for a contact
+    pub fn delete_ratchet_state(&mut self, contact_public_key: &[u8; 32]) -> StorageResult<bool> {
+        let tag = self.cipher.index_tag(SESSIONS, contact_public_key)?;
+        Ok(self.conn.execute(
+            "DELETE FROM session_keys WHERE contact_tag = ?1",
+            [&tag[..]],
+        )? > 0)
+    }
+
+    /// Change the password and re-encrypt every row under the new key
+    ///
+    /// Runs in a single transaction: on failure the store keeps the old key.
+    /// With `secure_delete` the replaced ciphertexts are zeroed on disk, so
+    /// nothing is left that the old password could open.
+    pub fn rotate_key(&mut self, new_password: &[u8]) -> StorageResult<()> {
+        let tx = self.conn.transaction()?;
+        let new_cipher = write_key_material(&tx, new_password)?;
+
+        rotate_table::<Message>(
+            &tx,
+            &self.cipher,
+            &new_cipher,
+            MESSAGES,
+            "conversation_tag",
+            |m| m.conversation_id.as_bytes().to_vec(),
+        )?;
+        rotate_table::<Contact>(
+            &tx,
+            &self.cipher,
+            &new_cipher,
+            CONTACTS,
+            "username_tag",
+            |c| c.username.as_bytes().to_vec(),
+        )?;
+        rotate_table::<RatchetState>(
+            &tx,
+            &self.cipher,
+            &new_cipher,
+            SESSIONS,
+            "contact_tag",
+            |s| s.contact_public_key.to_vec(),
+        )?;
+
+        tx.commit()?;
+        self.cipher = new_cipher;
+        Ok(())
+    }
+}
+
+/// Overwrite and delete a database file and its sidecars, ignoring errors
+fn destroy_files(path: &Path) {
+    for suffix in ["", "-journal", "-wal", "-shm"] {
+        let mut file_path = path.as_os_str().to_owned();
+        file_path.push(suffix);
+        let file_path = PathBuf::from(file_path);
+
+        if let Ok(mut file) = OpenOptions::new().write(true).open(&file_path) {
+            let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+            let zeros = [0u8; 4096];
+            let mut written = 0u64;
+            while written < len {
+                let n = (len - written).min(zeros.len() as u64) as usize;
+                if file.write_all(&zeros[..n]).is_err() {
+                    break;
+                }
+                written += n as u64;
+            }
+            let _ = file.sync_all();
+        }
+        let _ = fs::remove_file(&file_path);
+    }
+}
+
+/// Write a fresh salt and key check, returning the derived cipher
+fn write_key_material(conn: &Connection, password: &[u8]) -> StorageResult<RowCipher> {
+    let mut salt = [0u8; SALT_SIZE];
+    rand::thread_rng().fill_bytes(&mut salt);
+
+    let cipher = RowCipher::from_password(password, &salt)?;
+    let check = cipher.seal(META, 0, KEY_CHECK)?;
+
+    meta_set(conn, "salt", &salt)?;
+    meta_set(conn, "key_check", &check)?;
+    Ok(cipher)
+}
+
+fn meta_get(conn: &Connection, name: &str) -> StorageResult<Option<Vec<u8>>> {
+    Ok(conn
+        .query_row("SELECT value FROM meta WHERE name = ?1", [name], |row| {
+            row.get(0)
+        })
+        .optional()?)
+}
+
+fn meta_set(conn: &Connection, name: &str, value: &[u8]) -> StorageResult<()> {
+    conn.execute(
+        "INSERT INTO meta (name, value) VALUES (?1, ?2)
+         ON CONFLICT(name) DO UPDATE SET value = excluded.value",
+        params![name, value],
+    )?;
+    Ok(())
+}
+
+fn encode<T: Serialize>(value: &T) -> StorageResult<Zeroizing<Vec<u8>>> {
+    bincode::serialize(value)
+        .map(Zeroizing::new)
+        .map_err(|e| StorageError::Corrupted(e.to_string()))
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> StorageResult<T> {
+    bincode::deserialize(bytes).map_err(|e| StorageError::Corrupted(e.to_string()))
+}
+
+/// Insert a row; the ID is allocated first so it can be bound as AAD
+pub(crate) fn insert_sealed<T: Serialize>(
+    conn: &Connection,
+    cipher: &RowCipher,
+    table: &str,
+    tag_column: &str,
+    tag: &[u8; 32],
+    value: &T,
+) -> StorageResult<i64> {
+    conn.execute(
+        &format!(
+            "INSERT INTO {} ({}, payload) VALUES (?1, x'')",
+            table, tag_column
+        ),
+        [&tag[..]],
+    )?;
+    let id = conn.last_insert_rowid();
+    update_sealed(conn, cipher, table, id, value)?;
+    Ok(id)
+}
+
+pub(crate) fn upsert_sealed<T: Serialize>(
+    conn: &Connection,
+    cipher: &RowCipher,
+    table: &str,
+    tag_column: &str,
+    tag: &[u8; 32],
+    value: &T,
+) -> StorageResult<i64> {
+    let existing: Option<i64> = conn
+        .query_row(
+            &format!("SELECT id FROM {} WHERE {} = ?1", table, tag_column),
+            [&tag[..]],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    match existing {
+        Some(id) => {
+            update_sealed(conn, cipher, table, id, value)?;
+            Ok(id)
+        }
+        None => insert_sealed(conn, cipher, table, tag_column, tag, value),
+    }
+}
+
+fn update_sealed<T: Serialize>(
+    conn: &Connection,
+    cipher: &RowCipher,
+    table: &str,
+    id: i64,
+    value: &T,
+) -> StorageResult<()> {
+    let blob = cipher.seal(table, id, &encode(value)?)?;
+    conn.execute(
+        &format!("UPDATE {} SET payload = ?1 WHERE id = ?2", table),
+        params![blob, id],
+    )?;
+    Ok(())
+}
+
+fn open_rows<T: DeserializeOwned>(
+    cipher: &RowCipher,
+    table: &str,
+    rows: Vec<(i64, Vec<u8>)>,
+) -> StorageResult<Vec<(i64, T)>> {
+    rows.into_iter()
+        .map(|(id, blob)| {
+            let plaintext = Zeroizing::new(cipher.open(table, id, &blob)?);
+            Ok((id, decode(&plaintext)?))
+        })
+        .collect()
+}
+
+fn query_rows(
+    conn: &Connection,
+    sql: &str,
+    param: impl rusqlite::ToSql,
+) -> StorageResult<Vec<(i64, Vec<u8>)>> {
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt
+        .query_map([param], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+fn load_by_id<T: DeserializeOwned>(
+    conn: &Connection,
+    cipher: &RowCipher,
+    table: &str,
+    id: i64,
+) -> StorageResult<Option<T>> {
+    let rows = query_rows(
+        conn,
+        &format!("SELECT id, payload FROM {} WHERE id = ?1", table),
+        id,
+    )?;
+    Ok(open_rows(cipher, table, rows)?
+        .pop()
+        .map(|(_, value)| value))
+}
+
+fn load_by_tag<T: DeserializeOwned>(
+    conn: &Connection,
+    cipher: &RowCipher,
+    table: &str,
+    tag_column: &str,
+    tag: &[u8; 32],
+) -> StorageResult<Vec<(i64, T)>> {
+    let sql = format!(
+        "SELECT id, payload FROM {} WHERE {} = ?1 ORDER BY id",
+        table, tag_column
+    );
+    open_rows(cipher, table, query_rows(conn, &sql, &tag[..])?)
+}
+
+fn load_all<T: DeserializeOwned>(
+    conn: &Connection,
+    cipher: &RowCipher,
+    table: &str,
+) -> StorageResult<Vec<(i64, T)>> {
+    let sql = format!("SELECT id, payload FROM {} WHERE ?1 ORDER BY id", table);
+    open_rows(cipher, table, query_rows(conn, &sql, true)?)
+}
+
+/// Decrypt rows one at a time, oldest first
+pub(crate) fn for_each_row<T: DeserializeOwned>(
+    conn: &Connection,
+    cipher: &RowCipher,
+    table: &str,
+    mut f: impl FnMut(T) -> StorageResult<()>,
+) -> StorageResult<()> {
+    let mut stmt = conn.prepare(&format!("SELECT id, payload FROM {} ORDER BY id", table))?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let id: i64 = row.get(0)?;
+        let blob: Vec<u8> = row.get(1)?;
+        let plaintext = Zeroizing::new(cipher.open(table, id, &blob)?);
+        f(decode(&plaintext)?)?;
+    }
+    Ok(())
+}
+
+/// Re-encrypt every row of a table and recompute its lookup tags
+fn rotate_table<T: Serialize + DeserializeOwned>(
+    conn: &Connection,
+    old: &RowCipher,
+    new: &RowCipher,
+    table: &str,
+    tag_column: &str,
+    tag_value: impl Fn(&T) -> Vec<u8>,
+) -> StorageResult<()> {
+    for (id, value) in load_all::<T>(conn, old, table)? {
+        let tag = new.index_tag(table, &tag_value(&value))?;
+        let blob = new.seal(table, id, &encode(&value)?)?;
+        conn.execute(
+            &format!(
+                "UPDATE {} SET {} = ?1, payload = ?2 WHERE id = ?3",
+                table, tag_column
+            ),
+            params![&tag[..], blob, id],
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn temp_store(password: &[u8]) -> (TempDir, std::path::PathBuf, Store) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chakchat.db");
+        let store = Store::open(&path, password).unwrap();
+        (dir, path, store)
+    }
+
+    fn message(conversation_id: &str, content: &[u8]) -> Message {
+        Message {
+            conversation_id: conversation_id.to_string(),
+            sender_public_key: [7u8; 32],
+            content: content.to_vec(),
+            timestamp: 1_700_000_000_000,
+            is_read: false,
+        }
+    }
+
+    fn contact(username: &str) -> Contact {
+        Contact {
+            username: username.to_string(),
+            public_key: [9u8; 32],
+            identity_signature: vec![1u8; 64],
+            last_seen: 0,
+            verified: false,
+        }
+    }
+
+    #[test]
+    fn test_messages_persist_across_reopen() {
+        let (_dir, path, mut store) = temp_store(b"hunter2");
+        let first = store
+            .insert_message(&message("alice-bob", b"hi bob"))
+            .unwrap();
+        store
+            .insert_message(&message("alice-bob", b"are you there?"))
+            .unwrap();
+        store
+            .insert_message(&message("alice-carol", b"hi carol"))
+            .unwrap();
+        assert!(store.mark_read(first).unwrap());
+        drop(store);
+
+        let store = Store::open(&path, b"hunter2").unwrap();
+        let conversation = store.conversation("alice-bob").unwrap();
+        assert_eq!(conversation.len(), 2);
+        assert_eq!(conversation[0].1.content, b"hi bob".to_vec());
+        assert!(conversation[0].1.is_read);
+        assert!(!conversation[1].1.is_read);
+    }
+
+    #[test]
+    fn test_wrong_password_rejected() {
+        let (_dir, path, store) = temp_store(b"correct horse");
+        drop(store);
+
+        assert!(matches!(
+            Store::open(&path, b"battery staple"),
+            Err(StorageError::WrongPassword)
+        ));
+    }
+
+    #[test]
+    fn test_swapped_rows_detected() {
+        let (_dir, _path, mut store) = temp_store(b"pw");
+        let a = store.insert_message(&message("c", b"first")).unwrap();
+        let b = store.insert_message(&message("c", b"second")).unwrap();
+
+        // Copy row b's ciphertext over row a
+        store
+            .conn
+            .execute(
+                "UPDATE messages SET payload = (SELECT payload FROM messages WHERE id = ?1) WHERE id = ?2",
+                [b, a],
+            )
+            .unwrap();
+
+        assert!(matches!(store.message(a), Err(StorageError::Corrupted(_))));
+        assert_eq!(
+            store.message(b).unwrap().unwrap().content,
+            b"second".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_no_plaintext_on_disk() {
+        let (_dir, path, mut store) = temp_store(b"pw");
+        store
+            .save_contact(&contact("very-unique-username"))
+            .unwrap();
+        store
+            .insert_message(&message("secret-conversation", b"secret-content"))
+            .unwrap();
+        drop(store);
+
+        let raw = std::fs::read(&path).unwrap();
+        for needle in [
+            &b"very-unique-username"[..],
+            b"secret-conversation",
+            b"secret-content",
+        ] {
+            assert!(!raw.windows(needle.len()).any(|w| w == needle));
+        }
+    }
+
+    #[test]
+    fn test_contacts_and_ratchet_state_upsert() {
+        let (_dir, _path, mut store) = temp_store(b"pw");
+
+        let id = store.save_contact(&contact("bob")).unwrap();
+        let mut bob = contact("bob");
+        bob.verified = true;
+        assert_eq!(store.save_contact(&bob).unwrap(), id);
+        assert_eq!(store.contact("bob").unwrap(), Some(bob));
+        assert_eq!(store.contacts().unwrap().len(), 1);
+
+        let mut state = RatchetState {
+            contact_public_key: [9u8; 32],
+            chain_key_state: vec![0xAA; 64],
+            message_count: 1,
+            created_at: 0,
+            updated_at: 0,
+        };
+        store.save_ratchet_state(&state).unwrap();
+        state.message_count = 2;
+        store.save_ratchet_state(&state).unwrap();
+        assert_eq!(
+            store
+                .ratchet_state(&[9u8; 32])
+                .unwrap()
+                .unwrap()
+                .message_count,
+            2
+        );
+
+        assert!(store.delete_ratchet_state(&[9u8; 32]).unwrap());
+        assert!(store.delete_contact("bob").unwrap());
+        assert!(store.ratchet_state(&[9u8; 32]).unwrap().is_none());
+        assert!(store.contact("bob").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_rotate_key_reencrypts_all_rows() {
+        let (_dir, path, mut store) = temp_store(b"old password");
+        store.insert_message(&message("c", b"hello")).unwrap();
+        store.save_contact(&contact("bob")).unwrap();
+        let before: Vec<u8> = store
+            .conn
+            .query_row("SELECT payload FROM messages", [], |row| row.get(0))
+            .unwrap();
+
+        store.rotate_key(b"new password").unwrap();
+        let after: Vec<u8> = store
+            .conn
+            .query_row("SELECT payload FROM messages", [], |row| row.get(0))
+            .unwrap();
+        assert_ne!(before, after);
+        drop(store);
+
+        assert!(matches!(
+            Store::open(&path, b"old password"),
+            Err(StorageError::WrongPassword)
+        ));
+
+        let store = Store::open(&path, b"new password").unwrap();
+        assert_eq!(
+            store.conversation("c").unwrap()[0].1.content,
+            b"hello".to_vec()
+        );
+        assert!(store.contact("bob").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_old_ciphertexts_not_left_on_disk() {
+        let (_dir, path, mut store) = temp_store(b"old password");
+        store.insert_message(&message("c", b"kept")).unwrap();
+        let deleted = store.insert_message(&message("c", b"deleted")).unwrap();
+        let payloads = |store: &Store| -> Vec<Vec<u8>> {
+            let mut stmt = store.conn.prepare("SELECT payload FROM messages").unwrap();
+            let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+            rows.map(Result::unwrap).collect()
+        };
+        let mut old = payloads(&store);
+        old.push(meta_get(&store.conn, "key_check").unwrap().unwrap());
+
+        assert!(store.delete_message(deleted).unwrap());
+        store.rotate_key(b"new password").unwrap();
+        drop(store);
+
+        let raw = std::fs::read(&path).unwrap();
+        for needle in &old {
+            assert!(!raw.windows(needle.len()).any(|w| w == &needle[..]));
+        }
+    }
+
+    #[test]
+    fn test_open_migrates_old_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("old.db");
+
+        let mut conn = Connection::open(&path).unwrap();
+        migrations::migrate_to(&mut conn, 1).unwrap();
+        drop(conn);
+
+        let store = Store::open(&path, b"pw").unwrap();
+        assert_eq!(store.schema_version().unwrap(), migrations::SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_destroy_files_removes_sidecars() {
+        let (dir, path, mut store) = temp_store(b"pw");
+        store.insert_message(&message("c", b"secret")).unwrap();
+        let journal = dir.path().join("chakchat.db-journal");
+        fs::write(&journal, b"journal").unwrap();
+
+        destroy_files(&path);
+        assert!(!path.exists());
+        assert!(!journal.exists());
+    }
+}
@@ -0,0 +1,33 @@
+//! Panic Wipe
+//!
+//! Runs in its own test binary: `panic_wipe()` is process-wide and would
+//! break unrelated tests running in parallel.
+
+use chakchat_crypto::registry;
+use chakchat_crypto::CryptoError;
+use chakchat_storage::{Contact, StorageError, Store};
+
+#[test]
+fn test_panic_wipe_destroys_store() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("chakchat.db");
+    let mut store = Store::open(&path, b"pw").unwrap();
+    store
+        .save_contact(&Contact {
+            username: "bob".to_string(),
+            public_key: [2u8; 32],
+            identity_signature: vec![3u8; 64],
+            last_seen: 1_700_000_000_000,
+            verified: true,
+        })
+        .unwrap();
+    store.destroy_on_panic_wipe();
+
+    registry::panic_wipe();
+
+    assert!(!path.exists());
+    assert!(matches!(
+        store.contact("bob"),
+        Err(StorageError::Crypto(CryptoError::Wiped))
+    ));
+}